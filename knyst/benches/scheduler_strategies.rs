@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use knyst::graph::scheduler_strategy::{Clustered, DepthFirst, Partitioned};
+use knyst::graph::SchedulerStrategy;
+use knyst::{graph::RunGraph, prelude::*};
+
+fn strategies() -> [(&'static str, Arc<dyn SchedulerStrategy>); 3] {
+    [
+        ("depth first", Arc::new(DepthFirst)),
+        ("clustered", Arc::new(Clustered)),
+        ("partitioned", Arc::new(Partitioned)),
+    ]
+}
+
+fn graph_settings(strategy: Arc<dyn SchedulerStrategy>) -> GraphSettings {
+    GraphSettings::default()
+        .block_size(64)
+        .sample_rate(44100.)
+        .num_outputs(2)
+        .num_nodes(2048)
+        .shared_scheduler_strategy(strategy)
+}
+
+fn bench_graph(c: &mut Criterion, name: &str, mut graph: Graph) {
+    graph.update();
+    let resources = Resources::new(ResourcesSettings::default());
+    let (mut run_graph, _, _) =
+        RunGraph::new(&mut graph, resources, RunGraphSettings::default()).unwrap();
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            run_graph.process_block();
+            black_box(run_graph.graph_output_buffers().get_channel(0));
+        });
+    });
+}
+
+/// 256 independent chains of an oscillator followed by three multipliers
+pub fn chains(c: &mut Criterion) {
+    for (strategy_name, strategy) in strategies() {
+        let mut graph = Graph::new(graph_settings(strategy));
+        for _ in 0..256 {
+            let mut node = graph.push(WavetableOscillatorOwned::new(Wavetable::sine()));
+            for _ in 0..3 {
+                let mult = graph.push(Mult);
+                graph.connect(node.to(mult)).unwrap();
+                graph.connect(constant(0.9).to(mult).to_index(1)).unwrap();
+                node = mult;
+            }
+            graph.connect(node.to_graph_out()).unwrap();
+        }
+        bench_graph(c, &format!("256 chains of 4 nodes, {strategy_name}"), graph);
+    }
+}
+
+/// 16 modulators, each modulating the frequency of 32 oscillators that are
+/// each scaled by a multiplier
+pub fn fan_out(c: &mut Criterion) {
+    for (strategy_name, strategy) in strategies() {
+        let mut graph = Graph::new(graph_settings(strategy));
+        for _ in 0..16 {
+            let modulator = graph.push(WavetableOscillatorOwned::new(Wavetable::sine()));
+            graph
+                .connect(constant(2.0).to(modulator).to_label("freq"))
+                .unwrap();
+            for _ in 0..32 {
+                let osc = graph.push(WavetableOscillatorOwned::new(Wavetable::sine()));
+                graph.connect(modulator.to(osc).to_label("freq")).unwrap();
+                let mult = graph.push(Mult);
+                graph.connect(osc.to(mult)).unwrap();
+                graph.connect(modulator.to(mult).to_index(1)).unwrap();
+                graph.connect(mult.to_graph_out()).unwrap();
+            }
+        }
+        bench_graph(
+            c,
+            &format!("16 modulators fanning out to 1024 nodes, {strategy_name}"),
+            graph,
+        );
+    }
+}
+
+/// Planning the order of a graph with many connections, which happens every
+/// time the connections change
+pub fn plan_order(c: &mut Criterion) {
+    for (strategy_name, strategy) in strategies() {
+        let mut graph = Graph::new(graph_settings(strategy));
+        for _ in 0..16 {
+            let modulator = graph.push(WavetableOscillatorOwned::new(Wavetable::sine()));
+            for _ in 0..32 {
+                let osc = graph.push(WavetableOscillatorOwned::new(Wavetable::sine()));
+                graph.connect(modulator.to(osc).to_label("freq")).unwrap();
+                graph.connect(osc.to_graph_out()).unwrap();
+            }
+        }
+        c.bench_function(&format!("plan order of 528 nodes, {strategy_name}"), |b| {
+            b.iter(|| {
+                graph.calculate_node_order().unwrap();
+            });
+        });
+    }
+}
+
+criterion_group!(benches, chains, fan_out, plan_order);
+
+criterion_main!(benches);
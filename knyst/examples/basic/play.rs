@@ -0,0 +1,15 @@
+use anyhow::Result;
+use knyst::prelude::*;
+use knyst::quick::{play, sine};
+
+/// The shortest way to make a sound: `play` starts the default audio backend
+/// and runs the closure to build the graph.
+fn main() -> Result<()> {
+    play(|| graph_output(0, sine().freq(440.) * 0.2))?;
+
+    println!("Playing a sine wave at 440 Hz at an amplitude of 0.2");
+    println!("Press [ENTER] to exit");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(())
+}
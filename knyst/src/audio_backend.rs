@@ -2,7 +2,8 @@
 //! To use the backends in this module you need to enable either the jack or the cpal feature.
 //!
 //! [`JackBackend`] currently has better support including a duplex client with
-//! the same number of inputs and outputs as the [`Graph`].
+//! the same number of inputs and outputs as the [`Graph`]. Its inputs can be
+//! monitored without latency through [`AudioBackend::input_monitor`].
 //!
 //! To use an [`AudioBackend`], first create it to get the parameters of the
 //! system. When you have created your main graph, call
@@ -11,10 +12,20 @@
 //! the backend output buffer with the output of the [`Graph`]. From this point,
 //! the [`Graph`] is considered to be running, meaning changes to the [`Graph`]
 //! may take longer to perform since they involve the audio thread.
+//!
+//! By default, output channel N of the [`Graph`] is sent to channel N of the
+//! backend. An [`OutputChannelMap`] set through
+//! [`AudioBackend::set_output_channel_map`], or
+//! [`SphereSettings::output_channel_map`](crate::sphere::SphereSettings::output_channel_map),
+//! routes the graph outputs to any backend channels instead, e.g. to send a
+//! sub bass feed to channels 3 and 4 of an audio interface.
 
 use crate::{
-    controller::Controller, graph::RunGraphSettings, prelude::MultiThreadedKnystCommands,
-    KnystError,
+    controller::Controller,
+    graph::{InputMonitor, RunGraphSettings},
+    node_buffer::NodeBufferRef,
+    prelude::MultiThreadedKnystCommands,
+    KnystError, Sample,
 };
 #[allow(unused)]
 use crate::{
@@ -66,6 +77,159 @@ pub trait AudioBackend {
     fn native_output_channels(&self) -> Option<usize>;
     /// Get the native number of input channels for this backend, if any
     fn native_input_channels(&self) -> Option<usize>;
+    /// Returns the [`InputMonitor`] for monitoring the inputs on the outputs
+    /// without latency, if the backend is running and supports inputs.
+    fn input_monitor(&self) -> Option<InputMonitor> {
+        None
+    }
+    /// Route the outputs of the graph to the backend channels according to
+    /// `map`. Has to be set before starting the backend. When a map is set,
+    /// the graph should have [`OutputChannelMap::num_graph_outputs`] outputs.
+    ///
+    /// Returns an error if the backend doesn't support output channel maps or
+    /// if the map uses channels that the backend doesn't have.
+    fn set_output_channel_map(&mut self, map: OutputChannelMap) -> Result<(), AudioBackendError> {
+        let _ = map;
+        Err(AudioBackendError::OutputChannelMapUnsupported)
+    }
+}
+
+/// Routes the output channels of a [`Graph`] to the channels of an
+/// [`AudioBackend`], e.g. the channels of an audio interface.
+///
+/// A graph output can be sent to any number of backend channels and several
+/// graph outputs sent to the same backend channel are mixed. Backend channels
+/// that no graph output is routed to are left alone where the backend allows
+/// it, see the documentation of the backend.
+///
+/// # Example
+/// ```
+/// # use knyst::audio_backend::OutputChannelMap;
+/// // Stereo mains on channels 0 and 1, a sub feed on channels 2 and 3 and a
+/// // cue mix on channel 6
+/// let map = OutputChannelMap::new()
+///     .route(0, 0)
+///     .route(1, 1)
+///     .route(2, 2)
+///     .route(2, 3)
+///     .route(3, 6);
+/// assert_eq!(map.num_graph_outputs(), 4);
+/// assert_eq!(map.num_hardware_channels(), 7);
+/// assert!(!map.is_hardware_channel_used(4));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputChannelMap {
+    /// (graph output, hardware channel)
+    routes: Vec<(usize, usize)>,
+}
+
+impl OutputChannelMap {
+    /// Create a map without any routes
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Create a map sending graph output N to backend channel N, which is
+    /// what backends do without a map.
+    pub fn identity(num_channels: usize) -> Self {
+        Self {
+            routes: (0..num_channels)
+                .map(|channel| (channel, channel))
+                .collect(),
+        }
+    }
+    /// Send `graph_output` to `hardware_channel`, in addition to any other
+    /// routes already added for either of them.
+    pub fn route(mut self, graph_output: usize, hardware_channel: usize) -> Self {
+        if !self.routes.contains(&(graph_output, hardware_channel)) {
+            self.routes.push((graph_output, hardware_channel));
+        }
+        self
+    }
+    /// The routes as (graph output, hardware channel) pairs
+    pub fn routes(&self) -> &[(usize, usize)] {
+        &self.routes
+    }
+    /// The number of outputs the graph needs for every route to have a
+    /// source, i.e. the highest graph output routed plus one.
+    pub fn num_graph_outputs(&self) -> usize {
+        self.routes
+            .iter()
+            .map(|(graph_output, _)| graph_output + 1)
+            .max()
+            .unwrap_or(0)
+    }
+    /// The number of backend channels needed for every route to have a
+    /// destination, i.e. the highest backend channel routed to plus one.
+    pub fn num_hardware_channels(&self) -> usize {
+        self.routes
+            .iter()
+            .map(|(_, hardware_channel)| hardware_channel + 1)
+            .max()
+            .unwrap_or(0)
+    }
+    /// Returns true if any graph output is routed to the backend channel
+    pub fn is_hardware_channel_used(&self, hardware_channel: usize) -> bool {
+        self.routes.iter().any(|(_, h)| *h == hardware_channel)
+    }
+    /// The graph outputs routed to every backend channel, see
+    /// [`HardwareChannelSources`]
+    pub fn hardware_channel_sources(&self) -> HardwareChannelSources {
+        let mut sources = vec![vec![]; self.num_hardware_channels()];
+        for &(graph_output, hardware_channel) in &self.routes {
+            sources[hardware_channel].push(graph_output);
+        }
+        HardwareChannelSources { sources }
+    }
+    /// The value of a backend channel at the given frame of the graph
+    /// outputs: the sum of the graph outputs routed to it, or 0 if there are
+    /// none. Graph outputs that don't exist are skipped.
+    #[inline]
+    pub fn hardware_sample(
+        &self,
+        graph_outputs: &NodeBufferRef,
+        hardware_channel: usize,
+        frame: usize,
+    ) -> Sample {
+        let mut value = 0.0;
+        for &(graph_output, h) in &self.routes {
+            if h == hardware_channel && graph_output < graph_outputs.channels() {
+                value += graph_outputs.read(graph_output, frame);
+            }
+        }
+        value
+    }
+}
+
+/// The graph outputs routed to every backend channel of an
+/// [`OutputChannelMap`], for looking them up in an audio callback without
+/// searching through the routes. Backends create it once using
+/// [`OutputChannelMap::hardware_channel_sources`] when the map is set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HardwareChannelSources {
+    /// The graph outputs routed to each backend channel, indexed by backend
+    /// channel
+    sources: Vec<Vec<usize>>,
+}
+
+impl HardwareChannelSources {
+    /// The same as [`OutputChannelMap::hardware_sample`]
+    #[inline]
+    pub fn hardware_sample(
+        &self,
+        graph_outputs: &NodeBufferRef,
+        hardware_channel: usize,
+        frame: usize,
+    ) -> Sample {
+        let mut value = 0.0;
+        if let Some(sources) = self.sources.get(hardware_channel) {
+            for &graph_output in sources {
+                if graph_output < graph_outputs.channels() {
+                    value += graph_outputs.read(graph_output, frame);
+                }
+            }
+        }
+        value
+    }
 }
 
 #[allow(missing_docs)]
@@ -77,6 +241,15 @@ pub enum AudioBackendError {
     BackendNotRunning,
     #[error("Unable to create a node from the Graph: {0}")]
     CouldNotCreateNode(String),
+    #[error("This backend does not support output channel maps.")]
+    OutputChannelMapUnsupported,
+    #[error("The output channel map routes to channel {channel}, but the backend only has {num_channels} channels.")]
+    HardwareChannelOutOfRange { channel: usize, num_channels: usize },
+    #[error("The graph has {graph_outputs} outputs, but the backend expects {expected}.")]
+    WrongNumberOfGraphOutputs {
+        graph_outputs: usize,
+        expected: usize,
+    },
     #[error(transparent)]
     RunGraphError(#[from] crate::graph::run_graph::RunGraphError),
     #[cfg(feature = "jack")]
@@ -101,9 +274,11 @@ pub enum AudioBackendError {
 
 #[cfg(feature = "jack")]
 mod jack_backend {
-    use crate::audio_backend::{AudioBackend, AudioBackendError};
+    use crate::audio_backend::{
+        AudioBackend, AudioBackendError, HardwareChannelSources, OutputChannelMap,
+    };
     use crate::controller::Controller;
-    use crate::graph::{RunGraph, RunGraphSettings};
+    use crate::graph::{InputMonitor, RunGraph, RunGraphSettings};
     use crate::{graph::Graph, Resources};
     use crate::{KnystError, Sample};
     #[cfg(all(debug_assertions, feature = "assert_no_alloc"))]
@@ -114,10 +289,17 @@ mod jack_backend {
     }
 
     /// A backend using JACK
+    ///
+    /// The output ports are named `out_N` after the backend channel N. With an
+    /// [`OutputChannelMap`], ports are only registered for the channels that a
+    /// graph output is routed to, leaving the remaining channels free for
+    /// other JACK clients.
     pub struct JackBackend {
         client: Option<JackClient>,
         sample_rate: usize,
         block_size: usize,
+        output_channel_map: Option<OutputChannelMap>,
+        input_monitor: Option<InputMonitor>,
     }
 
     impl JackBackend {
@@ -132,6 +314,8 @@ mod jack_backend {
                 client: Some(JackClient::Passive(client)),
                 sample_rate,
                 block_size,
+                input_monitor: None,
+                output_channel_map: None,
             })
         }
     }
@@ -153,17 +337,37 @@ mod jack_backend {
                     in_ports
                         .push(client.register_port(&format!("in_{i}"), jack::AudioIn::default())?);
                 }
-                for i in 0..num_outputs {
-                    out_ports.push(
+                if let Some(map) = &self.output_channel_map {
+                    if num_outputs != map.num_graph_outputs() {
+                        return Err(AudioBackendError::WrongNumberOfGraphOutputs {
+                            graph_outputs: num_outputs,
+                            expected: map.num_graph_outputs(),
+                        });
+                    }
+                }
+                let hardware_channels: Vec<usize> = match &self.output_channel_map {
+                    Some(map) => (0..map.num_hardware_channels())
+                        .filter(|&channel| map.is_hardware_channel_used(channel))
+                        .collect(),
+                    None => (0..num_outputs).collect(),
+                };
+                for i in hardware_channels {
+                    out_ports.push((
+                        i,
                         client.register_port(&format!("out_{i}"), jack::AudioOut::default())?,
-                    );
+                    ));
                 }
                 let (run_graph, resources_command_sender, resources_command_receiver) =
                     RunGraph::new(&mut graph, resources, run_graph_settings)?;
+                self.input_monitor = Some(run_graph.input_monitor());
                 let jack_process = JackProcess {
                     run_graph,
                     in_ports,
                     out_ports,
+                    output_channel_sources: self
+                        .output_channel_map
+                        .as_ref()
+                        .map(OutputChannelMap::hardware_channel_sources),
                 };
                 // Activate the client, which starts the processing.
                 let active_client = client
@@ -185,6 +389,7 @@ mod jack_backend {
         fn stop(&mut self) -> Result<(), AudioBackendError> {
             if let Some(JackClient::Active(active_client)) = self.client.take() {
                 active_client.deactivate().unwrap();
+                self.input_monitor = None;
                 Ok(())
             } else {
                 return Err(AudioBackendError::BackendNotRunning);
@@ -206,12 +411,65 @@ mod jack_backend {
         fn native_input_channels(&self) -> Option<usize> {
             None
         }
+
+        fn input_monitor(&self) -> Option<InputMonitor> {
+            self.input_monitor.clone()
+        }
+
+        fn set_output_channel_map(
+            &mut self,
+            map: OutputChannelMap,
+        ) -> Result<(), AudioBackendError> {
+            if let Some(JackClient::Active(_)) = self.client {
+                return Err(AudioBackendError::BackendAlreadyRunning);
+            }
+            self.output_channel_map = Some(map);
+            Ok(())
+        }
     }
 
     struct JackProcess {
         run_graph: RunGraph,
         in_ports: Vec<jack::Port<jack::AudioIn>>,
-        out_ports: Vec<jack::Port<jack::AudioOut>>,
+        /// (backend channel, port)
+        out_ports: Vec<(usize, jack::Port<jack::AudioOut>)>,
+        output_channel_sources: Option<HardwareChannelSources>,
+    }
+
+    impl JackProcess {
+        fn write_outputs(&mut self, ps: &jack::ProcessScope) {
+            let graph_output_buffers = self.run_graph.graph_output_buffers_mut();
+            for i in 0..graph_output_buffers.channels() {
+                let out_buffer = unsafe { graph_output_buffers.get_channel_mut(i) };
+                for sample in out_buffer.iter_mut() {
+                    *sample = sample.clamp(-1.0, 1.0);
+                    if sample.is_nan() {
+                        *sample = 0.0;
+                    }
+                }
+            }
+            let graph_output_buffers = self.run_graph.graph_output_buffers();
+            for (channel, out_port) in self.out_ports.iter_mut() {
+                let out_port_slice = out_port.as_mut_slice(ps);
+                match &self.output_channel_sources {
+                    Some(sources) => {
+                        for (frame, to_jack) in out_port_slice.iter_mut().enumerate() {
+                            // Mixed outputs may exceed the range again
+                            *to_jack = sources
+                                .hardware_sample(graph_output_buffers, *channel, frame)
+                                .clamp(-1.0, 1.0);
+                        }
+                    }
+                    None => {
+                        let out_buffer = graph_output_buffers.get_channel(*channel);
+                        for (to_jack, graph_out) in out_port_slice.iter_mut().zip(out_buffer.iter())
+                        {
+                            *to_jack = *graph_out as f32;
+                        }
+                    }
+                }
+            }
+        }
     }
 
     impl jack::ProcessHandler for JackProcess {
@@ -232,23 +490,7 @@ mod jack_backend {
                     }
                     self.run_graph.run_resources_communication(50);
                     self.run_graph.process_block();
-
-                    let graph_output_buffers = self.run_graph.graph_output_buffers_mut();
-                    for (i, out_port) in self.out_ports.iter_mut().enumerate() {
-                        let out_buffer = unsafe { graph_output_buffers.get_channel_mut(i) };
-                        for sample in out_buffer.iter_mut() {
-                            *sample = sample.clamp(-1.0, 1.0);
-                            if sample.is_nan() {
-                                *sample = 0.0;
-                            }
-                        }
-                        let out_port_slice = out_port.as_mut_slice(ps);
-                        // out_port_slice.clone_from_slice(out_buffer);
-                        for (to_jack, graph_out) in out_port_slice.iter_mut().zip(out_buffer.iter())
-                        {
-                            *to_jack = *graph_out as f32;
-                        }
-                    }
+                    self.write_outputs(ps);
                     jack::Control::Continue
                 })
             }
@@ -265,22 +507,7 @@ mod jack_backend {
                 }
                 self.run_graph.run_resources_communication(50);
                 self.run_graph.process_block();
-
-                let graph_output_buffers = self.run_graph.graph_output_buffers_mut();
-                for (i, out_port) in self.out_ports.iter_mut().enumerate() {
-                    let out_buffer = unsafe { graph_output_buffers.get_channel_mut(i) };
-                    for sample in out_buffer.iter_mut() {
-                        *sample = sample.clamp(-1.0, 1.0);
-                        if sample.is_nan() {
-                            *sample = 0.0;
-                        }
-                    }
-                    let out_port_slice = out_port.as_mut_slice(ps);
-                    // out_port_slice.clone_from_slice(out_buffer);
-                    for (to_jack, graph_out) in out_port_slice.iter_mut().zip(out_buffer.iter()) {
-                        *to_jack = *graph_out as f32;
-                    }
-                }
+                self.write_outputs(ps);
                 jack::Control::Continue
             }
         }
@@ -369,7 +596,9 @@ mod jack_backend {
 /// [`AudioBackend`] implementation for CPAL
 #[cfg(feature = "cpal")]
 pub mod cpal_backend {
-    use crate::audio_backend::{AudioBackend, AudioBackendError};
+    use crate::audio_backend::{
+        AudioBackend, AudioBackendError, HardwareChannelSources, OutputChannelMap,
+    };
     use crate::controller::Controller;
     use crate::graph::{RunGraph, RunGraphSettings};
     use crate::KnystError;
@@ -393,11 +622,18 @@ pub mod cpal_backend {
         }
     }
     /// CPAL backend for convenience. The CPAL backend currently does not support passing on audio inputs from outside the program.
+    ///
+    /// With an [`OutputChannelMap`], device channels that no graph output is
+    /// routed to are filled with silence since the stream owns all channels
+    /// of the device.
     pub struct CpalBackend {
         stream: Option<cpal::Stream>,
         sample_rate: usize,
         config: cpal::SupportedStreamConfig,
         device: cpal::Device,
+        output_channel_map: Option<OutputChannelMap>,
+        /// Created from `output_channel_map` when it is set
+        output_channel_sources: Option<HardwareChannelSources>,
     }
 
     impl CpalBackend {
@@ -425,6 +661,8 @@ pub mod cpal_backend {
                 sample_rate: config.sample_rate().0 as usize,
                 config,
                 device,
+                output_channel_map: None,
+                output_channel_sources: None,
             })
         }
         /// The number of outputs for the device's default output config
@@ -444,8 +682,12 @@ pub mod cpal_backend {
             if self.stream.is_some() {
                 return Err(AudioBackendError::BackendAlreadyRunning);
             }
-            if graph.num_outputs() != self.config.channels() as usize {
-                panic!("CpalBackend expects a graph with the same number of outputs as the device. Check CpalBackend::channels().")
+            let expected_outputs = self.native_output_channels().unwrap_or(0);
+            if graph.num_outputs() != expected_outputs {
+                return Err(AudioBackendError::WrongNumberOfGraphOutputs {
+                    graph_outputs: graph.num_outputs(),
+                    expected: expected_outputs,
+                });
             }
             if graph.num_inputs() > 0 {
                 eprintln!("Warning: CpalBackend currently does not support inputs into the top level Graph.")
@@ -453,17 +695,38 @@ pub mod cpal_backend {
             let (run_graph, resources_command_sender, resources_command_receiver) =
                 RunGraph::new(&mut graph, resources, run_graph_settings)?;
             let config = self.config.clone();
+            let sources = self.output_channel_sources.clone();
             let stream = match self.config.sample_format() {
-                cpal::SampleFormat::F32 => run::<f32>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::I16 => run::<i16>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::U16 => run::<u16>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::I8 => run::<i8>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::I32 => run::<i32>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::I64 => run::<i64>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::U8 => run::<u8>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::U32 => run::<u32>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::U64 => run::<u64>(&self.device, &config.into(), run_graph),
-                cpal::SampleFormat::F64 => run::<f64>(&self.device, &config.into(), run_graph),
+                cpal::SampleFormat::F32 => {
+                    run::<f32>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::I16 => {
+                    run::<i16>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::U16 => {
+                    run::<u16>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::I8 => {
+                    run::<i8>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::I32 => {
+                    run::<i32>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::I64 => {
+                    run::<i64>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::U8 => {
+                    run::<u8>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::U32 => {
+                    run::<u32>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::U64 => {
+                    run::<u64>(&self.device, &config.into(), run_graph, sources)
+                }
+                cpal::SampleFormat::F64 => {
+                    run::<f64>(&self.device, &config.into(), run_graph, sources)
+                }
                 _ => todo!(),
             }?;
             self.stream = Some(stream);
@@ -477,7 +740,14 @@ pub mod cpal_backend {
         }
 
         fn stop(&mut self) -> Result<(), AudioBackendError> {
-            todo!()
+            // Dropping the stream stops it and drops the RunGraph
+            match self.stream.take() {
+                Some(stream) => {
+                    drop(stream);
+                    Ok(())
+                }
+                None => Err(AudioBackendError::BackendNotRunning),
+            }
         }
 
         fn sample_rate(&self) -> usize {
@@ -489,19 +759,41 @@ pub mod cpal_backend {
         }
 
         fn native_output_channels(&self) -> Option<usize> {
-            Some(self.num_outputs())
+            match &self.output_channel_map {
+                Some(map) => Some(map.num_graph_outputs()),
+                None => Some(self.num_outputs()),
+            }
         }
 
         fn native_input_channels(&self) -> Option<usize> {
             // TODO: support duplex streams
             Some(0)
         }
+
+        fn set_output_channel_map(
+            &mut self,
+            map: OutputChannelMap,
+        ) -> Result<(), AudioBackendError> {
+            if self.stream.is_some() {
+                return Err(AudioBackendError::BackendAlreadyRunning);
+            }
+            if map.num_hardware_channels() > self.num_outputs() {
+                return Err(AudioBackendError::HardwareChannelOutOfRange {
+                    channel: map.num_hardware_channels() - 1,
+                    num_channels: self.num_outputs(),
+                });
+            }
+            self.output_channel_sources = Some(map.hardware_channel_sources());
+            self.output_channel_map = Some(map);
+            Ok(())
+        }
     }
 
     fn run<T>(
         device: &cpal::Device,
         config: &cpal::StreamConfig,
         mut run_graph: RunGraph,
+        output_channel_sources: Option<HardwareChannelSources>,
     ) -> Result<cpal::Stream, AudioBackendError>
     where
         T: cpal::Sample + cpal::FromSample<Sample> + cpal::SizedSample + std::fmt::Display,
@@ -531,9 +823,13 @@ pub mod cpal_backend {
                             let buffer = run_graph.graph_output_buffers();
                             // println!("{}", T::from_sample(buffer.read(0, sample_counter)));
                             for (channel_i, out) in frame.iter_mut().enumerate() {
-                                let value: T =
-                                    T::from_sample(buffer.read(channel_i, sample_counter));
-                                *out = value;
+                                let value = match &output_channel_sources {
+                                    Some(sources) => {
+                                        sources.hardware_sample(buffer, channel_i, sample_counter)
+                                    }
+                                    None => buffer.read(channel_i, sample_counter),
+                                };
+                                *out = T::from_sample(value);
                             }
                             sample_counter += 1;
                         }
@@ -550,8 +846,13 @@ pub mod cpal_backend {
                         let buffer = run_graph.graph_output_buffers();
                         // println!("{}", T::from_sample(buffer.read(0, sample_counter)));
                         for (channel_i, out) in frame.iter_mut().enumerate() {
-                            let value: T = T::from_sample(buffer.read(channel_i, sample_counter));
-                            *out = value;
+                            let value = match &output_channel_sources {
+                                Some(sources) => {
+                                    sources.hardware_sample(buffer, channel_i, sample_counter)
+                                }
+                                None => buffer.read(channel_i, sample_counter),
+                            };
+                            *out = T::from_sample(value);
                         }
                         sample_counter += 1;
                     }
@@ -565,3 +866,42 @@ pub mod cpal_backend {
         Ok(stream)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::OutputChannelMap;
+    use crate::{node_buffer::NodeBufferRef, Sample};
+
+    #[test]
+    fn output_channel_map_routes_and_mixes() {
+        let block_size = 2;
+        let mut outputs: Vec<Sample> = vec![0.1, 0.1, 0.2, 0.2, 0.4, 0.4];
+        let buffers = NodeBufferRef::new(outputs.as_mut_ptr(), 3, block_size);
+        let map = OutputChannelMap::new()
+            .route(0, 2)
+            .route(1, 2)
+            .route(1, 3)
+            .route(1, 3)
+            // Graph output 5 doesn't exist and is skipped
+            .route(5, 0);
+        assert_eq!(map.routes().len(), 4);
+        assert_eq!(map.hardware_sample(&buffers, 0, 1), 0.0);
+        assert_eq!(map.hardware_sample(&buffers, 1, 1), 0.0);
+        assert!((map.hardware_sample(&buffers, 2, 1) - 0.3).abs() < 1e-6);
+        assert_eq!(map.hardware_sample(&buffers, 3, 0), 0.2);
+        let sources = map.hardware_channel_sources();
+        for channel in 0..5 {
+            assert_eq!(
+                sources.hardware_sample(&buffers, channel, 1),
+                map.hardware_sample(&buffers, channel, 1)
+            );
+        }
+        let identity = OutputChannelMap::identity(3);
+        for channel in 0..3 {
+            assert_eq!(
+                identity.hardware_sample(&buffers, channel, 0),
+                buffers.read(channel, 0)
+            );
+        }
+    }
+}
@@ -16,15 +16,19 @@ use std::{
 
 use crate::{
     buffer::Buffer,
-    graph::{NodeChanges, ScheduleError, Time},
-    inspection::GraphInspection,
+    gen::activity::{ActivityEvent, ActivityMeter},
+    gen::random::Wobble,
+    graph::{
+        GraphCounts, InputConstant, NodeChanges, NodeDoneEvent, OrderError, ScheduleError, Time,
+    },
+    inspection::{GraphInspection, GraphInspector},
     knyst_commands,
-    resources::{BufferId, ResourcesCommand, ResourcesResponse, WavetableId},
+    resources::{BufferId, ResourcesCommand, ResourcesResponse, ReturnedResources, WavetableId},
     wavetable_aa::Wavetable,
 };
 use crate::{
     graph::{
-        connection::{ConnectionBundle, ConnectionError, InputBundle},
+        connection::{ConnectionBundle, ConnectionError, InputBundle, NodeChannel, NodeInput},
         Connection, FreeError, GenOrGraph, GenOrGraphEnum, Graph, GraphId, GraphSettings, NodeId,
         ParameterChange, SimultaneousChanges,
     },
@@ -32,10 +36,24 @@ use crate::{
     inputs,
     scheduling::MusicalTimeMap,
     time::Beats,
-    KnystError,
+    KnystError, Sample,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 
+/// The number of commands applied by the [`Controller`] before running
+/// maintenance when it is not given an explicit limit.
+pub(crate) const DEFAULT_MAX_COMMANDS_BEFORE_UPDATE: usize = 300;
+/// How long [`Controller::shutdown`] waits for the resources to be returned
+/// after the fade out has finished.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A request for a [`GraphInspection`] to be generated a few nodes at a time
+struct ChunkedInspectionRequest {
+    buffer: GraphInspection,
+    nodes_per_update: usize,
+    sender: std::sync::mpsc::SyncSender<GraphInspection>,
+}
+
 /// Encodes commands sent from a [`KnystCommands`]
 enum Command {
     Push {
@@ -54,11 +72,36 @@ enum Command {
     FreeNodeMendConnections(NodeId),
     ScheduleChange(ParameterChange),
     ScheduleChanges(SimultaneousChanges),
+    ResetInput(NodeInput),
     FreeDisconnectedNodes,
     ResourcesCommand(ResourcesCommand),
     ChangeMusicalTimeMap(Box<dyn FnOnce(&mut MusicalTimeMap) + Send>),
     ScheduleBeatCallback(BeatCallback, StartBeat),
     RequestInspection(std::sync::mpsc::SyncSender<GraphInspection>),
+    RequestChunkedInspection(ChunkedInspectionRequest),
+    RequestCounts(GraphId, std::sync::mpsc::SyncSender<Option<GraphCounts>>),
+    RequestInputConstants(
+        NodeId,
+        std::sync::mpsc::SyncSender<Option<Vec<InputConstant>>>,
+    ),
+    NotifyWhenDone {
+        node: NodeId,
+        sender: Sender<NodeDoneEvent>,
+    },
+    OrderNodes {
+        before: NodeId,
+        after: NodeId,
+    },
+    RemoveNodeOrder {
+        before: NodeId,
+        after: NodeId,
+    },
+    StartEditBatch,
+    CommitEditBatch,
+    WatchActivity {
+        meter: ActivityMeter,
+        sender: Sender<ActivityEvent>,
+    },
 }
 impl std::fmt::Debug for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -84,7 +127,10 @@ impl std::fmt::Debug for Command {
                 .finish(),
             Self::ScheduleChange(arg0) => f.debug_tuple("ScheduleChange").field(arg0).finish(),
             Self::ScheduleChanges(arg0) => f.debug_tuple("ScheduleChanges").field(arg0).finish(),
+            Self::ResetInput(arg0) => f.debug_tuple("ResetInput").field(arg0).finish(),
             Self::FreeDisconnectedNodes => write!(f, "FreeDisconnectedNodes"),
+            Self::StartEditBatch => write!(f, "StartEditBatch"),
+            Self::CommitEditBatch => write!(f, "CommitEditBatch"),
             Self::ResourcesCommand(_arg0) => f.debug_tuple("ResourcesCommand").finish(),
             Self::ChangeMusicalTimeMap(_arg0) => f.debug_tuple("ChangeMusicalTimeMap").finish(),
             Self::ScheduleBeatCallback(_arg0, _arg1) => {
@@ -93,11 +139,39 @@ impl std::fmt::Debug for Command {
             Self::RequestInspection(arg0) => {
                 f.debug_tuple("RequestInspection").field(arg0).finish()
             }
+            Self::RequestCounts(arg0, arg1) => f
+                .debug_tuple("RequestCounts")
+                .field(arg0)
+                .field(arg1)
+                .finish(),
+            Self::RequestInputConstants(arg0, arg1) => f
+                .debug_tuple("RequestInputConstants")
+                .field(arg0)
+                .field(arg1)
+                .finish(),
+            Self::RequestChunkedInspection(arg0) => f
+                .debug_tuple("RequestChunkedInspection")
+                .field(&arg0.nodes_per_update)
+                .finish(),
             Command::SetMortality { node, is_mortal } => f
                 .debug_tuple("SetMortality")
                 .field(node)
                 .field(is_mortal)
                 .finish(),
+            Command::NotifyWhenDone { node, sender: _ } => {
+                f.debug_tuple("NotifyWhenDone").field(node).finish()
+            }
+            Command::OrderNodes { before, after } => f
+                .debug_tuple("OrderNodes")
+                .field(before)
+                .field(after)
+                .finish(),
+            Command::RemoveNodeOrder { before, after } => f
+                .debug_tuple("RemoveNodeOrder")
+                .field(before)
+                .field(after)
+                .finish(),
+            Command::WatchActivity { .. } => f.debug_tuple("WatchActivity").finish(),
         }
     }
 }
@@ -134,6 +208,26 @@ pub trait KnystCommands {
     /// Make several connections at once using any of the ConnectionBundle
     /// notations
     fn connect_bundle(&mut self, bundle: impl Into<ConnectionBundle>);
+    /// Add slow random drift to an input of an existing node by pushing a
+    /// [`Wobble`] to the graph of that node and connecting it to the input.
+    /// The drift is added to the constant value of the input and to any
+    /// other connections. Returns the id of the [`Wobble`] node, which can be
+    /// freed to remove the drift again.
+    fn wobble_input(
+        &mut self,
+        node: NodeId,
+        input: impl Into<NodeChannel>,
+        amount: Sample,
+        rate: Sample,
+    ) -> NodeId {
+        let wobble = self.push_to_graph(
+            Wobble::new(),
+            node.graph_id(),
+            inputs!(("amount": amount), ("rate": rate)),
+        );
+        self.connect(wobble.to(node).to_channel(input));
+        wobble
+    }
     /// Add a new beat callback. See [`BeatCallback`] for documentation.
     fn schedule_beat_callback(
         &mut self,
@@ -144,6 +238,22 @@ pub trait KnystCommands {
     fn disconnect(&mut self, connection: Connection);
     /// Sets the mortality of a node to mortal (true) or immortal (false). An immortal node cannot be freed.
     fn set_mortality(&mut self, node: NodeId, is_mortal: bool);
+    /// Send a [`NodeDoneEvent`] through `sender` when the node is freed,
+    /// either because it freed itself (e.g. an envelope with
+    /// [`StopAction::FreeSelf`](crate::gen::StopAction) finished) or because
+    /// it was freed manually.
+    fn notify_when_done(&mut self, node: NodeId, sender: Sender<NodeDoneEvent>);
+    /// Send an [`ActivityEvent`] through `sender` every time a channel
+    /// watched by the [`ActivityDetector`](crate::gen::activity::ActivityDetector)
+    /// of `meter` falls silent or becomes active again. The channels are
+    /// checked every time the [`Controller`] runs its maintenance. Watching
+    /// stops when the receiver is dropped.
+    fn watch_activity(&mut self, meter: ActivityMeter, sender: Sender<ActivityEvent>);
+    /// Pin `before` to be processed before `after` even if they are not
+    /// connected. See [`Graph::order_nodes`].
+    fn order_nodes(&mut self, before: NodeId, after: NodeId);
+    /// Remove a constraint added using [`KnystCommands::order_nodes`].
+    fn remove_node_order(&mut self, before: NodeId, after: NodeId);
     /// Free any nodes that are not currently connected to the graph's outputs
     /// via any chain of connections.
     fn free_disconnected_nodes(&mut self);
@@ -165,6 +275,10 @@ pub trait KnystCommands {
     /// for them to be sent to the audio thread. If you are getting your
     /// [`KnystCommands`] through `AudioBackend::start_processing` this is taken
     /// care of automatically.
+    ///
+    /// Inside a scheduling bundle, changes at [`Time::Immediately`] become part
+    /// of the bundle. Changes at any other time are scheduled at that time,
+    /// separately from the bundle.
     fn schedule_changes(&mut self, changes: SimultaneousChanges);
     /// Inserts a new buffer in the [`Resources`] and returns an id which can be
     /// converted to a key on the audio thread with access to a [`Resources`].
@@ -187,6 +301,35 @@ pub trait KnystCommands {
     );
     /// Request a [`GraphInspection`] of the top level graph which will be sent back in the returned channel
     fn request_inspection(&mut self) -> std::sync::mpsc::Receiver<GraphInspection>;
+    /// Like [`KnystCommands::request_inspection`], but the [`Controller`]
+    /// inspects at most `nodes_per_update` nodes every time it runs so that
+    /// inspecting a very large graph doesn't delay other commands. The buffers
+    /// of `buffer` are reused, e.g. pass in the previous inspection when
+    /// monitoring a graph continuously, or [`GraphInspection::empty`].
+    ///
+    /// Changes that are made while the inspection is in progress may or may
+    /// not be included in the inspection.
+    fn request_chunked_inspection(
+        &mut self,
+        buffer: GraphInspection,
+        nodes_per_update: usize,
+    ) -> std::sync::mpsc::Receiver<GraphInspection>;
+    /// Request the current number of nodes and connections in a graph
+    /// together with its quotas. `None` is sent back if the graph wasn't found.
+    fn request_counts(
+        &mut self,
+        graph_id: GraphId,
+    ) -> std::sync::mpsc::Receiver<Option<GraphCounts>>;
+    /// Request the input constants of a node together with their defaults,
+    /// see [`Graph::input_constants`]. `None` is sent back if the node wasn't
+    /// found.
+    fn request_input_constants(
+        &mut self,
+        node: NodeId,
+    ) -> std::sync::mpsc::Receiver<Option<Vec<InputConstant>>>;
+    /// Set an input constant back to the default value of its Gen as soon as
+    /// possible, see [`Graph::reset_input`].
+    fn reset_input(&mut self, input: NodeInput);
 
     /// Return the [`GraphSettings`] of the top level graph. This means you
     /// don't have to manually keep track of matching sample rate and block size
@@ -205,8 +348,22 @@ pub trait KnystCommands {
         -> Option<crate::handles::Handle<crate::handles::GraphHandle>>;
     /// Start a scheduling bundle, meaning any change scheduled will not be applied until [`KnystCommands::upload_scheduling_bundle`] is called. Prefer using [`schedule_bundle`] as it is more difficult to misuse.
     fn start_scheduling_bundle(&mut self, time: Time);
+    /// Like [`KnystCommands::start_scheduling_bundle`], but the bundle is scheduled using `latency` instead of the scheduling latency of the sphere. Prefer using [`schedule_bundle_with_latency`].
+    fn start_scheduling_bundle_with_latency(&mut self, time: Time, latency: Duration);
     /// Uploads scheduled changes to the graph and schedules them for the time specified in [`KnystCommands::start_scheduling_bundle`]. Prefer [`schedule_bundle`] to help reinforce scoping and potential thread switches.
     fn upload_scheduling_bundle(&mut self);
+    /// Start a batch of edits, meaning that changes to the nodes and
+    /// connections of the graphs running on the audio thread are held back
+    /// until [`KnystCommands::commit_edit_batch`] is called and then applied
+    /// together at the start of a block. Prefer using [`edit_batch`] as it is
+    /// more difficult to misuse.
+    fn start_edit_batch(&mut self);
+    /// Commit the edits made since [`KnystCommands::start_edit_batch`]. Prefer [`edit_batch`].
+    fn commit_edit_batch(&mut self);
+    /// Returns the shortest scheduling latency at which changes to the top
+    /// level graph can reliably be applied on time. Use it as a lower bound
+    /// when overriding the latency for a bundle.
+    fn minimum_scheduling_latency(&self) -> Duration;
 }
 
 /// Create a new local graph, runs the init function to let you build it, and then uploads it to the active Sphere.
@@ -228,6 +385,35 @@ pub fn schedule_bundle(time: Time, c: impl FnOnce()) {
     knyst_commands().upload_scheduling_bundle();
 }
 
+/// Like [`schedule_bundle`], but overrides the scheduling latency for the
+/// bundle, e.g. to use [`KnystCommands::minimum_scheduling_latency`] for live
+/// triggers or a long latency for sequenced material.
+pub fn schedule_bundle_with_latency(time: Time, latency: Duration, c: impl FnOnce()) {
+    knyst_commands().start_scheduling_bundle_with_latency(time, latency);
+    c();
+    knyst_commands().upload_scheduling_bundle();
+}
+
+/// Applies all edits made in the closure to a graph at the start of the
+/// same block, so that the audio thread never runs a half applied change,
+/// e.g. a new node before it has been connected. See
+/// [`Graph::start_edit_batch`], including for edits spanning inner graphs.
+///
+/// The batch is committed even if the closure panics, so that the graph
+/// doesn't hold back all later changes.
+pub fn edit_batch(c: impl FnOnce()) {
+    /// Commits the edit batch when dropped, also when unwinding
+    struct CommitOnDrop;
+    impl Drop for CommitOnDrop {
+        fn drop(&mut self) {
+            knyst_commands().commit_edit_batch();
+        }
+    }
+    knyst_commands().start_edit_batch();
+    let _commit = CommitOnDrop;
+    c();
+}
+
 #[derive(Clone)]
 /// Multi threaded implementation on KnystCommands, default
 pub struct MultiThreadedKnystCommands {
@@ -244,6 +430,8 @@ pub struct MultiThreadedKnystCommands {
     /// The vec holding changes to be later scheduled as a bundle
     changes_bundle: Vec<NodeChanges>,
     changes_bundle_time: Time,
+    /// Overrides the scheduling latency for the bundle if set
+    changes_bundle_latency: Option<Duration>,
 }
 
 impl KnystCommands for MultiThreadedKnystCommands {
@@ -455,11 +643,13 @@ impl KnystCommands for MultiThreadedKnystCommands {
     /// [`KnystCommands`] through `AudioBackend::start_processing` this is taken
     /// care of automatically.
     fn schedule_changes(&mut self, changes: SimultaneousChanges) {
-        if self.bundle_changes {
+        // Changes with a time of their own are not moved to the time of the bundle
+        if self.bundle_changes && matches!(changes.time, Time::Immediately) {
             self.changes_bundle.extend(changes.changes);
         } else {
             let mut all_node_graphs = vec![];
             let time = changes.time;
+            let latency = changes.latency;
             for c in &changes.changes {
                 if !all_node_graphs.contains(&c.node.graph_id()) {
                     all_node_graphs.push(c.node.graph_id());
@@ -481,7 +671,7 @@ impl KnystCommands for MultiThreadedKnystCommands {
             for changes in change_bundles_per_graph {
                 LOCAL_GRAPH.with_borrow_mut(|g| {
                     if let Some(g) = g.last_mut() {
-                        if let Err(e) = g.schedule_changes(changes, time) {
+                        if let Err(e) = g.schedule_changes_with_latency(changes, time, latency) {
                             // TODO: report error
                             // TODO: recover the gen_or_graph from the PushError
                             eprintln!("Local graph schedule_changes error: {e:?}");
@@ -492,6 +682,7 @@ impl KnystCommands for MultiThreadedKnystCommands {
                             .send(Command::ScheduleChanges(SimultaneousChanges {
                                 time,
                                 changes,
+                                latency,
                             }))
                             .unwrap();
                     }
@@ -605,6 +796,60 @@ impl KnystCommands for MultiThreadedKnystCommands {
         receiver
     }
 
+    fn request_counts(
+        &mut self,
+        graph_id: GraphId,
+    ) -> std::sync::mpsc::Receiver<Option<GraphCounts>> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        self.sender
+            .send(Command::RequestCounts(graph_id, sender))
+            .unwrap();
+        receiver
+    }
+
+    fn request_input_constants(
+        &mut self,
+        node: NodeId,
+    ) -> std::sync::mpsc::Receiver<Option<Vec<InputConstant>>> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        self.sender
+            .send(Command::RequestInputConstants(node, sender))
+            .unwrap();
+        receiver
+    }
+
+    fn reset_input(&mut self, input: NodeInput) {
+        LOCAL_GRAPH.with_borrow_mut(|g| {
+            if let Some(g) = g.last_mut() {
+                if let Err(e) = g.reset_input(input) {
+                    // TODO: report error
+                    eprintln!("{e:?}");
+                }
+            } else {
+                // There is no local graph
+                self.sender.send(Command::ResetInput(input)).unwrap();
+            }
+        });
+    }
+
+    fn request_chunked_inspection(
+        &mut self,
+        buffer: GraphInspection,
+        nodes_per_update: usize,
+    ) -> std::sync::mpsc::Receiver<GraphInspection> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        self.sender
+            .send(Command::RequestChunkedInspection(
+                ChunkedInspectionRequest {
+                    buffer,
+                    nodes_per_update,
+                    sender,
+                },
+            ))
+            .unwrap();
+        receiver
+    }
+
     fn to_graph(&mut self, graph_id: GraphId) {
         self.selected_graph_remote_graph = graph_id;
     }
@@ -616,6 +861,7 @@ impl KnystCommands for MultiThreadedKnystCommands {
     fn start_scheduling_bundle(&mut self, time: Time) {
         self.bundle_changes = true;
         self.changes_bundle_time = time;
+        self.changes_bundle_latency = None;
         if !self.changes_bundle.is_empty() {
             eprintln!(
                 "Warning: Starting a new scheduling bundle before the previous one was scheduled."
@@ -623,15 +869,37 @@ impl KnystCommands for MultiThreadedKnystCommands {
         }
     }
 
+    fn start_scheduling_bundle_with_latency(&mut self, time: Time, latency: Duration) {
+        self.start_scheduling_bundle(time);
+        self.changes_bundle_latency = Some(latency);
+    }
+
     fn upload_scheduling_bundle(&mut self) {
         self.bundle_changes = false;
         let changes = SimultaneousChanges {
             time: self.changes_bundle_time,
             changes: self.changes_bundle.clone(),
+            latency: self.changes_bundle_latency,
         };
         self.schedule_changes(changes);
         self.changes_bundle.clear();
         self.changes_bundle_time = Time::Immediately;
+        self.changes_bundle_latency = None;
+    }
+
+    fn start_edit_batch(&mut self) {
+        self.sender.send(Command::StartEditBatch).unwrap();
+    }
+
+    fn commit_edit_batch(&mut self) {
+        self.sender.send(Command::CommitEditBatch).unwrap();
+    }
+
+    fn minimum_scheduling_latency(&self) -> Duration {
+        crate::graph::minimum_scheduling_latency(
+            self.top_level_graph_settings.block_size,
+            self.top_level_graph_settings.sample_rate,
+        )
     }
 
     fn current_graph(&self) -> GraphId {
@@ -670,6 +938,82 @@ impl KnystCommands for MultiThreadedKnystCommands {
                 .unwrap();
         }
     }
+    fn notify_when_done(&mut self, node: NodeId, sender: Sender<NodeDoneEvent>) {
+        // The node may be in our local graph or remotely. Check local first.
+        let sender = LOCAL_GRAPH.with_borrow_mut(|g| {
+            if let Some(g) = g.last_mut() {
+                match g.notify_when_done(node, sender.clone()) {
+                    Ok(()) => None,
+                    Err(e) => match e {
+                        ScheduleError::GraphNotFound(_) => Some(sender),
+                        _ => {
+                            // TODO: Report this error
+                            eprintln!("Error: {e:?}");
+                            None
+                        }
+                    },
+                }
+            } else {
+                Some(sender)
+            }
+        });
+        if let Some(sender) = sender {
+            self.sender
+                .send(Command::NotifyWhenDone { node, sender })
+                .unwrap();
+        }
+    }
+    fn watch_activity(&mut self, meter: ActivityMeter, sender: Sender<ActivityEvent>) {
+        self.sender
+            .send(Command::WatchActivity { meter, sender })
+            .unwrap();
+    }
+    fn order_nodes(&mut self, before: NodeId, after: NodeId) {
+        // The nodes may be in our local graph or remotely. Check local first.
+        let found_in_local = LOCAL_GRAPH.with_borrow_mut(|g| {
+            if let Some(g) = g.last_mut() {
+                match g.order_nodes(before, after) {
+                    Ok(()) => true,
+                    Err(OrderError::GraphNotFound(_)) => false,
+                    Err(e) => {
+                        // TODO: Report this error
+                        eprintln!("Error: {e:?}");
+                        true
+                    }
+                }
+            } else {
+                false
+            }
+        });
+        if !found_in_local {
+            self.sender
+                .send(Command::OrderNodes { before, after })
+                .unwrap();
+        }
+    }
+    fn remove_node_order(&mut self, before: NodeId, after: NodeId) {
+        // The nodes may be in our local graph or remotely. Check local first.
+        let found_in_local = LOCAL_GRAPH.with_borrow_mut(|g| {
+            if let Some(g) = g.last_mut() {
+                match g.remove_node_order(before, after) {
+                    Ok(()) => true,
+                    Err(OrderError::GraphNotFound(_)) => false,
+                    Err(e) => {
+                        // TODO: Report this error
+                        eprintln!("Error: {e:?}");
+                        true
+                    }
+                }
+            } else {
+                false
+            }
+        });
+        if !found_in_local {
+            self.sender
+                .send(Command::RemoveNodeOrder { before, after })
+                .unwrap();
+        }
+    }
     // /// Create a new Self which pushes to the selected GraphId by default
     // fn to_graph(&self, graph_id: GraphId) -> Self {
     //     let mut k = self.clone();
@@ -769,6 +1113,15 @@ enum CallbackResult {
     Delete,
 }
 
+/// An [`ActivityMeter`] watched by the [`Controller`], see
+/// [`KnystCommands::watch_activity`]
+struct ActivityWatcher {
+    meter: ActivityMeter,
+    sender: Sender<ActivityEvent>,
+    /// Whether each channel was silent the last time it was checked
+    silent: Vec<bool>,
+}
+
 /// Receives commands from one or several [`KnystCommands`] that may be on
 /// different threads, and applies those to a top level [`Graph`].
 pub struct Controller {
@@ -784,6 +1137,13 @@ pub struct Controller {
     command_queue: Vec<(Instant, Command)>,
     error_handler: Box<dyn FnMut(KnystError) + Send>,
     beat_callbacks: Vec<BeatCallback>,
+    /// Chunked inspections waiting to be completed, the first one is in progress
+    chunked_inspections: std::collections::VecDeque<ChunkedInspectionRequest>,
+    inspector: GraphInspector,
+    /// Resources sent back by the RunGraph after a shutdown
+    returned_resources: ReturnedResources,
+    shutdown_complete: bool,
+    activity_watchers: Vec<ActivityWatcher>,
 }
 impl Controller {
     /// Creates a new [`Controller`] taking the top level [`Graph`] to which
@@ -805,6 +1165,11 @@ impl Controller {
             resources_receiver,
             resources_sender,
             beat_callbacks: vec![],
+            chunked_inspections: std::collections::VecDeque::new(),
+            inspector: GraphInspector::new(GraphInspection::empty()),
+            returned_resources: ReturnedResources::default(),
+            shutdown_complete: false,
+            activity_watchers: vec![],
         }
     }
 
@@ -888,6 +1253,9 @@ impl Controller {
                 .top_level_graph
                 .schedule_change(change)
                 .map_err(|e| From::from(e)),
+            Command::ResetInput(input) => {
+                self.top_level_graph.reset_input(input).map_err(From::from)
+            }
             Command::FreeDisconnectedNodes => self
                 .top_level_graph
                 .free_disconnected_nodes()
@@ -913,10 +1281,11 @@ impl Controller {
                 .map_err(|e| From::from(e)),
             Command::ScheduleChanges(changes) => {
                 let changes_clone = changes.clone();
-                match self
-                    .top_level_graph
-                    .schedule_changes(changes.changes, changes.time)
-                {
+                match self.top_level_graph.schedule_changes_with_latency(
+                    changes.changes,
+                    changes.time,
+                    changes.latency,
+                ) {
                     Ok(_) => Ok(()),
                     Err(e) => match e {
                         crate::graph::ScheduleError::GraphNotFound(_node) => {
@@ -948,6 +1317,22 @@ impl Controller {
                 self.beat_callbacks.push(callback);
                 Ok(())
             }
+            Command::RequestCounts(graph_id, sender) => {
+                // The receiver may have been dropped which is fine
+                sender
+                    .send(self.top_level_graph.counts_of_graph(graph_id))
+                    .ok();
+                Ok(())
+            }
+            Command::RequestInputConstants(node, sender) => {
+                // The receiver may have been dropped which is fine
+                sender.send(self.top_level_graph.input_constants(node)).ok();
+                Ok(())
+            }
+            Command::RequestChunkedInspection(request) => {
+                self.chunked_inspections.push_back(request);
+                Ok(())
+            }
             Command::RequestInspection(sender) => {
                 // TODO: Proper error handling
                 sender
@@ -959,6 +1344,37 @@ impl Controller {
                 .top_level_graph
                 .set_node_mortality(node, is_mortal)
                 .map_err(|e| From::from(e)),
+            Command::NotifyWhenDone { node, sender } => self
+                .top_level_graph
+                .notify_when_done(node, sender)
+                .map_err(From::from),
+            Command::OrderNodes { before, after } => self
+                .top_level_graph
+                .order_nodes(before, after)
+                .map_err(From::from),
+            Command::RemoveNodeOrder { before, after } => self
+                .top_level_graph
+                .remove_node_order(before, after)
+                .map_err(From::from),
+            Command::StartEditBatch => {
+                self.top_level_graph.start_edit_batch();
+                Ok(())
+            }
+            Command::CommitEditBatch => {
+                self.top_level_graph.commit_edit_batch();
+                Ok(())
+            }
+            Command::WatchActivity { meter, sender } => {
+                let silent = (0..meter.num_channels())
+                    .map(|channel| meter.is_silent(channel))
+                    .collect();
+                self.activity_watchers.push(ActivityWatcher {
+                    meter,
+                    sender,
+                    silent,
+                });
+                Ok(())
+            }
         };
 
         if let Err(e) = result {
@@ -984,9 +1400,60 @@ impl Controller {
         true
     }
 
+    /// Inspect the next chunk of nodes for the oldest chunked inspection
+    /// request and send the inspection back if it's done.
+    fn run_chunked_inspection(&mut self) {
+        let Some(request) = self.chunked_inspections.front_mut() else {
+            return;
+        };
+        if !self.inspector.in_progress() {
+            let buffer = std::mem::replace(&mut request.buffer, GraphInspection::empty());
+            self.inspector.replace_inspection(buffer);
+        }
+        if self
+            .top_level_graph
+            .inspect_chunk(&mut self.inspector, request.nodes_per_update.max(1))
+        {
+            let request = self.chunked_inspections.pop_front().unwrap();
+            // The receiver may have been dropped which is fine
+            request
+                .sender
+                .send(self.inspector.replace_inspection(GraphInspection::empty()))
+                .ok();
+        }
+    }
+
+    /// Send an [`ActivityEvent`] for every watched channel that has changed
+    /// between silent and active, removing watchers whose receiver has been
+    /// dropped.
+    fn check_activity_watchers(&mut self) {
+        self.activity_watchers.retain_mut(|watcher| {
+            for (channel, was_silent) in watcher.silent.iter_mut().enumerate() {
+                let silent = watcher.meter.is_silent(channel);
+                if silent != *was_silent {
+                    *was_silent = silent;
+                    let event = if silent {
+                        ActivityEvent::Silent { channel }
+                    } else {
+                        ActivityEvent::Active { channel }
+                    };
+                    if watcher.sender.send(event).is_err() {
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+    }
+
     /// Run maintenance tasks: update the graph and run internal maintenance
     fn run_maintenance(&mut self) {
         self.top_level_graph.update();
+        if let Some(e) = self.top_level_graph.take_scheduler_error() {
+            (*self.error_handler)(e.into());
+        }
+        self.run_chunked_inspection();
+        self.check_activity_watchers();
         while let Ok(response) = self.resources_receiver.pop() {
             match response {
                 ResourcesResponse::InsertBuffer(res) => {
@@ -1019,6 +1486,13 @@ impl Controller {
                         (*self.error_handler)(e.into())
                     }
                 }
+                ResourcesResponse::ReturnBuffer(id, buffer) => {
+                    self.returned_resources.buffers.push((id, buffer));
+                }
+                ResourcesResponse::ReturnWavetable(id, wavetable) => {
+                    self.returned_resources.wavetables.push((id, wavetable));
+                }
+                ResourcesResponse::ShutdownComplete => self.shutdown_complete = true,
             }
         }
     }
@@ -1072,30 +1546,102 @@ impl Controller {
             bundle_changes: false,
             changes_bundle: vec![],
             changes_bundle_time: Time::Immediately,
+            changes_bundle_latency: None,
         }
     }
 
     /// Consumes the [`Controller`] and moves it to a new thread where it will `run` in a loop.
     pub fn start_on_new_thread(self) -> MultiThreadedKnystCommands {
+        let (knyst_commands, _controller_thread) = self.start_on_new_thread_with_handle();
+        knyst_commands
+    }
+
+    /// Like [`Controller::start_on_new_thread`], but also returns a
+    /// [`ControllerThread`] through which the thread can be stopped and the
+    /// [`Controller`] taken back, e.g. to shut it down using
+    /// [`Controller::shutdown`]. Dropping the [`ControllerThread`] leaves the
+    /// thread running.
+    pub fn start_on_new_thread_with_handle(self) -> (MultiThreadedKnystCommands, ControllerThread) {
         let top_level_graph_id = self.top_level_graph.id();
         let top_level_graph_settings = self.top_level_graph.graph_settings();
         let mut controller = self;
         let sender = controller.command_sender.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
 
-        std::thread::spawn(move || loop {
-            while !controller.run(300) {}
-            std::thread::sleep(Duration::from_micros(1));
+        let join_handle = std::thread::spawn(move || {
+            while !stop_thread.load(std::sync::atomic::Ordering::Acquire) {
+                while !controller.run(DEFAULT_MAX_COMMANDS_BEFORE_UPDATE) {}
+                std::thread::sleep(Duration::from_micros(1));
+            }
+            controller
         });
 
-        MultiThreadedKnystCommands {
-            sender,
-            top_level_graph_id,
-            top_level_graph_settings,
-            selected_graph_remote_graph: top_level_graph_id,
-            bundle_changes: false,
-            changes_bundle: vec![],
-            changes_bundle_time: Time::Immediately,
+        (
+            MultiThreadedKnystCommands {
+                sender,
+                top_level_graph_id,
+                top_level_graph_settings,
+                selected_graph_remote_graph: top_level_graph_id,
+                bundle_changes: false,
+                changes_bundle: vec![],
+                changes_bundle_time: Time::Immediately,
+                changes_bundle_latency: None,
+            },
+            ControllerThread { stop, join_handle },
+        )
+    }
+
+    /// Fade out the output of the [`RunGraph`](crate::graph::RunGraph) over
+    /// `fade_out` and wait for it to send back all of its buffers and
+    /// wavetables, which are returned so that they can be reused. The output
+    /// of the RunGraph stays silent afterwards.
+    ///
+    /// The RunGraph has to keep running for this to complete. If it doesn't,
+    /// e.g. because the audio backend has already been stopped, this returns
+    /// whatever has been received after waiting for `fade_out` plus
+    /// [`SHUTDOWN_TIMEOUT`].
+    pub fn shutdown(&mut self, fade_out: Duration) -> ReturnedResources {
+        let deadline = Instant::now() + fade_out + SHUTDOWN_TIMEOUT;
+        let mut command = Some(ResourcesCommand::Shutdown { fade_out });
+        self.shutdown_complete = false;
+        while !self.shutdown_complete
+            && Instant::now() < deadline
+            && !self.resources_sender.is_abandoned()
+        {
+            if let Some(c) = command.take() {
+                if let Err(rtrb::PushError::Full(c)) = self.resources_sender.push(c) {
+                    command = Some(c);
+                }
+            }
+            self.run_maintenance();
+            std::thread::sleep(Duration::from_millis(1));
         }
+        std::mem::take(&mut self.returned_resources)
+    }
+}
+
+/// Handle to a thread running a [`Controller`], returned by
+/// [`Controller::start_on_new_thread_with_handle`].
+pub struct ControllerThread {
+    stop: Arc<AtomicBool>,
+    join_handle: std::thread::JoinHandle<Controller>,
+}
+
+impl ControllerThread {
+    /// Stop the thread after it has finished applying the commands it has
+    /// received, and return the [`Controller`].
+    ///
+    /// # Panics
+    /// Panics if the controller thread has panicked.
+    pub fn stop(self) -> Controller {
+        self.try_stop().expect("The Controller thread panicked")
+    }
+    /// Like [`ControllerThread::stop`], but returns the panic payload instead
+    /// of panicking if the controller thread has panicked.
+    pub fn try_stop(self) -> std::thread::Result<Controller> {
+        self.stop.store(true, std::sync::atomic::Ordering::Release);
+        self.join_handle.join()
     }
 }
 
@@ -1108,7 +1654,10 @@ pub fn print_error_handler(e: KnystError) {
 mod tests {
     use super::schedule_bundle;
     use crate as knyst;
-    use crate::{knyst_commands, offline::KnystOffline, prelude::*, trig::once_trig};
+    use crate::{
+        inspection::GraphInspection, knyst_commands, offline::KnystOffline, prelude::*,
+        trig::once_trig,
+    };
 
     // Outputs its input value + 1
     struct OneGen {}
@@ -1188,6 +1737,69 @@ mod tests {
         assert_eq!(o[20], 5.0);
     }
 
+    #[test]
+    fn chunked_inspection() {
+        let mut kt = KnystOffline::new(44100, 64, 0, 1);
+        let nodes: Vec<_> = (0..5).map(|_| one_gen()).collect();
+        graph_output(0, nodes[4]);
+        let inspection = knyst_commands().request_inspection();
+        kt.process_block();
+        let full_inspection = inspection.recv().unwrap();
+        let receiver = knyst_commands().request_chunked_inspection(GraphInspection::empty(), 2);
+        let mut num_updates = 0;
+        let inspection = loop {
+            kt.process_block();
+            num_updates += 1;
+            if let Ok(inspection) = receiver.try_recv() {
+                break inspection;
+            }
+            assert!(num_updates < 10);
+        };
+        assert_eq!(num_updates, full_inspection.nodes.len().div_ceil(2));
+        assert_eq!(inspection.nodes.len(), full_inspection.nodes.len());
+        for (a, b) in inspection.nodes.iter().zip(full_inspection.nodes.iter()) {
+            assert_eq!(a.address, b.address);
+            assert_eq!(a.name, b.name);
+        }
+        assert_eq!(
+            inspection.graph_output_input_edges.len(),
+            full_inspection.graph_output_input_edges.len()
+        );
+    }
+    #[test]
+    fn request_counts() {
+        let mut kt = KnystOffline::new(44100, 64, 0, 1);
+        let graph_id = knyst_commands().current_graph();
+        let node = one_gen();
+        graph_output(0, node);
+        let counts = knyst_commands().request_counts(graph_id);
+        kt.process_block();
+        let counts = counts.recv().unwrap().unwrap();
+        assert_eq!(counts.nodes, 1);
+        assert_eq!(counts.connections, 1);
+        assert_eq!(counts.max_nodes, None);
+    }
+    #[test]
+    fn wobble_input() {
+        let mut kt = KnystOffline::new(44100, 64, 0, 1);
+        let node = one_gen().passthrough(10.0);
+        graph_output(0, node);
+        let node_id = node.node_ids().next().unwrap();
+        knyst_commands().wobble_input(node_id, "passthrough", 0.5, 10.0);
+        let (mut min, mut max) = (Sample::MAX, Sample::MIN);
+        for _ in 0..100 {
+            kt.process_block();
+            for &out in kt.output_channel(0).unwrap() {
+                min = min.min(out);
+                max = max.max(out);
+            }
+        }
+        assert!(
+            min >= 11.0 - 0.5 * 1.25 && max <= 11.0 + 0.5 * 1.25,
+            "{min} {max}"
+        );
+        assert!(max - min > 0.1, "{min} {max}");
+    }
     #[test]
     fn schedule_bundle_inner_graph_test() {
         let sr = 44100;
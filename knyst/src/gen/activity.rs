@@ -0,0 +1,236 @@
+//! Silence detection for keeping an eye on long running patches
+//!
+//! An installation running for weeks may stop making sound because of a bug,
+//! a crashed input device or a buffer that failed to load. [`ActivityDetector`]
+//! watches any number of buses and reports which of them have been silent for
+//! longer than a given duration through an [`ActivityMeter`]. The meter can be
+//! polled from any thread, or passed to
+//! [`KnystCommands::watch_activity`] to have the
+//! [`Controller`](crate::controller::Controller) send an [`ActivityEvent`]
+//! every time a bus falls silent or becomes active again, e.g. to log it or to
+//! restart the patch.
+use std::{
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    time::Seconds,
+    Resources, Sample,
+};
+
+/// A change of activity on a channel watched by an [`ActivityDetector`], sent
+/// by the [`Controller`](crate::controller::Controller), see
+/// [`KnystCommands::watch_activity`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActivityEvent {
+    /// The channel has been silent for the silence duration of the detector
+    Silent {
+        /// The input channel of the [`ActivityDetector`]
+        channel: usize,
+    },
+    /// The channel is no longer silent
+    Active {
+        /// The input channel of the [`ActivityDetector`]
+        channel: usize,
+    },
+}
+
+/// Shared state between an [`ActivityDetector`] and its [`ActivityMeter`]
+struct ActivityShared {
+    /// The number of samples since the last sample above the threshold, per channel
+    silent_samples: Vec<AtomicU64>,
+    /// [`Sample`] stored as bits, set when the detector is initialised
+    sample_rate: AtomicU32,
+    silence_duration: Seconds,
+}
+
+/// Reads the activity of the channels of an [`ActivityDetector`] from any thread.
+#[derive(Clone)]
+pub struct ActivityMeter {
+    shared: Arc<ActivityShared>,
+}
+
+impl ActivityMeter {
+    /// The number of channels watched by the [`ActivityDetector`]
+    pub fn num_channels(&self) -> usize {
+        self.shared.silent_samples.len()
+    }
+    /// The duration after which a channel is considered silent
+    pub fn silence_duration(&self) -> Seconds {
+        self.shared.silence_duration
+    }
+    /// How long the channel has been silent. Updated once per block. Returns
+    /// zero if the channel doesn't exist or the detector hasn't started
+    /// running yet.
+    pub fn silent_for(&self, channel: usize) -> Duration {
+        let sample_rate = Sample::from_bits(self.shared.sample_rate.load(Ordering::Relaxed));
+        match self.shared.silent_samples.get(channel) {
+            Some(samples) if sample_rate > 0.0 => {
+                Duration::from_secs_f64(samples.load(Ordering::Relaxed) as f64 / sample_rate as f64)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+    /// Returns true if the channel has been silent for at least the silence duration
+    pub fn is_silent(&self, channel: usize) -> bool {
+        self.silent_for(channel).as_secs_f64() >= self.shared.silence_duration.to_seconds_f64()
+    }
+    /// Returns true if all channels have been silent for at least the silence duration
+    pub fn all_silent(&self) -> bool {
+        (0..self.num_channels()).all(|channel| self.is_silent(channel))
+    }
+}
+
+/// Watches its input channels for silence. A channel is silent when no sample
+/// has exceeded the threshold for the silence duration. The state of each
+/// channel is read through the [`ActivityMeter`] returned when creating the
+/// [`ActivityDetector`]. Channels start out active, so a channel that is
+/// silent from the start is reported once the silence duration has passed.
+///
+/// *inputs*
+/// 0..N: The signals to watch
+///
+/// *outputs*
+/// 0..N: 1.0 while the channel is silent, otherwise 0.0
+pub struct ActivityDetector {
+    threshold: Sample,
+    silence_samples: u64,
+    silent_samples: Vec<u64>,
+    shared: Arc<ActivityShared>,
+}
+
+impl ActivityDetector {
+    /// Create a new [`ActivityDetector`] watching `num_channels` channels and
+    /// reporting a channel as silent after `silence_duration`, together with
+    /// the [`ActivityMeter`] for reading the state of the channels.
+    pub fn new(num_channels: usize, silence_duration: Seconds) -> (Self, ActivityMeter) {
+        let shared = Arc::new(ActivityShared {
+            silent_samples: (0..num_channels).map(|_| AtomicU64::new(0)).collect(),
+            sample_rate: AtomicU32::new((0.0 as Sample).to_bits()),
+            silence_duration,
+        });
+        (
+            Self {
+                threshold: 0.0001,
+                silence_samples: 0,
+                silent_samples: vec![0; num_channels],
+                shared: shared.clone(),
+            },
+            ActivityMeter { shared },
+        )
+    }
+    /// Set the amplitude at or below which a sample counts as silence. The
+    /// default is 0.0001, i.e. -80 dB.
+    pub fn threshold(mut self, threshold: Sample) -> Self {
+        self.threshold = threshold;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let num_channels = self.silent_samples.len();
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, num_channels, num_channels))
+    }
+}
+
+impl Gen for ActivityDetector {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size() as u64;
+        for (channel, (out, silent_samples)) in ctx
+            .outputs
+            .iter_mut()
+            .zip(self.silent_samples.iter_mut())
+            .enumerate()
+        {
+            let input = ctx.inputs.get_channel(channel);
+            *silent_samples = match input.iter().rposition(|s| s.abs() > self.threshold) {
+                Some(last_active) => block_size - 1 - last_active as u64,
+                None => *silent_samples + block_size,
+            };
+            self.shared.silent_samples[channel].store(*silent_samples, Ordering::Relaxed);
+            let silent = *silent_samples >= self.silence_samples;
+            out.fill(if silent { 1.0 } else { 0.0 });
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        self.silent_samples.len()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.silent_samples.len()
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.silence_samples = self
+            .shared
+            .silence_duration
+            .to_samples(sample_rate as u64)
+            .max(1);
+        self.silent_samples.fill(0);
+        for samples in &self.shared.silent_samples {
+            samples.store(0, Ordering::Relaxed);
+        }
+        self.shared
+            .sample_rate
+            .store(sample_rate.to_bits(), Ordering::Relaxed);
+    }
+
+    fn name(&self) -> &'static str {
+        "ActivityDetector"
+    }
+}
+
+/// Upload an [`ActivityDetector`] watching `num_channels` channels to the
+/// current graph and return a handle to it together with the
+/// [`ActivityMeter`] for reading the state of the channels.
+pub fn activity_detector(
+    num_channels: usize,
+    silence_duration: Seconds,
+) -> (Handle<GenericHandle>, ActivityMeter) {
+    let (detector, meter) = ActivityDetector::new(num_channels, silence_duration);
+    (detector.upload(), meter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActivityDetector;
+    use crate::{gen::testing::GenTester, time::Seconds, Sample};
+
+    const BLOCK_SIZE: usize = 10;
+    const SR: Sample = 100.;
+
+    #[test]
+    fn reports_silence_after_duration() {
+        let (detector, meter) = ActivityDetector::new(2, Seconds::from_seconds_f64(0.5));
+        let mut detector = GenTester::new(detector, BLOCK_SIZE, SR);
+        // Channel 0 is active with one sample per block, channel 1 is silent
+        detector.input_mut(0)[3] = 0.5;
+        for _ in 0..4 {
+            detector.process_block();
+        }
+        assert_eq!(meter.silent_for(1).as_secs_f64(), 0.4);
+        assert!(!meter.is_silent(1));
+        detector.process_block();
+        assert!(meter.is_silent(1));
+        assert_eq!(detector.output(1), [1.0; BLOCK_SIZE]);
+        assert!(!meter.is_silent(0));
+        assert_eq!(meter.silent_for(0).as_secs_f64(), 0.06);
+        assert_eq!(detector.output(0), [0.0; BLOCK_SIZE]);
+        assert!(!meter.all_silent());
+        // Channel 1 becomes active again
+        detector.input_mut(1)[9] = -0.1;
+        detector.process_block();
+        assert!(!meter.is_silent(1));
+        assert_eq!(meter.silent_for(1), std::time::Duration::ZERO);
+    }
+}
@@ -0,0 +1,330 @@
+//! Mixing desk style channel strip
+//!
+//! A [`ChannelStrip`] takes a mono source through gain, a 3-band EQ, mute and
+//! pan to a stereo output, with a mono post-fader send for feeding effects.
+//! Every stage can be bypassed through its own input so that a strip can be
+//! put on every source of a mix and only the stages that are needed used.
+use crate::{
+    db_to_amplitude,
+    gen::{
+        filter::svf::{SvfFilter, SvfFilterType},
+        Gen, GenContext, GenState,
+    },
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+const SHELF_Q: Sample = std::f64::consts::FRAC_1_SQRT_2 as Sample;
+
+/// Channel strip with gain, a 3-band EQ, mute, pan and a post-fader send.
+///
+/// The signal passes through the stages in the order gain, EQ, mute and pan.
+/// The send output is taken after mute, but before pan, so it follows the
+/// fader and is silent when the channel is muted. Gain, mute, pan and send
+/// changes are ramped over a block to avoid clicks.
+///
+/// The EQ consists of a low shelf, a bell and a high shelf filter. Their
+/// frequencies are set at creation, see [`ChannelStrip::low_freq`],
+/// [`ChannelStrip::mid_freq`], [`ChannelStrip::mid_q`] and
+/// [`ChannelStrip::high_freq`]. With all inputs at 0 the strip is panned to
+/// the center, where the constant power pan law puts the input on both
+/// outputs at -3 dB (about 0.707), and the send is silent.
+///
+/// *inputs*
+/// 0. "input": The mono input signal
+/// 1. "gain": The gain in dB
+/// 2. "low_gain": The gain of the low shelf in dB
+/// 3. "mid_gain": The gain of the mid bell in dB
+/// 4. "high_gain": The gain of the high shelf in dB
+/// 5. "pan": Pan position between -1 (left) and 1 (right)
+/// 6. "mute": The channel is muted when > 0
+/// 7. "send": The amplitude of the send output
+/// 8. "gain_bypass": The gain stage is bypassed when > 0
+/// 9. "eq_bypass": The EQ is bypassed when > 0
+/// 10. "pan_bypass": The pan stage is bypassed when > 0, sending the signal
+///     to both outputs unchanged
+///
+/// *outputs*
+/// 0. "left": The left channel of the output
+/// 1. "right": The right channel of the output
+/// 2. "send": The post-fader send
+pub struct ChannelStrip {
+    low: SvfFilter,
+    mid: SvfFilter,
+    high: SvfFilter,
+    low_freq: Sample,
+    mid_freq: Sample,
+    mid_q: Sample,
+    high_freq: Sample,
+    /// The EQ gains in dB the filter coefficients were last calculated for
+    eq_gains: [Sample; 3],
+    sample_rate: Sample,
+    /// Amplitudes at the end of the previous block, ramped from in the next
+    last_gain: Sample,
+    last_mute: Sample,
+    last_pan: [Sample; 2],
+    last_send: Sample,
+}
+
+impl ChannelStrip {
+    /// Create a new channel strip with EQ frequencies of 100 Hz for the low
+    /// shelf, 1 kHz for the bell and 8 kHz for the high shelf.
+    pub fn new() -> Self {
+        let low_freq = 100.;
+        let mid_freq = 1000.;
+        let mid_q = 0.7;
+        let high_freq = 8000.;
+        Self {
+            low: SvfFilter::new(SvfFilterType::LowShelf, low_freq, SHELF_Q, 0.0),
+            mid: SvfFilter::new(SvfFilterType::Bell, mid_freq, mid_q, 0.0),
+            high: SvfFilter::new(SvfFilterType::HighShelf, high_freq, SHELF_Q, 0.0),
+            low_freq,
+            mid_freq,
+            mid_q,
+            high_freq,
+            eq_gains: [0.0; 3],
+            sample_rate: 44100.,
+            last_gain: 1.0,
+            last_mute: 1.0,
+            last_pan: Self::pan_gains(0.0),
+            last_send: 0.0,
+        }
+    }
+    /// Set the corner frequency of the low shelf
+    pub fn low_freq(mut self, freq: Sample) -> Self {
+        self.low_freq = freq;
+        self
+    }
+    /// Set the center frequency of the mid bell
+    pub fn mid_freq(mut self, freq: Sample) -> Self {
+        self.mid_freq = freq;
+        self
+    }
+    /// Set the Q of the mid bell
+    pub fn mid_q(mut self, q: Sample) -> Self {
+        self.mid_q = q;
+        self
+    }
+    /// Set the corner frequency of the high shelf
+    pub fn high_freq(mut self, freq: Sample) -> Self {
+        self.high_freq = freq;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 11, 3))
+    }
+    /// Left and right gain using the same cos/sine pan law as [`PanMonoToStereo`](crate::gen::PanMonoToStereo)
+    fn pan_gains(pan: Sample) -> [Sample; 2] {
+        let pan = pan.clamp(-1.0, 1.0) * 0.5 + 0.5;
+        let radians = pan * std::f64::consts::FRAC_PI_2 as Sample;
+        [radians.cos(), radians.sin()]
+    }
+    fn set_eq_coeffs(&mut self) {
+        let [low_gain, mid_gain, high_gain] = self.eq_gains;
+        self.low
+            .set_coeffs(self.low_freq, SHELF_Q, low_gain, self.sample_rate);
+        self.mid
+            .set_coeffs(self.mid_freq, self.mid_q, mid_gain, self.sample_rate);
+        self.high
+            .set_coeffs(self.high_freq, SHELF_Q, high_gain, self.sample_rate);
+    }
+}
+
+impl Default for ChannelStrip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gen for ChannelStrip {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let inputs = ctx.inputs;
+        let input = inputs.get_channel(0);
+        // Parameters are read at block rate
+        let gain = if inputs.read(8, 0) > 0.0 {
+            1.0
+        } else {
+            db_to_amplitude(inputs.read(1, 0))
+        };
+        let eq_gains = [inputs.read(2, 0), inputs.read(3, 0), inputs.read(4, 0)];
+        if eq_gains != self.eq_gains {
+            self.eq_gains = eq_gains;
+            self.set_eq_coeffs();
+        }
+        let eq_bypass = inputs.read(9, 0) > 0.0;
+        let mute = if inputs.read(6, 0) > 0.0 { 0.0 } else { 1.0 };
+        let pan = if inputs.read(10, 0) > 0.0 {
+            [1.0, 1.0]
+        } else {
+            Self::pan_gains(inputs.read(5, 0))
+        };
+        let send = inputs.read(7, 0);
+
+        let ramp_step = 1.0 / block_size as Sample;
+        for (i, &input) in input.iter().enumerate().take(block_size) {
+            let t = (i + 1) as Sample * ramp_step;
+            let mut v = input * (self.last_gain + (gain - self.last_gain) * t);
+            // The filters always run to keep their state when the EQ is toggled
+            let eq = self
+                .high
+                .process_sample(self.mid.process_sample(self.low.process_sample(v)));
+            if !eq_bypass {
+                v = eq;
+            }
+            v *= self.last_mute + (mute - self.last_mute) * t;
+            let left = self.last_pan[0] + (pan[0] - self.last_pan[0]) * t;
+            let right = self.last_pan[1] + (pan[1] - self.last_pan[1]) * t;
+            ctx.outputs.write(v * left, 0, i);
+            ctx.outputs.write(v * right, 1, i);
+            ctx.outputs
+                .write(v * (self.last_send + (send - self.last_send) * t), 2, i);
+        }
+        self.last_gain = gain;
+        self.last_mute = mute;
+        self.last_pan = pan;
+        self.last_send = send;
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        11
+    }
+
+    fn num_outputs(&self) -> usize {
+        3
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.sample_rate = sample_rate;
+        self.set_eq_coeffs();
+        self.low.reset();
+        self.mid.reset();
+        self.high.reset();
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input",
+            1 => "gain",
+            2 => "low_gain",
+            3 => "mid_gain",
+            4 => "high_gain",
+            5 => "pan",
+            6 => "mute",
+            7 => "send",
+            8 => "gain_bypass",
+            9 => "eq_bypass",
+            10 => "pan_bypass",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "left",
+            1 => "right",
+            2 => "send",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "ChannelStrip"
+    }
+}
+
+/// Upload a [`ChannelStrip`] with the default EQ frequencies to the current graph and return a handle to it.
+pub fn channel_strip() -> Handle<GenericHandle> {
+    ChannelStrip::new().upload()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelStrip;
+    use crate::{gen::testing::GenTester, Sample};
+
+    const BLOCK_SIZE: usize = 64;
+    const SR: Sample = 44100.;
+
+    /// Run a sine through the strip with the given constant inputs and return
+    /// the peak of each output once the ramps and filters have settled.
+    fn peaks(
+        tester: &mut GenTester<ChannelStrip>,
+        freq: Sample,
+        params: &[(usize, Sample)],
+    ) -> [Sample; 3] {
+        for input in 1..11 {
+            tester.set_input(input, 0.0);
+        }
+        for &(input, value) in params {
+            tester.set_input(input, value);
+        }
+        let mut peaks = [0.0 as Sample; 3];
+        for block in 0..200 {
+            for (i, sample) in tester.input_mut(0).iter_mut().enumerate() {
+                let t = (block * BLOCK_SIZE + i) as Sample / SR;
+                *sample = (t * freq * std::f64::consts::TAU as Sample).sin();
+            }
+            tester.process_block();
+            if block > 100 {
+                for (channel, peak) in peaks.iter_mut().enumerate() {
+                    *peak = peak.max(tester.output_peak(channel));
+                }
+            }
+        }
+        peaks
+    }
+
+    #[test]
+    fn stages_and_bypasses() {
+        let mut strip = GenTester::new(ChannelStrip::new(), BLOCK_SIZE, SR);
+        let center = std::f64::consts::FRAC_1_SQRT_2 as Sample;
+        let close = |a: Sample, b: Sample| (a - b).abs() < 0.01;
+        // -3 dB on each side in the center, no send
+        let [l, r, s] = peaks(&mut strip, 1000., &[]);
+        assert!(
+            close(l, center) && close(r, center) && s == 0.0,
+            "{l} {r} {s}"
+        );
+        // Gain and a post-fader send
+        let [l, _, s] = peaks(&mut strip, 1000., &[(1, -6.0), (7, 0.5)]);
+        assert!(
+            close(l, center * 0.5012) && close(s, 0.5012 * 0.5),
+            "{l} {s}"
+        );
+        let [l, _, s] = peaks(&mut strip, 1000., &[(1, -6.0), (7, 0.5), (8, 1.0)]);
+        assert!(close(l, center) && close(s, 0.5), "{l} {s}");
+        // EQ, bypassed and not
+        let [l, ..] = peaks(&mut strip, 1000., &[(3, 12.0)]);
+        assert!(close(l, center * 3.981), "{l}");
+        let [l, ..] = peaks(&mut strip, 1000., &[(3, 12.0), (9, 1.0)]);
+        assert!(close(l, center), "{l}");
+        let [l, ..] = peaks(&mut strip, 50., &[(2, -12.0)]);
+        assert!(l < center * 0.4, "{l}");
+        // Pan, bypassed and not
+        let [l, r, _] = peaks(&mut strip, 1000., &[(5, -1.0)]);
+        assert!(close(l, 1.0) && r < 0.01, "{l} {r}");
+        let [l, r, _] = peaks(&mut strip, 1000., &[(5, -1.0), (10, 1.0)]);
+        assert!(close(l, 1.0) && close(r, 1.0), "{l} {r}");
+        // Mute silences both the outputs and the send
+        let [l, r, s] = peaks(&mut strip, 1000., &[(6, 1.0), (7, 1.0)]);
+        assert!(l == 0.0 && r == 0.0 && s == 0.0, "{l} {r} {s}");
+    }
+
+    #[test]
+    fn send_changes_are_ramped() {
+        let mut strip = GenTester::new(ChannelStrip::new(), BLOCK_SIZE, SR);
+        strip.set_input(0, 1.0);
+        strip.set_input(7, 1.0);
+        strip.process_block();
+        let send = strip.output(2);
+        assert!(send[0] > 0.0 && send[0] < 0.1, "{}", send[0]);
+        assert!(send.windows(2).all(|w| w[1] > w[0]));
+        assert_eq!(send[BLOCK_SIZE - 1], 1.0);
+    }
+}
@@ -0,0 +1,461 @@
+//! Low latency convolution with short impulse responses
+//!
+//! [`ShortConvolver`] is a stereo uniformly partitioned convolver using one
+//! partition per block. It adds no latency on top of the block size which
+//! makes it suitable for cabinet simulation and small rooms, i.e. impulse
+//! responses up to around 200 ms. The impulse response can be switched while
+//! running using a [`ConvolverIrSender`], crossfading between the old and the
+//! new impulse response to avoid clicks.
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use realfft::{num_complex::Complex, ComplexToReal, RealFftPlanner, RealToComplex};
+
+use crate::{
+    gen::{fft::inverse_real_fft, Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+/// The length of impulse response, in seconds, that a [`ShortConvolver`]
+/// reserves space for. Impulse responses switched to using a
+/// [`ConvolverIrSender`] are truncated to this length or to the length of the
+/// initial impulse response, whichever is longer.
+pub const MAX_SHORT_IR_SECONDS: Sample = 0.2;
+
+/// Error from switching the impulse response of a [`ShortConvolver`]
+#[derive(thiserror::Error, Debug)]
+pub enum ConvolverError {
+    /// The [`ShortConvolver`] has not been initialised by a graph yet so the
+    /// partition size is unknown.
+    #[error("The ShortConvolver has not been added to a graph yet.")]
+    NotInitialized,
+    /// Too many impulse responses have been sent without the
+    /// [`ShortConvolver`] processing them.
+    #[error("The queue of impulse responses is full.")]
+    QueueFull,
+}
+
+/// An impulse response split into partitions in the frequency domain
+struct IrSpectra {
+    partition_size: usize,
+    /// Indexed by `[channel][partition][bin]`. The spectra are pre-scaled to
+    /// compensate for the unnormalised inverse FFT.
+    channels: [Vec<Vec<Complex<Sample>>>; 2],
+}
+
+impl IrSpectra {
+    fn new(left: &[Sample], right: &[Sample], partition_size: usize) -> Self {
+        let fft_size = partition_size * 2;
+        let fft = RealFftPlanner::<Sample>::new().plan_fft_forward(fft_size);
+        let num_partitions = left.len().max(right.len()).div_ceil(partition_size);
+        let num_partitions = num_partitions.max(1);
+        let mut input = fft.make_input_vec();
+        let mut scratch = fft.make_scratch_vec();
+        let mut transform = |ir: &[Sample]| -> Vec<Vec<Complex<Sample>>> {
+            (0..num_partitions)
+                .map(|partition| {
+                    input.fill(0.0);
+                    let start = (partition * partition_size).min(ir.len());
+                    let end = ((partition + 1) * partition_size).min(ir.len());
+                    input[..end - start].copy_from_slice(&ir[start..end]);
+                    let mut spectrum = fft.make_output_vec();
+                    fft.process_with_scratch(&mut input, &mut spectrum, &mut scratch)
+                        .expect("buffers are created by the fft");
+                    for bin in &mut spectrum {
+                        *bin /= fft_size as Sample;
+                    }
+                    spectrum
+                })
+                .collect()
+        };
+        let channels = [transform(left), transform(right)];
+        Self {
+            partition_size,
+            channels,
+        }
+    }
+    fn num_partitions(&self) -> usize {
+        self.channels[0].len()
+    }
+}
+
+/// Buffers used for summing the partitions and transforming the sum back to
+/// the time domain
+struct ConvolutionBuffers {
+    accumulator: Vec<Complex<Sample>>,
+    scratch: Vec<Complex<Sample>>,
+}
+
+impl ConvolutionBuffers {
+    /// Convolve the input spectra of `channel` in the frequency domain delay
+    /// line with `ir`, writing the result to `output`
+    fn convolve(
+        &mut self,
+        ir: &IrSpectra,
+        channel: usize,
+        fdl: &[Vec<Complex<Sample>>],
+        fdl_position: usize,
+        ifft: &dyn ComplexToReal<Sample>,
+        output: &mut [Sample],
+    ) {
+        self.accumulator.fill(Complex::new(0.0, 0.0));
+        let num_partitions = ir.num_partitions().min(fdl.len());
+        for (partition, ir_spectrum) in ir.channels[channel].iter().enumerate().take(num_partitions)
+        {
+            let input_spectrum = &fdl[(fdl_position + fdl.len() - partition) % fdl.len()];
+            for ((acc, x), h) in self
+                .accumulator
+                .iter_mut()
+                .zip(input_spectrum.iter())
+                .zip(ir_spectrum.iter())
+            {
+                *acc += x * h;
+            }
+        }
+        inverse_real_fft(ifft, &mut self.accumulator, output, &mut self.scratch);
+    }
+}
+
+/// Switches the impulse response of a running [`ShortConvolver`] without
+/// blocking or allocating on the audio thread. The frequency domain
+/// representation of the impulse response is calculated on the thread calling
+/// [`ConvolverIrSender::set_ir`].
+pub struct ConvolverIrSender {
+    ir_producer: rtrb::Producer<Box<IrSpectra>>,
+    /// Impulse responses no longer in use, returned so that they can be dropped outside of the audio thread
+    garbage_consumer: rtrb::Consumer<Box<IrSpectra>>,
+    partition_size: Arc<AtomicUsize>,
+}
+
+impl ConvolverIrSender {
+    /// Crossfade to a new stereo impulse response. For a mono impulse
+    /// response, pass the same slice for both channels.
+    ///
+    /// # Errors
+    /// Returns an error if the [`ShortConvolver`] has not yet been added to a
+    /// graph or if it hasn't processed the impulse responses sent previously.
+    pub fn set_ir(&mut self, left: &[Sample], right: &[Sample]) -> Result<(), ConvolverError> {
+        while self.garbage_consumer.pop().is_ok() {}
+        let partition_size = self.partition_size.load(Ordering::SeqCst);
+        if partition_size == 0 {
+            return Err(ConvolverError::NotInitialized);
+        }
+        if self.ir_producer.is_full() {
+            return Err(ConvolverError::QueueFull);
+        }
+        let ir = Box::new(IrSpectra::new(left, right, partition_size));
+        self.ir_producer
+            .push(ir)
+            .map_err(|_| ConvolverError::QueueFull)
+    }
+}
+
+/// Stereo convolver for short impulse responses, e.g. cabinet simulation.
+/// Adds no latency on top of the block size of the graph.
+///
+/// Create it with [`ShortConvolver::new`] which also returns a
+/// [`ConvolverIrSender`] for switching the impulse response with a crossfade.
+///
+/// *inputs*
+/// 0. "input_left": The left channel of the signal to convolve
+/// 1. "input_right": The right channel of the signal to convolve
+///
+/// *outputs*
+/// 0. "output_left": The left channel of the convolved signal
+/// 1. "output_right": The right channel of the convolved signal
+pub struct ShortConvolver {
+    initial_ir: Option<[Vec<Sample>; 2]>,
+    ir: Option<Box<IrSpectra>>,
+    next_ir: Option<Box<IrSpectra>>,
+    ir_consumer: rtrb::Consumer<Box<IrSpectra>>,
+    garbage_producer: rtrb::Producer<Box<IrSpectra>>,
+    partition_size: Arc<AtomicUsize>,
+    crossfade_time: Sample,
+    crossfade_samples: usize,
+    crossfade_position: usize,
+    fft: Option<Arc<dyn RealToComplex<Sample>>>,
+    ifft: Option<Arc<dyn ComplexToReal<Sample>>>,
+    /// The last two partitions of input per channel
+    input_windows: [Vec<Sample>; 2],
+    /// Frequency domain delay line of input spectra, indexed by `[channel][partition][bin]`
+    fdl: [Vec<Vec<Complex<Sample>>>; 2],
+    fdl_position: usize,
+    fft_input: Vec<Sample>,
+    buffers: ConvolutionBuffers,
+    ifft_outputs: [Vec<Sample>; 2],
+}
+
+impl ShortConvolver {
+    /// Create a new convolver from a stereo impulse response. For a mono
+    /// impulse response, pass the same slice for both channels.
+    ///
+    /// The impulse response is transformed when the convolver is added to a
+    /// graph since the partition size depends on the block size.
+    pub fn new(left_ir: &[Sample], right_ir: &[Sample]) -> (Self, ConvolverIrSender) {
+        let (ir_producer, ir_consumer) = rtrb::RingBuffer::new(4);
+        // Room for every impulse response in the queue as well as the one currently playing
+        let (garbage_producer, garbage_consumer) = rtrb::RingBuffer::new(8);
+        let partition_size = Arc::new(AtomicUsize::new(0));
+        let convolver = Self {
+            initial_ir: Some([left_ir.to_vec(), right_ir.to_vec()]),
+            ir: None,
+            next_ir: None,
+            ir_consumer,
+            garbage_producer,
+            partition_size: partition_size.clone(),
+            crossfade_time: 0.02,
+            crossfade_samples: 1,
+            crossfade_position: 0,
+            fft: None,
+            ifft: None,
+            input_windows: [vec![], vec![]],
+            fdl: [vec![], vec![]],
+            fdl_position: 0,
+            fft_input: vec![],
+            buffers: ConvolutionBuffers {
+                accumulator: vec![],
+                scratch: vec![],
+            },
+            ifft_outputs: [vec![], vec![]],
+        };
+        let sender = ConvolverIrSender {
+            ir_producer,
+            garbage_consumer,
+            partition_size,
+        };
+        (convolver, sender)
+    }
+    /// Set the time in seconds to crossfade between impulse responses. Default: 0.02
+    #[must_use]
+    pub fn crossfade_time(mut self, seconds: Sample) -> Self {
+        self.crossfade_time = seconds;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 2, 2))
+    }
+}
+
+impl Gen for ShortConvolver {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let (Some(fft), Some(ifft)) = (&self.fft, &self.ifft) else {
+            ctx.outputs.fill(0.0);
+            return GenState::Continue;
+        };
+        let partition_size = self.partition_size.load(Ordering::SeqCst);
+        // Only start a crossfade if the current impulse response can be returned
+        if self.next_ir.is_none() && self.garbage_producer.slots() > 0 {
+            if let Ok(ir) = self.ir_consumer.pop() {
+                if ir.partition_size == partition_size {
+                    self.next_ir = Some(ir);
+                    self.crossfade_position = 0;
+                } else {
+                    self.garbage_producer.push(ir).ok();
+                }
+            }
+        }
+        let Some(ir) = &self.ir else {
+            ctx.outputs.fill(0.0);
+            return GenState::Continue;
+        };
+        let block_size = ctx.block_size();
+        // A partial block at the start of the node's life is treated as a full
+        // block preceded by silence to keep the partitions aligned.
+        let offset = partition_size - block_size;
+        self.fdl_position = (self.fdl_position + 1) % self.fdl[0].len();
+        let crossfade_start = self.crossfade_position;
+        for channel in 0..2 {
+            let window = &mut self.input_windows[channel];
+            window.copy_within(partition_size.., 0);
+            window[partition_size..partition_size + offset].fill(0.0);
+            window[partition_size + offset..].copy_from_slice(ctx.inputs.get_channel(channel));
+            self.fft_input.copy_from_slice(window);
+            fft.process_with_scratch(
+                &mut self.fft_input,
+                &mut self.fdl[channel][self.fdl_position],
+                &mut self.buffers.scratch,
+            )
+            .expect("buffers are created by the fft");
+            let [output, next_output] = &mut self.ifft_outputs;
+            self.buffers.convolve(
+                ir,
+                channel,
+                &self.fdl[channel],
+                self.fdl_position,
+                ifft.as_ref(),
+                output,
+            );
+            let output = &output[partition_size + offset..];
+            if let Some(next_ir) = &self.next_ir {
+                self.buffers.convolve(
+                    next_ir,
+                    channel,
+                    &self.fdl[channel],
+                    self.fdl_position,
+                    ifft.as_ref(),
+                    next_output,
+                );
+                let next_output = &next_output[partition_size + offset..];
+                for (i, (&old, &new)) in output.iter().zip(next_output.iter()).enumerate() {
+                    let fade = ((crossfade_start + i) as Sample / self.crossfade_samples as Sample)
+                        .min(1.0);
+                    ctx.outputs.write(old + (new - old) * fade, channel, i);
+                }
+            } else {
+                for (i, &value) in output.iter().enumerate() {
+                    ctx.outputs.write(value, channel, i);
+                }
+            }
+        }
+        if self.next_ir.is_some() {
+            self.crossfade_position += block_size;
+            if self.crossfade_position >= self.crossfade_samples {
+                let old_ir = std::mem::replace(&mut self.ir, self.next_ir.take());
+                if let Some(old_ir) = old_ir {
+                    // There is always room since we checked before starting the crossfade
+                    self.garbage_producer.push(old_ir).ok();
+                }
+            }
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+
+    fn init(&mut self, block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        let partition_size = block_size;
+        let fft_size = partition_size * 2;
+        let mut planner = RealFftPlanner::<Sample>::new();
+        let fft = planner.plan_fft_forward(fft_size);
+        let ifft = planner.plan_fft_inverse(fft_size);
+        if let Some([left, right]) = self.initial_ir.take() {
+            self.ir = Some(Box::new(IrSpectra::new(&left, &right, partition_size)));
+        }
+        let ir_partitions = self.ir.as_ref().map_or(1, |ir| ir.num_partitions());
+        let max_partitions =
+            ((MAX_SHORT_IR_SECONDS * sample_rate) as usize).div_ceil(partition_size);
+        let fdl_len = ir_partitions.max(max_partitions);
+        for channel in 0..2 {
+            self.input_windows[channel] = vec![0.0; fft_size];
+            self.fdl[channel] = vec![fft.make_output_vec(); fdl_len];
+            self.ifft_outputs[channel] = ifft.make_output_vec();
+        }
+        self.fdl_position = 0;
+        self.fft_input = fft.make_input_vec();
+        self.buffers = ConvolutionBuffers {
+            accumulator: fft.make_output_vec(),
+            scratch: vec![
+                Complex::new(0.0, 0.0);
+                fft.get_scratch_len().max(ifft.get_scratch_len())
+            ],
+        };
+        self.crossfade_samples = ((self.crossfade_time * sample_rate) as usize).max(1);
+        self.fft = Some(fft);
+        self.ifft = Some(ifft);
+        self.partition_size.store(partition_size, Ordering::SeqCst);
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input_left",
+            1 => "input_right",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "output_left",
+            1 => "output_right",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "ShortConvolver"
+    }
+}
+
+/// Upload a [`ShortConvolver`] to the current graph, returning a handle to it
+/// and a [`ConvolverIrSender`] for switching the impulse response.
+pub fn short_convolver(
+    left_ir: &[Sample],
+    right_ir: &[Sample],
+) -> (Handle<GenericHandle>, ConvolverIrSender) {
+    let (convolver, sender) = ShortConvolver::new(left_ir, right_ir);
+    (convolver.upload(), sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShortConvolver;
+    use crate::{gen::testing::GenTester, Sample};
+
+    const BLOCK_SIZE: usize = 16;
+
+    /// Process `input` (the same for both channels) block by block, returning the left output
+    fn run(tester: &mut GenTester<ShortConvolver>, input: &[Sample]) -> Vec<Sample> {
+        let mut output = vec![];
+        for block in input.chunks(BLOCK_SIZE) {
+            tester.input_mut(0).copy_from_slice(block);
+            tester.input_mut(1).copy_from_slice(block);
+            tester.process_block();
+            output.extend_from_slice(tester.output(0));
+        }
+        output
+    }
+
+    #[test]
+    fn convolves_an_impulse_into_the_ir() {
+        let ir: Vec<Sample> = (0..50).map(|i| 1.0 - i as Sample * 0.02).collect();
+        let (convolver, _sender) = ShortConvolver::new(&ir, &ir);
+        let mut tester = GenTester::new(convolver, BLOCK_SIZE, 44100.);
+        let mut input = vec![0.0; 128];
+        input[5] = 1.0;
+        input[40] = 0.5;
+        let output = run(&mut tester, &input);
+        for (i, &o) in output.iter().enumerate() {
+            let mut expected = 0.0;
+            if i >= 5 && i - 5 < ir.len() {
+                expected += ir[i - 5];
+            }
+            if i >= 40 && i - 40 < ir.len() {
+                expected += ir[i - 40] * 0.5;
+            }
+            assert!((o - expected).abs() < 0.0001, "{i}: {o} != {expected}");
+        }
+    }
+
+    #[test]
+    fn crossfades_to_a_new_ir() {
+        let (convolver, mut sender) = ShortConvolver::new(&[1.0], &[1.0]);
+        let convolver = convolver.crossfade_time(64. / 44100.);
+        assert!(sender.set_ir(&[0.5], &[0.5]).is_err());
+        let mut tester = GenTester::new(convolver, BLOCK_SIZE, 44100.);
+        let input = vec![1.0; 32];
+        let output = run(&mut tester, &input);
+        assert!(output.iter().all(|&o| (o - 1.0).abs() < 0.0001));
+        sender.set_ir(&[0.5], &[0.5]).unwrap();
+        let input = vec![1.0; 128];
+        let output = run(&mut tester, &input);
+        assert!((output[0] - 1.0).abs() < 0.0001);
+        // Crossfading smoothly
+        for pair in output[..64].windows(2) {
+            assert!(pair[1] <= pair[0] && pair[0] - pair[1] < 0.01);
+        }
+        assert!(output[64..].iter().all(|&o| (o - 0.5).abs() < 0.0001));
+    }
+}
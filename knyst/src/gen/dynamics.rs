@@ -1,2 +1,3 @@
 //! [`Gen`](super::Gen)s for dynamics e.g. compressors and limiters
+pub mod multiband_compressor;
 pub mod randja_compressor;
@@ -0,0 +1,170 @@
+//! Stereo multiband compressor
+//!
+//! Splits the signal into bands using a [`LinkwitzRileyCrossover`] per channel
+//! and compresses each band separately using a [`RandjaCompressor`] before
+//! summing the bands again. The left and right channels are linked within each
+//! band to keep the stereo image stable, making it suitable for the top level
+//! output of a mastering chain.
+use crate::{
+    gen::{filter::linkwitz_riley::LinkwitzRileyCrossover, Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+use super::randja_compressor::RandjaCompressor;
+
+/// Stereo multiband compressor with one [`RandjaCompressor`] per band. The
+/// crossover frequencies are set at creation and the compressor for each band
+/// is configured through [`MultibandCompressor::band_mut`].
+///
+/// *inputs*
+/// 0. "input_left": The left channel of the signal to compress
+/// 1. "input_right": The right channel of the signal to compress
+///
+/// *outputs*
+/// 0. "output_left": The left channel of the compressed signal
+/// 1. "output_right": The right channel of the compressed signal
+pub struct MultibandCompressor {
+    crossovers: [LinkwitzRileyCrossover; 2],
+    compressors: Vec<RandjaCompressor>,
+    bands_left: Vec<Sample>,
+    bands_right: Vec<Sample>,
+}
+
+impl MultibandCompressor {
+    /// Create a new multiband compressor with `crossover_freqs.len() + 1`
+    /// bands. The compressors start out with the default settings of
+    /// [`RandjaCompressor::new`], i.e. no compression.
+    pub fn new(crossover_freqs: &[Sample]) -> Self {
+        let crossover = LinkwitzRileyCrossover::new(crossover_freqs);
+        let num_bands = crossover.num_bands();
+        Self {
+            crossovers: [crossover.clone(), crossover],
+            compressors: (0..num_bands).map(|_| RandjaCompressor::new()).collect(),
+            bands_left: vec![0.0; num_bands],
+            bands_right: vec![0.0; num_bands],
+        }
+    }
+    /// The number of bands the signal is split into
+    pub fn num_bands(&self) -> usize {
+        self.compressors.len()
+    }
+    /// Returns the compressor of a band for changing its settings. Band 0 is
+    /// the lowest band.
+    ///
+    /// # Panics
+    /// If `band` is not smaller than [`MultibandCompressor::num_bands`]
+    pub fn band_mut(&mut self, band: usize) -> &mut RandjaCompressor {
+        &mut self.compressors[band]
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 2, 2))
+    }
+}
+
+impl Gen for MultibandCompressor {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let input_left = ctx.inputs.get_channel(0);
+        let input_right = ctx.inputs.get_channel(1);
+        let [crossover_left, crossover_right] = &mut self.crossovers;
+        for i in 0..block_size {
+            crossover_left.process_sample(input_left[i], &mut self.bands_left);
+            crossover_right.process_sample(input_right[i], &mut self.bands_right);
+            let mut out_left = 0.0;
+            let mut out_right = 0.0;
+            for ((compressor, &band_left), &band_right) in self
+                .compressors
+                .iter_mut()
+                .zip(self.bands_left.iter())
+                .zip(self.bands_right.iter())
+            {
+                let (l, r) = compressor.process_sample(band_left, band_right);
+                out_left += l;
+                out_right += r;
+            }
+            ctx.outputs.write(out_left, 0, i);
+            ctx.outputs.write(out_right, 1, i);
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        for crossover in &mut self.crossovers {
+            crossover.set_sample_rate(sample_rate);
+            crossover.reset();
+        }
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input_left",
+            1 => "input_right",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "output_left",
+            1 => "output_right",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "MultibandCompressor"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultibandCompressor;
+    use crate::{gen::testing::GenTester, Sample};
+
+    #[test]
+    fn compresses_only_the_selected_band() {
+        let sr = 44100.;
+        let block_size = 441;
+        let mut mbc = MultibandCompressor::new(&[1000.]);
+        // Heavy compression of the high band
+        let high = mbc.band_mut(1);
+        high.set_threshold(0.1);
+        high.set_ratio(0.0);
+        high.set_attack(10.);
+        high.set_release(1000.);
+        let mut tester = GenTester::new(mbc, block_size, sr);
+        let mut peak_for_freq = |freq: Sample| {
+            let mut peak: Sample = 0.0;
+            for block in 0..100 {
+                for channel in 0..2 {
+                    for (i, v) in tester.input_mut(channel).iter_mut().enumerate() {
+                        let t = (block * block_size + i) as Sample / sr;
+                        *v = (t * freq * std::f64::consts::TAU as Sample).sin() * 0.8;
+                    }
+                }
+                tester.process_block();
+                if block > 50 {
+                    peak = peak.max(tester.output_peak(0)).max(tester.output_peak(1));
+                }
+            }
+            peak
+        };
+        let low_peak = peak_for_freq(100.);
+        let high_peak = peak_for_freq(8000.);
+        assert!((low_peak - 0.8).abs() < 0.02, "{low_peak}");
+        assert!(high_peak < 0.15, "{high_peak}");
+    }
+}
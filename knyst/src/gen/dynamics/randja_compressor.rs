@@ -67,6 +67,27 @@ impl RandjaCompressor {
         self.env = 0.;
         self.gain = 1.;
     }
+    /// Process one stereo sample, returning the compressed `(left, right)` pair
+    #[inline]
+    pub fn process_sample(&mut self, il: Sample, ir: Sample) -> (Sample, Sample) {
+        let det = il.abs().max(ir.abs()) + 10e-30;
+        self.env = if det >= self.env {
+            det
+        } else {
+            det + self.envelope_decay * (self.env - det)
+        };
+        let transfer_gain = if self.env > self.threshold {
+            self.env.powf(self.transfer_a) * self.transfer_b
+        } else {
+            self.output
+        };
+        self.gain = if transfer_gain < self.gain {
+            transfer_gain + self.attack * (self.gain - transfer_gain)
+        } else {
+            transfer_gain + self.release * (self.gain - transfer_gain)
+        };
+        (il * self.gain, ir * self.gain)
+    }
     #[allow(missing_docs)]
     pub fn process(
         &mut self,
@@ -82,24 +103,7 @@ impl RandjaCompressor {
             .zip(output_left.iter_mut())
             .zip(output_right.iter_mut())
         {
-            let det = il.abs().max(ir.abs()) + 10e-30;
-            self.env = if det >= self.env {
-                det
-            } else {
-                det + self.envelope_decay * (self.env - det)
-            };
-            let transfer_gain = if self.env > self.threshold {
-                self.env.powf(self.transfer_a) * self.transfer_b
-            } else {
-                self.output
-            };
-            self.gain = if transfer_gain < self.gain {
-                transfer_gain + self.attack * (self.gain - transfer_gain)
-            } else {
-                transfer_gain + self.release * (self.gain - transfer_gain)
-            };
-            *ol = il * self.gain;
-            *or = ir * self.gain;
+            (*ol, *or) = self.process_sample(*il, *ir);
         }
         GenState::Continue
     }
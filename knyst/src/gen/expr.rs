@@ -0,0 +1,645 @@
+//! Math expressions compiled to a node
+//!
+//! [`ExprGen`] parses a small math expression such as
+//! `"sin(p0) * 0.5 + in0 * p1"` when it is created and compiles it to
+//! bytecode for a small stack machine, which is then evaluated for every
+//! sample. This makes it possible to try out an idea, e.g. from a text field
+//! in a running program, without recompiling the host program.
+//!
+//! # Syntax
+//! - Numbers: `1`, `0.5`, `.5`, `2e-3`
+//! - Inputs: `in0` to `in15` for signals and `p0` to `p15` for parameters.
+//!   Both are inputs of the node, named like in the expression. The node has
+//!   every `in` input up to the highest one used, followed by every `p`
+//!   input up to the highest one used.
+//! - Constants: `pi`, `tau`, `e` and `sr`, the sample rate
+//! - Operators, from lowest to highest precedence: `+` and `-`; `*`, `/` and
+//!   `%`; unary `-`; `^` (power, right associative)
+//! - Functions: `sin`, `cos`, `tan`, `tanh`, `abs`, `sqrt`, `exp`, `ln`,
+//!   `log2`, `log10`, `floor`, `ceil`, `round`, `fract`, `sign`, `min(a, b)`,
+//!   `max(a, b)`, `pow(a, b)` and `clamp(x, low, high)`
+//!
+//! Parts of the expression that don't depend on any input are calculated
+//! once when compiling.
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+/// The maximum number of `in` and of `p` inputs respectively
+pub const MAX_EXPR_INPUTS: usize = 16;
+
+const SIGNAL_INPUT_NAMES: [&str; MAX_EXPR_INPUTS] = [
+    "in0", "in1", "in2", "in3", "in4", "in5", "in6", "in7", "in8", "in9", "in10", "in11", "in12",
+    "in13", "in14", "in15",
+];
+const PARAMETER_INPUT_NAMES: [&str; MAX_EXPR_INPUTS] = [
+    "p0", "p1", "p2", "p3", "p4", "p5", "p6", "p7", "p8", "p9", "p10", "p11", "p12", "p13", "p14",
+    "p15",
+];
+
+/// Error compiling an expression for an [`ExprGen`]. Positions are counted in
+/// characters from the start of the expression.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ExprError {
+    /// A character which isn't part of the syntax
+    #[error("Unexpected character `{character}` at position {position}.")]
+    UnexpectedCharacter {
+        #[allow(missing_docs)]
+        character: char,
+        #[allow(missing_docs)]
+        position: usize,
+    },
+    /// A valid token in a place where it doesn't make sense, e.g. two numbers in a row
+    #[error("Unexpected `{token}` at position {position}.")]
+    UnexpectedToken {
+        #[allow(missing_docs)]
+        token: String,
+        #[allow(missing_docs)]
+        position: usize,
+    },
+    /// The expression ended where more was expected, e.g. after an operator
+    #[error("The expression ended unexpectedly.")]
+    UnexpectedEnd,
+    /// A number which could not be parsed
+    #[error("Invalid number `{0}`.")]
+    InvalidNumber(String),
+    /// A name which is neither an input, a constant nor a function
+    #[error("Unknown name `{0}`. Inputs are named in0 to in15 and p0 to p15.")]
+    UnknownIdentifier(String),
+    /// A function called with the wrong number of arguments
+    #[error("The function `{function}` takes {expected} arguments, but was given {found}.")]
+    WrongNumberOfArguments {
+        #[allow(missing_docs)]
+        function: String,
+        #[allow(missing_docs)]
+        expected: usize,
+        #[allow(missing_docs)]
+        found: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(Sample),
+    Identifier(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    Comma,
+    OpenParen,
+    CloseParen,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Identifier(name) => write!(f, "{name}"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
+            Token::Caret => write!(f, "^"),
+            Token::Comma => write!(f, ","),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, ExprError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '0'..='9' | '.' => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // Exponent, e.g. 2e-3
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && (chars[j] == '-' || chars[j] == '+') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text
+                    .parse::<Sample>()
+                    .map_err(|_| ExprError::InvalidNumber(text))?;
+                tokens.push((Token::Number(number), start));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((Token::Identifier(chars[start..i].iter().collect()), start));
+                continue;
+            }
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '%' => Token::Percent,
+            '^' => Token::Caret,
+            ',' => Token::Comma,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            character => {
+                return Err(ExprError::UnexpectedCharacter {
+                    character,
+                    position: start,
+                })
+            }
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// An instruction for the stack machine evaluating an expression
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Constant(Sample),
+    /// Input channel of the node
+    Input(usize),
+    /// Parameter input, only used while compiling since the final index
+    /// depends on the number of signal inputs
+    Parameter(usize),
+    SampleRate,
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Min,
+    Max,
+    Clamp,
+    Function(fn(Sample) -> Sample),
+}
+
+impl Op {
+    /// The number of values taken from the stack
+    fn num_arguments(&self) -> usize {
+        match self {
+            Op::Constant(_) | Op::Input(_) | Op::Parameter(_) | Op::SampleRate => 0,
+            Op::Neg | Op::Function(_) => 1,
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem | Op::Pow | Op::Min | Op::Max => 2,
+            Op::Clamp => 3,
+        }
+    }
+    /// Apply an operator to its arguments. Not used for the ops without arguments.
+    #[inline]
+    fn apply(&self, args: &[Sample]) -> Sample {
+        match self {
+            Op::Neg => -args[0],
+            Op::Function(f) => f(args[0]),
+            Op::Add => args[0] + args[1],
+            Op::Sub => args[0] - args[1],
+            Op::Mul => args[0] * args[1],
+            Op::Div => args[0] / args[1],
+            Op::Rem => args[0] % args[1],
+            Op::Pow => args[0].powf(args[1]),
+            Op::Min => args[0].min(args[1]),
+            Op::Max => args[0].max(args[1]),
+            Op::Clamp => args[0].max(args[1]).min(args[2]),
+            Op::Constant(_) | Op::Input(_) | Op::Parameter(_) | Op::SampleRate => 0.0,
+        }
+    }
+}
+
+fn function(name: &str) -> Option<(Op, usize)> {
+    let f: fn(Sample) -> Sample = match name {
+        "sin" => Sample::sin,
+        "cos" => Sample::cos,
+        "tan" => Sample::tan,
+        "tanh" => Sample::tanh,
+        "abs" => Sample::abs,
+        "sqrt" => Sample::sqrt,
+        "exp" => Sample::exp,
+        "ln" => Sample::ln,
+        "log2" => Sample::log2,
+        "log10" => Sample::log10,
+        "floor" => Sample::floor,
+        "ceil" => Sample::ceil,
+        "round" => Sample::round,
+        "fract" => Sample::fract,
+        "sign" => Sample::signum,
+        "min" => return Some((Op::Min, 2)),
+        "max" => return Some((Op::Max, 2)),
+        "pow" => return Some((Op::Pow, 2)),
+        "clamp" => return Some((Op::Clamp, 3)),
+        _ => return None,
+    };
+    Some((Op::Function(f), 1))
+}
+
+/// Recursive descent parser emitting ops in postfix order
+struct Compiler {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    ops: Vec<Op>,
+}
+
+impl Compiler {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+    fn advance(&mut self) -> Result<Token, ExprError> {
+        let (token, _) = self.tokens.get(self.next).ok_or(ExprError::UnexpectedEnd)?;
+        self.next += 1;
+        Ok(token.clone())
+    }
+    fn unexpected(&self) -> ExprError {
+        match self.tokens.get(self.next) {
+            Some((token, position)) => ExprError::UnexpectedToken {
+                token: token.to_string(),
+                position: *position,
+            },
+            None => ExprError::UnexpectedEnd,
+        }
+    }
+    fn expect(&mut self, expected: Token) -> Result<(), ExprError> {
+        if self.peek() == Some(&expected) {
+            self.next += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+    /// Emit an op, calculating it right away if all of its arguments are constants
+    fn emit(&mut self, op: Op) {
+        let num_arguments = op.num_arguments();
+        if num_arguments > 0 && self.ops.len() >= num_arguments {
+            let first_argument = self.ops.len() - num_arguments;
+            let mut args = [0.0; 3];
+            let all_constant =
+                self.ops[first_argument..]
+                    .iter()
+                    .zip(args.iter_mut())
+                    .all(|(op, arg)| match op {
+                        Op::Constant(value) => {
+                            *arg = *value;
+                            true
+                        }
+                        _ => false,
+                    });
+            if all_constant {
+                self.ops.truncate(first_argument);
+                self.ops.push(Op::Constant(op.apply(&args)));
+                return;
+            }
+        }
+        self.ops.push(op);
+    }
+    fn expression(&mut self) -> Result<(), ExprError> {
+        self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => Op::Add,
+                Some(Token::Minus) => Op::Sub,
+                _ => return Ok(()),
+            };
+            self.next += 1;
+            self.term()?;
+            self.emit(op);
+        }
+    }
+    fn term(&mut self) -> Result<(), ExprError> {
+        self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => Op::Mul,
+                Some(Token::Slash) => Op::Div,
+                Some(Token::Percent) => Op::Rem,
+                _ => return Ok(()),
+            };
+            self.next += 1;
+            self.unary()?;
+            self.emit(op);
+        }
+    }
+    fn unary(&mut self) -> Result<(), ExprError> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.next += 1;
+                self.unary()?;
+                self.emit(Op::Neg);
+                Ok(())
+            }
+            Some(Token::Plus) => {
+                self.next += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+    fn power(&mut self) -> Result<(), ExprError> {
+        self.primary()?;
+        if self.peek() == Some(&Token::Caret) {
+            self.next += 1;
+            // Right associative and binding tighter than unary minus on the
+            // left, i.e. -2^2 is -4 and 2^-1 is 0.5
+            self.unary()?;
+            self.emit(Op::Pow);
+        }
+        Ok(())
+    }
+    fn primary(&mut self) -> Result<(), ExprError> {
+        let position = self.next;
+        match self.advance()? {
+            Token::Number(value) => self.emit(Op::Constant(value)),
+            Token::OpenParen => {
+                self.expression()?;
+                self.expect(Token::CloseParen)?;
+            }
+            Token::Identifier(name) => {
+                if self.peek() == Some(&Token::OpenParen) {
+                    self.next += 1;
+                    let (op, expected) = function(&name)
+                        .ok_or_else(|| ExprError::UnknownIdentifier(name.clone()))?;
+                    let mut found = 0;
+                    if self.peek() != Some(&Token::CloseParen) {
+                        loop {
+                            self.expression()?;
+                            found += 1;
+                            if self.peek() == Some(&Token::Comma) {
+                                self.next += 1;
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                    self.expect(Token::CloseParen)?;
+                    if found != expected {
+                        return Err(ExprError::WrongNumberOfArguments {
+                            function: name,
+                            expected,
+                            found,
+                        });
+                    }
+                    self.emit(op);
+                } else {
+                    let op = variable(&name).ok_or(ExprError::UnknownIdentifier(name))?;
+                    self.emit(op);
+                }
+            }
+            _ => {
+                self.next = position;
+                return Err(self.unexpected());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn variable(name: &str) -> Option<Op> {
+    let op = match name {
+        "pi" => Op::Constant(std::f64::consts::PI as Sample),
+        "tau" => Op::Constant(std::f64::consts::TAU as Sample),
+        "e" => Op::Constant(std::f64::consts::E as Sample),
+        "sr" => Op::SampleRate,
+        _ => {
+            if let Some(index) = SIGNAL_INPUT_NAMES.iter().position(|n| *n == name) {
+                Op::Input(index)
+            } else {
+                Op::Parameter(PARAMETER_INPUT_NAMES.iter().position(|n| *n == name)?)
+            }
+        }
+    };
+    Some(op)
+}
+
+/// Evaluates a math expression for every sample, see the [module
+/// documentation](self) for the syntax.
+///
+/// *inputs*
+/// 0..N: "in0" to "inN", the signal inputs used in the expression
+/// N..N+M: "p0" to "pM", the parameters used in the expression
+///
+/// *outputs*
+/// 0. "out": The result of the expression
+#[derive(Clone, Debug)]
+pub struct ExprGen {
+    ops: Vec<Op>,
+    stack: Vec<Sample>,
+    num_signal_inputs: usize,
+    num_parameters: usize,
+    sample_rate: Sample,
+}
+
+impl ExprGen {
+    /// Compile the expression, returning an error if it isn't valid.
+    pub fn new(expression: &str) -> Result<Self, ExprError> {
+        let mut compiler = Compiler {
+            tokens: tokenize(expression)?,
+            next: 0,
+            ops: vec![],
+        };
+        compiler.expression()?;
+        if compiler.next < compiler.tokens.len() {
+            return Err(compiler.unexpected());
+        }
+        let mut ops = compiler.ops;
+        let mut num_signal_inputs = 0;
+        let mut num_parameters = 0;
+        for op in &ops {
+            match op {
+                Op::Input(index) => num_signal_inputs = num_signal_inputs.max(index + 1),
+                Op::Parameter(index) => num_parameters = num_parameters.max(index + 1),
+                _ => (),
+            }
+        }
+        // Parameters come after the signal inputs
+        let mut depth = 0;
+        let mut max_depth = 0;
+        for op in &mut ops {
+            if let Op::Parameter(index) = *op {
+                *op = Op::Input(num_signal_inputs + index);
+            }
+            depth = depth + 1 - op.num_arguments();
+            max_depth = max_depth.max(depth);
+        }
+        Ok(Self {
+            ops,
+            stack: Vec::with_capacity(max_depth),
+            num_signal_inputs,
+            num_parameters,
+            sample_rate: 0.0,
+        })
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let num_inputs = self.num_inputs();
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, num_inputs, 1))
+    }
+}
+
+impl Gen for ExprGen {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let output = ctx.outputs.iter_mut().next().unwrap();
+        for (i, out) in output.iter_mut().enumerate().take(block_size) {
+            self.stack.clear();
+            for op in &self.ops {
+                let value = match op {
+                    Op::Constant(value) => *value,
+                    Op::Input(channel) => ctx.inputs.read(*channel, i),
+                    Op::SampleRate => self.sample_rate,
+                    op => {
+                        let first_argument = self.stack.len() - op.num_arguments();
+                        let value = op.apply(&self.stack[first_argument..]);
+                        self.stack.truncate(first_argument);
+                        value
+                    }
+                };
+                self.stack.push(value);
+            }
+            *out = self.stack.pop().unwrap_or(0.0);
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        self.num_signal_inputs + self.num_parameters
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        if input < self.num_signal_inputs {
+            SIGNAL_INPUT_NAMES[input]
+        } else if input < self.num_inputs() {
+            PARAMETER_INPUT_NAMES[input - self.num_signal_inputs]
+        } else {
+            ""
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "out",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "ExprGen"
+    }
+}
+
+/// Compile the expression to an [`ExprGen`] and upload it to the current
+/// graph, returning a handle to the new node.
+pub fn expr(expression: &str) -> Result<Handle<GenericHandle>, ExprError> {
+    Ok(ExprGen::new(expression)?.upload())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExprError, ExprGen};
+    use crate::{
+        gen::{testing::GenTester, Gen},
+        Sample,
+    };
+
+    const BLOCK_SIZE: usize = 2;
+    const SR: Sample = 100.;
+
+    /// Evaluate the expression for one block where every input is constant
+    fn evaluate(expression: &str, inputs: &[Sample]) -> Sample {
+        let gen = ExprGen::new(expression).unwrap();
+        assert_eq!(gen.num_inputs(), inputs.len());
+        let mut gen = GenTester::new(gen, BLOCK_SIZE, SR);
+        for (input, &value) in inputs.iter().enumerate() {
+            gen.set_input(input, value);
+        }
+        gen.process_block();
+        let output = gen.output(0);
+        assert_eq!(output[0], output[1]);
+        output[0]
+    }
+
+    #[test]
+    fn evaluates_expressions() {
+        assert_eq!(evaluate("1 + 2 * 3", &[]), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3", &[]), 9.0);
+        assert_eq!(evaluate("-2^2 + 2^-1 + 2^3^2", &[]), -4.0 + 0.5 + 512.0);
+        assert_eq!(evaluate("7 % 4 - 1 / 4", &[]), 2.75);
+        assert_eq!(evaluate("sr / 2", &[]), 50.0);
+        assert_eq!(
+            evaluate("clamp(in0, 0, 1) + max(p0, 2e-1)", &[3.0, 0.1]),
+            1.2
+        );
+        // Inputs are ordered signals first, parameters after
+        let result = evaluate("sin(p0)*0.5 + in0*p1", &[2.0, 0.25, 4.0]);
+        assert!((result - ((0.25 as Sample).sin() * 0.5 + 8.0)).abs() < 1e-6);
+        let gen = ExprGen::new("in1 * p0").unwrap();
+        assert_eq!(
+            (0..3).map(|i| gen.input_desc(i)).collect::<Vec<_>>(),
+            vec!["in0", "in1", "p0"]
+        );
+        // Constant parts are calculated when compiling
+        assert_eq!(ExprGen::new("in0 * (2 * pi + 1)").unwrap().ops.len(), 3);
+
+        assert_eq!(ExprGen::new("1 +").unwrap_err(), ExprError::UnexpectedEnd);
+        assert_eq!(
+            ExprGen::new("freq * 2").unwrap_err(),
+            ExprError::UnknownIdentifier("freq".to_string())
+        );
+        assert_eq!(
+            ExprGen::new("2 $ 3").unwrap_err(),
+            ExprError::UnexpectedCharacter {
+                character: '$',
+                position: 2
+            }
+        );
+        assert_eq!(
+            ExprGen::new("1 2").unwrap_err(),
+            ExprError::UnexpectedToken {
+                token: "2".to_string(),
+                position: 2
+            }
+        );
+        assert_eq!(
+            ExprGen::new("min(1)").unwrap_err(),
+            ExprError::WrongNumberOfArguments {
+                function: "min".to_string(),
+                expected: 2,
+                found: 1
+            }
+        );
+    }
+}
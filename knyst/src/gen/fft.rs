@@ -0,0 +1,19 @@
+//! Helpers shared by the FFT based [`Gen`](super::Gen)s
+use realfft::{num_complex::Complex, ComplexToReal, FftNum};
+
+/// Transform `spectrum` back to the real signal `output`, destroying the
+/// contents of `spectrum`.
+pub(crate) fn inverse_real_fft<T: FftNum>(
+    ifft: &dyn ComplexToReal<T>,
+    spectrum: &mut [Complex<T>],
+    output: &mut [T],
+    scratch: &mut [Complex<T>],
+) {
+    // The imaginary parts of the DC and Nyquist bins have to be 0 for a real
+    // signal, but processing the spectrum may have changed them
+    spectrum[0].im = T::zero();
+    let last = spectrum.len() - 1;
+    spectrum[last].im = T::zero();
+    ifft.process_with_scratch(spectrum, output, scratch)
+        .expect("buffers are created by the fft");
+}
@@ -0,0 +1,276 @@
+//! Linkwitz-Riley crossover for splitting a signal into multiple frequency bands
+//!
+//! Every crossover point is a 4th order Linkwitz-Riley filter pair, i.e. two
+//! cascaded Butterworth [`SvfFilter`]s. The lower bands are passed through the
+//! allpass filters matching the crossover points above them so that all the
+//! bands are phase coherent and sum to an allpass filtered version of the input.
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+use super::svf::{SvfFilter, SvfFilterType};
+
+const BUTTERWORTH_Q: Sample = std::f64::consts::FRAC_1_SQRT_2 as Sample;
+
+/// One crossover point of a [`LinkwitzRileyCrossover`]
+#[derive(Clone, Debug)]
+struct CrossoverPoint {
+    freq: Sample,
+    lowpass: [SvfFilter; 2],
+    highpass: [SvfFilter; 2],
+}
+impl CrossoverPoint {
+    fn new(freq: Sample) -> Self {
+        Self {
+            freq,
+            lowpass: [
+                SvfFilter::new(SvfFilterType::Low, freq, BUTTERWORTH_Q, 0.0),
+                SvfFilter::new(SvfFilterType::Low, freq, BUTTERWORTH_Q, 0.0),
+            ],
+            highpass: [
+                SvfFilter::new(SvfFilterType::High, freq, BUTTERWORTH_Q, 0.0),
+                SvfFilter::new(SvfFilterType::High, freq, BUTTERWORTH_Q, 0.0),
+            ],
+        }
+    }
+}
+
+/// Splits a signal into `crossover_freqs.len() + 1` phase coherent bands
+/// sample by sample. This is the DSP used by [`LinkwitzRileySplitter`] and
+/// [`MultibandCompressor`](crate::gen::dynamics::multiband_compressor::MultibandCompressor).
+#[derive(Clone, Debug)]
+pub struct LinkwitzRileyCrossover {
+    crossovers: Vec<CrossoverPoint>,
+    /// Allpass filters for compensating the phase of lower bands, indexed by
+    /// `band * num_crossovers + crossover`. Only crossovers above the band are used.
+    allpasses: Vec<SvfFilter>,
+}
+
+impl LinkwitzRileyCrossover {
+    /// Create a new crossover. The crossover frequencies will be sorted in ascending order.
+    pub fn new(crossover_freqs: &[Sample]) -> Self {
+        let mut freqs = crossover_freqs.to_vec();
+        freqs.sort_by(|a, b| a.total_cmp(b));
+        let crossovers: Vec<_> = freqs.iter().map(|&f| CrossoverPoint::new(f)).collect();
+        let num_crossovers = crossovers.len();
+        let mut allpasses = Vec::with_capacity(num_crossovers * num_crossovers);
+        for _band in 0..num_crossovers {
+            for &freq in &freqs {
+                allpasses.push(SvfFilter::new(SvfFilterType::All, freq, BUTTERWORTH_Q, 0.0));
+            }
+        }
+        Self {
+            crossovers,
+            allpasses,
+        }
+    }
+    /// The number of bands the signal is split into
+    pub fn num_bands(&self) -> usize {
+        self.crossovers.len() + 1
+    }
+    /// Calculate the filter coefficients for a new sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: Sample) {
+        let num_crossovers = self.crossovers.len();
+        for crossover in &mut self.crossovers {
+            let freq = crossover.freq;
+            for filter in crossover
+                .lowpass
+                .iter_mut()
+                .chain(crossover.highpass.iter_mut())
+            {
+                filter.set_coeffs(freq, BUTTERWORTH_Q, 0.0, sample_rate);
+            }
+        }
+        for (i, allpass) in self.allpasses.iter_mut().enumerate() {
+            let freq = self.crossovers[i % num_crossovers].freq;
+            allpass.set_coeffs(freq, BUTTERWORTH_Q, 0.0, sample_rate);
+        }
+    }
+    /// Reset the state of all filters
+    pub fn reset(&mut self) {
+        for crossover in &mut self.crossovers {
+            for filter in crossover
+                .lowpass
+                .iter_mut()
+                .chain(crossover.highpass.iter_mut())
+            {
+                filter.reset();
+            }
+        }
+        for allpass in &mut self.allpasses {
+            allpass.reset();
+        }
+    }
+    /// Process one sample, writing one sample per band from the lowest to the
+    /// highest band into `bands`. `bands` must be at least
+    /// [`LinkwitzRileyCrossover::num_bands`] long.
+    #[inline]
+    pub fn process_sample(&mut self, input: Sample, bands: &mut [Sample]) {
+        let num_crossovers = self.crossovers.len();
+        let mut rest = input;
+        for (band, crossover) in self.crossovers.iter_mut().enumerate() {
+            let [lowpass0, lowpass1] = &mut crossover.lowpass;
+            let mut low = lowpass1.process_sample(lowpass0.process_sample(rest));
+            for allpass in
+                &mut self.allpasses[band * num_crossovers + band + 1..(band + 1) * num_crossovers]
+            {
+                low = allpass.process_sample(low);
+            }
+            bands[band] = low;
+            let [highpass0, highpass1] = &mut crossover.highpass;
+            rest = highpass1.process_sample(highpass0.process_sample(rest));
+        }
+        bands[num_crossovers] = rest;
+    }
+}
+
+/// Linkwitz-Riley crossover splitting a signal into N bands. Summing the bands
+/// results in an allpass filtered version of the input signal, i.e. the
+/// magnitude response is flat.
+///
+/// The crossover frequencies are set at creation.
+///
+/// *inputs*
+/// 0. "input": The signal to split
+///
+/// *outputs*
+/// 0..N: One output per band, from the lowest to the highest band
+pub struct LinkwitzRileySplitter {
+    crossover: LinkwitzRileyCrossover,
+    bands: Vec<Sample>,
+}
+
+impl LinkwitzRileySplitter {
+    /// Create a new splitter with `crossover_freqs.len() + 1` bands.
+    pub fn new(crossover_freqs: &[Sample]) -> Self {
+        let crossover = LinkwitzRileyCrossover::new(crossover_freqs);
+        let bands = vec![0.0; crossover.num_bands()];
+        Self { crossover, bands }
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let num_outputs = self.crossover.num_bands();
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 1, num_outputs))
+    }
+}
+
+impl Gen for LinkwitzRileySplitter {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let input = ctx.inputs.get_channel(0);
+        for (i, &input) in input.iter().enumerate().take(block_size) {
+            self.crossover.process_sample(input, &mut self.bands);
+            for (band, &value) in self.bands.iter().enumerate() {
+                ctx.outputs.write(value, band, i);
+            }
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        1
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.crossover.num_bands()
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.crossover.set_sample_rate(sample_rate);
+        self.crossover.reset();
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        band_str(output)
+    }
+
+    fn name(&self) -> &'static str {
+        "LinkwitzRileySplitter"
+    }
+}
+
+/// Upload a [`LinkwitzRileySplitter`] to the current graph and return a handle to it.
+pub fn linkwitz_riley_splitter(crossover_freqs: &[Sample]) -> Handle<GenericHandle> {
+    LinkwitzRileySplitter::new(crossover_freqs).upload()
+}
+
+fn band_str(num: usize) -> &'static str {
+    match num {
+        0 => "band0",
+        1 => "band1",
+        2 => "band2",
+        3 => "band3",
+        4 => "band4",
+        5 => "band5",
+        6 => "band6",
+        7 => "band7",
+        8 => "band8",
+        9 => "band9",
+        10 => "band10",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkwitzRileyCrossover;
+    use crate::Sample;
+
+    /// Returns the peak amplitude of the sum of all bands for a sine input once settled
+    fn summed_peak(crossover: &mut LinkwitzRileyCrossover, freq: Sample, sr: Sample) -> Sample {
+        let mut bands = vec![0.0; crossover.num_bands()];
+        let mut peak: Sample = 0.0;
+        let num_samples = sr as usize;
+        for i in 0..num_samples {
+            let input = (i as Sample * freq * std::f64::consts::TAU as Sample / sr).sin();
+            crossover.process_sample(input, &mut bands);
+            if i > num_samples / 2 {
+                peak = peak.max(bands.iter().sum::<Sample>().abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn bands_sum_to_flat_magnitude() {
+        let sr = 44100.;
+        let mut crossover = LinkwitzRileyCrossover::new(&[2000., 200., 8000.]);
+        crossover.set_sample_rate(sr);
+        assert_eq!(crossover.num_bands(), 4);
+        for freq in [50., 200., 700., 2000., 5000., 8000., 12000.] {
+            crossover.reset();
+            let peak = summed_peak(&mut crossover, freq, sr);
+            assert!((peak - 1.0).abs() < 0.01, "freq: {freq}, peak: {peak}");
+        }
+    }
+    #[test]
+    fn bands_separate_frequencies() {
+        let sr = 44100.;
+        let mut crossover = LinkwitzRileyCrossover::new(&[500.]);
+        crossover.set_sample_rate(sr);
+        let mut bands = [0.0; 2];
+        let mut peaks: [Sample; 2] = [0.0; 2];
+        for i in 0..44100 {
+            let input = (i as Sample * 5000. * std::f64::consts::TAU as Sample / sr).sin();
+            crossover.process_sample(input, &mut bands);
+            if i > 22050 {
+                peaks[0] = peaks[0].max(bands[0].abs());
+                peaks[1] = peaks[1].max(bands[1].abs());
+            }
+        }
+        assert!(peaks[0] < 0.01, "{peaks:?}");
+        assert!((peaks[1] - 1.0).abs() < 0.01, "{peaks:?}");
+    }
+}
@@ -1,3 +1,4 @@
 //! Filter `Gen`s
+pub mod linkwitz_riley;
 pub mod one_pole;
 pub mod svf;
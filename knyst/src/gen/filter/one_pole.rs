@@ -126,7 +126,7 @@ impl OnePoleLpf {
         &mut self,
         sample_rate: SampleRate,
         sig: &[Sample],
-        cutoff_freq: &[Sample],
+        #[default(19000.)] cutoff_freq: &[Sample],
         output: &mut [Sample],
     ) -> GenState {
         for ((&i, &cutoff), o) in sig.iter().zip(cutoff_freq.iter()).zip(output.iter_mut()) {
@@ -162,7 +162,7 @@ impl OnePoleHpf {
         &mut self,
         sample_rate: SampleRate,
         sig: &[Sample],
-        cutoff_freq: &[Sample],
+        #[default(20.)] cutoff_freq: &[Sample],
         output: &mut [Sample],
     ) -> GenState {
         for ((&i, &cutoff), o) in sig.iter().zip(cutoff_freq.iter()).zip(output.iter_mut()) {
@@ -135,8 +135,7 @@ impl SvfFilter {
             }
             SvfFilterType::Bell => {
                 let amp = (10.0 as Sample).powf(gain_db / 40.);
-                let g =
-                    ((std::f64::consts::PI as Sample * cutoff) / sample_rate).tan() / amp.sqrt();
+                let g = ((std::f64::consts::PI as Sample * cutoff) / sample_rate).tan();
                 let k = 1.0 / (q * amp);
                 self.a1 = 1.0 / (1.0 + g * (g + k));
                 self.a2 = g * self.a1;
@@ -175,6 +174,11 @@ impl SvfFilter {
     pub fn init(&mut self, sample_rate: SampleRate) {
         self.set_coeffs(self.cutoff_freq, self.q, self.gain_db, *sample_rate);
     }
+    /// Reset the internal state of the filter, keeping the coefficients
+    pub fn reset(&mut self) {
+        self.ic1eq = 0.;
+        self.ic2eq = 0.;
+    }
     // TODO: This is vectorisable such that multiple filters can be run at once, e.g. multiple channels with the same coefficients
     #[allow(missing_docs)]
     pub fn process_sample(&mut self, v0: Sample) -> Sample {
@@ -230,9 +234,9 @@ impl SvfHighShelf {
     pub fn process(
         &mut self,
         input: &[Sample],
-        cutoff_freq: &[Sample],
+        #[default(20000.)] cutoff_freq: &[Sample],
         gain: &[Sample],
-        q: &[Sample],
+        #[default(1.0)] q: &[Sample],
         output: &mut [Sample],
         sample_rate: SampleRate,
     ) -> GenState {
@@ -269,9 +273,9 @@ impl SvfDynamic {
     pub fn process(
         &mut self,
         input: &[Sample],
-        cutoff_freq: &[Sample],
+        #[default(2000.)] cutoff_freq: &[Sample],
         gain: &[Sample],
-        q: &[Sample],
+        #[default(1.0)] q: &[Sample],
         output: &mut [Sample],
         sample_rate: SampleRate,
     ) -> GenState {
@@ -0,0 +1,634 @@
+//! Test signals for measuring rooms, speakers and filters
+//!
+//! - [`LogSweep`]: an exponential sine sweep. Recording a system playing the
+//!   sweep and passing the recording to [`deconvolve`] gives the impulse
+//!   response of the system, with the harmonic distortion ending up before
+//!   the linear response where it is easy to cut away (Farina's method).
+//! - [`ImpulseTrain`]: single sample impulses at a regular interval, or only
+//!   one impulse.
+//! - [`PinkBurst`]: bursts of pink noise at a calibrated RMS level, e.g. for
+//!   setting levels or measuring decay times.
+//! - [`FullScaleSquare`]: a square wave alternating between exactly the
+//!   positive and negative amplitude, without band limiting.
+//!
+//! Since knyst graphs can be rendered offline, the test signals can also be
+//! used to validate filters: render a [`LogSweep`] through the filter and
+//! [`deconvolve`] the output to get the impulse response of the filter.
+use std::f64::consts::TAU;
+
+use realfft::{num_complex::Complex, RealFftPlanner};
+
+use crate::{
+    gen::{
+        fft::inverse_real_fft, noise::PINK_NOISE_OCTAVES, Gen, GenContext, GenState, PinkNoise,
+        StopAction,
+    },
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    time::Seconds,
+    Resources, Sample,
+};
+
+/// An exponential (logarithmic) sine sweep from `start_freq` to `end_freq`
+/// over `duration`, spending the same time on every octave.
+///
+/// The sweep plays once and then outputs silence and applies its
+/// [`StopAction`].
+///
+/// *outputs*
+/// 0. "out": The sweep
+#[derive(Clone, Debug)]
+pub struct LogSweep {
+    start_freq: f64,
+    end_freq: f64,
+    duration: Seconds,
+    amplitude: Sample,
+    stop_action: StopAction,
+    sample_rate: f64,
+    num_samples: u64,
+    position: u64,
+}
+
+impl LogSweep {
+    /// Create a sweep from `start_freq` to `end_freq` in Hz lasting `duration`.
+    ///
+    /// # Panics
+    /// If either frequency is not above 0 or if the frequencies are the same.
+    pub fn new(start_freq: Sample, end_freq: Sample, duration: Seconds) -> Self {
+        assert!(
+            start_freq > 0.0 && end_freq > 0.0,
+            "The frequencies of a LogSweep have to be above 0"
+        );
+        assert!(
+            start_freq != end_freq,
+            "The start and end frequencies of a LogSweep have to be different"
+        );
+        Self {
+            start_freq: start_freq as f64,
+            end_freq: end_freq as f64,
+            duration,
+            amplitude: 1.0,
+            stop_action: StopAction::Continue,
+            sample_rate: 0.0,
+            num_samples: 0,
+            position: 0,
+        }
+    }
+    /// Set the peak amplitude of the sweep. The default is 1.0, i.e. full scale.
+    pub fn amplitude(mut self, amplitude: Sample) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+    /// Set what happens when the sweep has finished. The default is
+    /// [`StopAction::Continue`], outputting silence.
+    pub fn stop_action(mut self, stop_action: StopAction) -> Self {
+        self.stop_action = stop_action;
+        self
+    }
+    /// The time it takes for the frequency to rise by a factor of e
+    fn rate(&self) -> f64 {
+        self.duration.to_seconds_f64() / (self.end_freq / self.start_freq).ln()
+    }
+    /// The value of the sweep at `frame`, calculated from scratch to avoid
+    /// accumulating phase errors
+    fn value_at(&self, frame: u64, sample_rate: f64) -> Sample {
+        let rate = self.rate();
+        let t = frame as f64 / sample_rate;
+        let phase = TAU * self.start_freq * rate * ((t / rate).exp() - 1.0);
+        (phase.sin() * self.amplitude as f64) as Sample
+    }
+    /// Render the whole sweep at the given sample rate
+    pub fn render(&self, sample_rate: Sample) -> Vec<Sample> {
+        let num_samples = self.duration.to_samples(sample_rate as u64);
+        (0..num_samples)
+            .map(|frame| self.value_at(frame, sample_rate as f64))
+            .collect()
+    }
+    /// The inverse filter of the sweep: the sweep reversed in time, with a
+    /// rising amplitude compensating for the extra energy of the sweep in
+    /// the low frequencies. Convolving the sweep with its inverse filter
+    /// results in an impulse of amplitude 1. Used by [`deconvolve`].
+    pub fn inverse_filter(&self, sample_rate: Sample) -> Vec<Sample> {
+        let sweep = self.render(sample_rate);
+        let rate = self.rate();
+        let envelope = |frame: usize| (frame as f64 / sample_rate as f64 / rate).exp();
+        let inverse: Vec<f64> = sweep
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(frame, value)| *value as f64 * envelope(frame))
+            .collect();
+        // The value of the convolution at the position of the impulse
+        let peak: f64 = sweep
+            .iter()
+            .zip(inverse.iter().rev())
+            .map(|(sweep, inverse)| *sweep as f64 * inverse)
+            .sum();
+        inverse
+            .into_iter()
+            .map(|value| (value / peak) as Sample)
+            .collect()
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 0, 1))
+    }
+}
+
+impl Gen for LogSweep {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let output = ctx.outputs.iter_mut().next().unwrap();
+        let mut stop_sample = None;
+        for (i, out) in output.iter_mut().enumerate() {
+            if self.position < self.num_samples {
+                *out = self.value_at(self.position, self.sample_rate);
+                self.position += 1;
+            } else {
+                *out = 0.0;
+                stop_sample.get_or_insert(i);
+            }
+        }
+        match stop_sample {
+            Some(stop_sample) => self.stop_action.to_gen_state(stop_sample),
+            None => GenState::Continue,
+        }
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.sample_rate = sample_rate as f64;
+        self.num_samples = self.duration.to_samples(sample_rate as u64);
+        self.position = 0;
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "out",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "LogSweep"
+    }
+}
+
+/// Upload a full scale [`LogSweep`] to the current graph
+pub fn log_sweep(start_freq: Sample, end_freq: Sample, duration: Seconds) -> Handle<GenericHandle> {
+    LogSweep::new(start_freq, end_freq, duration).upload()
+}
+
+/// Calculate the impulse response of a system from a `recording` of the
+/// system playing `sweep`, recorded from the start of the sweep, at
+/// `sample_rate`. This is done offline and allocates.
+///
+/// The returned impulse response has the same length as the recording. Index
+/// 0 corresponds to no delay between playing and recording. Make the
+/// recording longer than the sweep by at least the length of the impulse
+/// response, e.g. the reverb time of the room. Harmonic distortion products
+/// wrap around to the end of the returned impulse response.
+pub fn deconvolve(recording: &[Sample], sweep: &LogSweep, sample_rate: Sample) -> Vec<Sample> {
+    let inverse = sweep.inverse_filter(sample_rate);
+    if recording.is_empty() || inverse.is_empty() {
+        return vec![0.0; recording.len()];
+    }
+    let convolution = fft_convolve(recording, &inverse);
+    // The impulse of a system without delay ends up at the last frame of the sweep
+    let offset = inverse.len() - 1;
+    let mut ir: Vec<Sample> = convolution[offset..].to_vec();
+    // The harmonic distortion comes before the linear response
+    ir.extend_from_slice(&convolution[..offset]);
+    ir.truncate(recording.len());
+    ir
+}
+
+/// Linear convolution of `a` and `b` through the FFT
+fn fft_convolve(a: &[Sample], b: &[Sample]) -> Vec<Sample> {
+    let output_len = a.len() + b.len() - 1;
+    let fft_size = output_len.next_power_of_two();
+    let mut planner = RealFftPlanner::<f64>::new();
+    let fft = planner.plan_fft_forward(fft_size);
+    let ifft = planner.plan_fft_inverse(fft_size);
+    let spectrum = |signal: &[Sample]| {
+        let mut input = fft.make_input_vec();
+        for (input, value) in input.iter_mut().zip(signal) {
+            *input = *value as f64;
+        }
+        let mut spectrum = fft.make_output_vec();
+        fft.process(&mut input, &mut spectrum)
+            .expect("buffers are created by the fft");
+        spectrum
+    };
+    let mut product: Vec<Complex<f64>> = spectrum(a)
+        .iter()
+        .zip(spectrum(b).iter())
+        .map(|(a, b)| a * b)
+        .collect();
+    let mut output = ifft.make_output_vec();
+    inverse_real_fft(
+        ifft.as_ref(),
+        &mut product,
+        &mut output,
+        &mut ifft.make_scratch_vec(),
+    );
+    output
+        .into_iter()
+        .take(output_len)
+        .map(|value| (value / fft_size as f64) as Sample)
+        .collect()
+}
+
+/// Single sample impulses at a regular interval, starting with an impulse
+/// at the first sample. After `count` impulses, if set, the output is silent
+/// and the [`StopAction`] is applied.
+///
+/// *outputs*
+/// 0. "out": The impulses
+#[derive(Clone, Debug)]
+pub struct ImpulseTrain {
+    interval: Seconds,
+    amplitude: Sample,
+    count: Option<usize>,
+    stop_action: StopAction,
+    interval_samples: u64,
+    samples_until_impulse: u64,
+    impulses_left: Option<usize>,
+}
+
+impl ImpulseTrain {
+    /// Create an impulse train with an impulse every `interval`
+    pub fn new(interval: Seconds) -> Self {
+        Self {
+            interval,
+            amplitude: 1.0,
+            count: None,
+            stop_action: StopAction::Continue,
+            interval_samples: 1,
+            samples_until_impulse: 0,
+            impulses_left: None,
+        }
+    }
+    /// Set the amplitude of the impulses. The default is 1.0, i.e. full scale.
+    pub fn amplitude(mut self, amplitude: Sample) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+    /// Stop after `count` impulses. By default the impulses go on forever.
+    /// A count of 1 gives a single impulse.
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+    /// Set what happens after the last impulse. The default is
+    /// [`StopAction::Continue`], outputting silence.
+    pub fn stop_action(mut self, stop_action: StopAction) -> Self {
+        self.stop_action = stop_action;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 0, 1))
+    }
+}
+
+impl Gen for ImpulseTrain {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let output = ctx.outputs.iter_mut().next().unwrap();
+        let mut stop_sample = None;
+        for (i, out) in output.iter_mut().enumerate() {
+            *out = 0.0;
+            if self.impulses_left == Some(0) {
+                stop_sample.get_or_insert(i);
+                continue;
+            }
+            if self.samples_until_impulse == 0 {
+                *out = self.amplitude;
+                self.samples_until_impulse = self.interval_samples;
+                if let Some(impulses_left) = &mut self.impulses_left {
+                    *impulses_left -= 1;
+                }
+            }
+            self.samples_until_impulse -= 1;
+        }
+        match stop_sample {
+            Some(stop_sample) => self.stop_action.to_gen_state(stop_sample),
+            None => GenState::Continue,
+        }
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.interval_samples = self.interval.to_samples(sample_rate as u64).max(1);
+        self.samples_until_impulse = 0;
+        self.impulses_left = self.count;
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "out",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "ImpulseTrain"
+    }
+}
+
+/// Upload a full scale [`ImpulseTrain`] to the current graph
+pub fn impulse_train(interval: Seconds) -> Handle<GenericHandle> {
+    ImpulseTrain::new(interval).upload()
+}
+
+/// Bursts of pink noise lasting `burst` at the start of every `interval`,
+/// with silence in between.
+///
+/// The RMS level of the noise during a burst is set through
+/// [`PinkBurst::rms`]. The peaks of the noise stay below 5.5 times the RMS
+/// level, so keep the RMS level at or below 0.18 to avoid clipping.
+///
+/// *outputs*
+/// 0. "out": The noise bursts
+pub struct PinkBurst {
+    burst: Seconds,
+    interval: Seconds,
+    gain: Sample,
+    noise: PinkNoise,
+    burst_samples: u64,
+    interval_samples: u64,
+    position: u64,
+}
+
+impl PinkBurst {
+    /// The RMS level of the pink noise before applying the gain. The noise
+    /// is the mean of `PINK_NOISE_OCTAVES + 1` uniformly distributed white
+    /// noise sources between -1 and 1, each with a variance of 1/3.
+    fn unscaled_rms() -> Sample {
+        1.0 / (3.0 * (PINK_NOISE_OCTAVES + 1) as Sample).sqrt()
+    }
+
+    /// Create pink noise bursts lasting `burst`, starting every `interval`,
+    /// at the default RMS level of 0.1 (-20 dBFS).
+    pub fn new(burst: Seconds, interval: Seconds) -> Self {
+        Self {
+            burst,
+            interval,
+            gain: 0.1 / Self::unscaled_rms(),
+            noise: PinkNoise::new(),
+            burst_samples: 0,
+            interval_samples: 1,
+            position: 0,
+        }
+    }
+    /// Set the RMS level of the noise during a burst
+    pub fn rms(mut self, rms: Sample) -> Self {
+        self.gain = rms / Self::unscaled_rms();
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 0, 1))
+    }
+}
+
+impl Gen for PinkBurst {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let output = ctx.outputs.iter_mut().next().unwrap();
+        for out in output.iter_mut() {
+            *out = if self.position < self.burst_samples {
+                self.noise.process_sample() * self.gain
+            } else {
+                0.0
+            };
+            self.position = (self.position + 1) % self.interval_samples;
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.burst_samples = self.burst.to_samples(sample_rate as u64);
+        self.interval_samples = self.interval.to_samples(sample_rate as u64).max(1);
+        self.position = 0;
+        // Fill every octave of the noise so that the first burst is at the full level
+        for _ in 0..(1 << PINK_NOISE_OCTAVES) {
+            self.noise.process_sample();
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "out",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "PinkBurst"
+    }
+}
+
+/// Upload a [`PinkBurst`] at -20 dBFS RMS to the current graph
+pub fn pink_burst(burst: Seconds, interval: Seconds) -> Handle<GenericHandle> {
+    PinkBurst::new(burst, interval).upload()
+}
+
+/// A square wave which is exactly +amplitude for the first half of every
+/// period and -amplitude for the second half. It isn't band limited, so
+/// that its peak and RMS levels are both exactly the amplitude.
+///
+/// *outputs*
+/// 0. "out": The square wave
+#[derive(Clone, Debug)]
+pub struct FullScaleSquare {
+    freq: f64,
+    amplitude: Sample,
+    phase: f64,
+    phase_increment: f64,
+}
+
+impl FullScaleSquare {
+    /// Create a square wave with the frequency `freq` in Hz
+    pub fn new(freq: Sample) -> Self {
+        Self {
+            freq: freq as f64,
+            amplitude: 1.0,
+            phase: 0.0,
+            phase_increment: 0.0,
+        }
+    }
+    /// Set the amplitude. The default is 1.0, i.e. full scale.
+    pub fn amplitude(mut self, amplitude: Sample) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 0, 1))
+    }
+}
+
+impl Gen for FullScaleSquare {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let output = ctx.outputs.iter_mut().next().unwrap();
+        for out in output.iter_mut() {
+            *out = if self.phase < 0.5 {
+                self.amplitude
+            } else {
+                -self.amplitude
+            };
+            self.phase = (self.phase + self.phase_increment).fract();
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.phase_increment = self.freq / sample_rate as f64;
+        self.phase = 0.0;
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "out",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "FullScaleSquare"
+    }
+}
+
+/// Upload a [`FullScaleSquare`] to the current graph
+pub fn full_scale_square(freq: Sample) -> Handle<GenericHandle> {
+    FullScaleSquare::new(freq).upload()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deconvolve, FullScaleSquare, ImpulseTrain, LogSweep, PinkBurst};
+    use crate::{
+        gen::{testing::GenTester, Gen, GenState, StopAction},
+        time::Seconds,
+        Sample,
+    };
+
+    const SR: Sample = 8000.;
+
+    /// Run `num_samples` samples through the gen, returning the output and
+    /// the state returned by the last block
+    fn render(gen: impl Gen, num_samples: usize) -> (Vec<Sample>, GenState) {
+        let mut gen = GenTester::new(gen, 64, SR);
+        let mut rendered = vec![];
+        let mut state = GenState::Continue;
+        while rendered.len() < num_samples {
+            state = gen.process_block();
+            rendered.extend_from_slice(gen.output(0));
+        }
+        rendered.truncate(num_samples);
+        (rendered, state)
+    }
+
+    #[test]
+    fn sweep_deconvolves_to_impulse_response() {
+        let sweep = LogSweep::new(20., 3500., Seconds::from_seconds_f64(0.5))
+            .stop_action(StopAction::FreeSelf);
+        let (rendered, state) = render(sweep.clone(), 5000);
+        assert_eq!(rendered[..4000], sweep.render(SR)[..]);
+        assert!(matches!(state, GenState::FreeSelf));
+        assert!(rendered[4000..].iter().all(|s| *s == 0.0));
+        // A system delaying by 10 samples with a gain of 0.5 plus an echo
+        let mut recording = vec![0.0; 5000];
+        for (i, s) in rendered.iter().enumerate().take(4000) {
+            recording[i + 10] += s * 0.5;
+            recording[i + 100] += s * 0.25;
+        }
+        let ir = deconvolve(&recording, &sweep, SR);
+        assert_eq!(ir.len(), recording.len());
+        assert!((ir[10] - 0.5).abs() < 0.01, "{}", ir[10]);
+        assert!((ir[100] - 0.25).abs() < 0.01, "{}", ir[100]);
+        let peak = |range: std::ops::Range<usize>| {
+            ir[range]
+                .iter()
+                .fold(0.0 as Sample, |acc, s| acc.max(s.abs()))
+        };
+        // Silent apart from ringing around the impulses, since the sweep is band limited
+        assert!(peak(30..80) < 0.02, "{}", peak(30..80));
+        assert!(peak(130..4500) < 0.02, "{}", peak(130..4500));
+    }
+
+    #[test]
+    fn test_signals() {
+        let (impulses, state) = render(
+            ImpulseTrain::new(Seconds::from_seconds_f64(0.01))
+                .count(3)
+                .stop_action(StopAction::FreeSelf),
+            256,
+        );
+        let positions: Vec<usize> = (0..impulses.len())
+            .filter(|i| impulses[*i] != 0.0)
+            .collect();
+        assert_eq!(positions, vec![0, 80, 160]);
+        assert_eq!(impulses[80], 1.0);
+        assert!(matches!(state, GenState::FreeSelf));
+
+        let (square, _) = render(FullScaleSquare::new(1000.).amplitude(0.5), 16);
+        assert_eq!(square, [[0.5; 4], [-0.5; 4], [0.5; 4], [-0.5; 4]].concat());
+
+        // 0.5 second bursts every second
+        let (bursts, _) = render(
+            PinkBurst::new(
+                Seconds::from_seconds_f64(0.5),
+                Seconds::from_seconds_f64(1.0),
+            )
+            .rms(0.05),
+            8000 * 3,
+        );
+        let rms = |samples: &[Sample]| {
+            (samples.iter().map(|s| s * s).sum::<Sample>() / samples.len() as Sample).sqrt()
+        };
+        assert!((rms(&bursts[..4000]) - 0.05).abs() < 0.005);
+        assert!(bursts[4000..8000].iter().all(|s| *s == 0.0));
+        assert!((rms(&bursts[8000..12000]) - 0.05).abs() < 0.005);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sweep_rejects_equal_frequencies() {
+        LogSweep::new(1000., 1000., Seconds::from_seconds_f64(1.0));
+    }
+}
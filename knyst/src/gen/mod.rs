@@ -11,9 +11,25 @@ mod smoothing;
 pub use smoothing::*;
 mod osc;
 use crate::{graph::NodeId, node_buffer::NodeBufferRef, resources::Resources, Sample};
+pub use knyst_core::gen::{GenState, StopAction};
 pub use osc::*;
+pub mod activity;
+pub mod channel_strip;
+pub mod convolution;
 pub mod delay;
+pub mod expr;
+mod fft;
 pub mod filter;
+pub mod measurement;
+pub mod null_test;
+pub mod param;
+pub mod percussion;
+pub mod playlist;
+pub mod resampler;
+pub mod spectral;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod tuner;
 
 #[allow(unused)]
 use crate::graph::{Connection, Graph};
@@ -49,6 +65,27 @@ pub trait Gen {
     fn output_desc(&self, output: usize) -> &'static str {
         ""
     }
+    /// Return false for inputs whose constant value must change instantly,
+    /// e.g. gates, even if the [`Graph`] is set to dezipper constant changes
+    /// (see [`crate::graph::GraphSettings::dezipper`]).
+    ///
+    /// With the [`impl_gen`] macro, use `#[dezipper(false)]` on the input.
+    /// Default: true
+    #[allow(unused)]
+    fn input_dezipper(&self, input: usize) -> bool {
+        true
+    }
+    /// The value of an input constant when the node is created, which
+    /// [`Graph::reset_input`] sets it back to. Connecting a node to an input
+    /// which is still at a non-zero default sets its constant to 0 so that
+    /// the connection replaces the default.
+    ///
+    /// With the [`impl_gen`] macro, use `#[default(value)]` on the input.
+    /// Default: 0.0
+    #[allow(unused)]
+    fn input_default(&self, input: usize) -> Sample {
+        0.0
+    }
     /// A name identifying this `Gen`.
     fn name(&self) -> &'static str {
         "no_name"
@@ -70,59 +107,3 @@ impl<'a, 'b> GenContext<'a, 'b> {
         self.outputs.block_size()
     }
 }
-
-/// The Gen should return Continue unless it needs to free itself or the Graph it is in.
-///
-/// No promise is made as to when the node or the Graph will be freed so the Node needs to do the right thing
-/// if run again the next block. E.g. a node returning `FreeSelfMendConnections` is expected to act as a
-/// connection bridge from its non constant inputs to its outputs as if it weren't there. Only inputs with a
-/// corresponding output should be passed through, e.g. in\[0\] -> out\[0\], in\[1\] -> out\[1\], in\[2..5\] go nowhere.
-///
-/// The FreeGraph and FreeGraphMendConnections values also return the relative
-/// sample in the current block after which the graph should return 0 or connect
-/// its non constant inputs to its outputs.
-#[derive(Debug, Clone, Copy)]
-pub enum GenState {
-    /// Continue running
-    Continue,
-    /// Free the node containing the Gen
-    FreeSelf,
-    /// Free the node containing the Gen, bridging its input node(s) to its output node(s).
-    FreeSelfMendConnections,
-    /// Free the graph containing the node containing the Gen.
-    FreeGraph(usize),
-    /// Free the graph containing the node containing the Gen, bridging its input node(s) to its output node(s).
-    FreeGraphMendConnections(usize),
-}
-
-/// Specify what happens when a [`Gen`] is done with its processing. This translates to a [`GenState`] being returned from the [`Gen`], but without additional parameters.
-#[derive(Debug, Clone, Copy)]
-pub enum StopAction {
-    /// Continue running
-    Continue,
-    /// Free the node containing the Gen
-    FreeSelf,
-    /// Free the node containing the Gen, bridging its input node(s) to its output node(s).
-    FreeSelfMendConnections,
-    /// Free the graph containing the node containing the Gen.
-    FreeGraph,
-    /// Free the graph containing the node containing the Gen, bridging its input node(s) to its output node(s).
-    FreeGraphMendConnections,
-}
-impl StopAction {
-    /// Convert the [`StopAction`] into a [`GenState`].
-    ///
-    /// `stop_sample` is only used for the `FreeGraph` and
-    /// `FreeGraphMendConnections` variants to communicate from what sample time
-    /// the graph outputs should be 0.
-    #[must_use]
-    pub fn to_gen_state(&self, stop_sample: usize) -> GenState {
-        match self {
-            StopAction::Continue => GenState::Continue,
-            StopAction::FreeSelf => GenState::FreeSelf,
-            StopAction::FreeSelfMendConnections => GenState::FreeSelfMendConnections,
-            StopAction::FreeGraph => GenState::FreeGraph(stop_sample),
-            StopAction::FreeGraphMendConnections => GenState::FreeGraphMendConnections(stop_sample),
-        }
-    }
-}
@@ -26,7 +26,7 @@ impl WhiteNoise {
         GenState::Continue
     }
 }
-const PINK_NOISE_OCTAVES: u32 = 9;
+pub(crate) const PINK_NOISE_OCTAVES: u32 = 9;
 /// Pink noise
 ///
 /// Usually outputs in the +-0.75 range and cannot surpass +-1.0
@@ -0,0 +1,239 @@
+//! A/B null tests for verifying that two signals are identical
+//!
+//! Subtracting two signals which should be the same, e.g. the output of a
+//! [`Gen`] before and after a refactor or with and without oversampling, and
+//! measuring what is left is a quick way of checking that a change is
+//! transparent or within tolerance. [`NullTest`] does this in a running graph
+//! and [`crate::offline::null_test`] compares two offline renders.
+use std::sync::{
+    atomic::{AtomicU32, AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+/// The result of comparing two signals
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NullTestReport {
+    /// The largest absolute difference between the two signals
+    pub max_difference: Sample,
+    /// The index of the first sample with the largest difference
+    pub max_difference_at: u64,
+    /// The root mean square of the difference between the two signals
+    pub rms_difference: Sample,
+    /// The number of samples compared
+    pub num_samples: u64,
+}
+
+impl NullTestReport {
+    /// Returns true if no sample differs by more than `tolerance`
+    pub fn is_within(&self, tolerance: Sample) -> bool {
+        self.max_difference <= tolerance
+    }
+}
+
+/// Accumulates the difference between two signals into a [`NullTestReport`]
+#[derive(Clone, Debug, Default)]
+pub struct NullTestAccumulator {
+    max_difference: Sample,
+    max_difference_at: u64,
+    sum_of_squares: f64,
+    num_samples: u64,
+}
+
+impl NullTestAccumulator {
+    /// Create an accumulator with no samples compared yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Compare two slices sample by sample, writing `a - b` to `difference`
+    /// if it is provided. Only the length of the shortest slice is compared.
+    pub fn compare(&mut self, a: &[Sample], b: &[Sample], difference: Option<&mut [Sample]>) {
+        let len = a.len().min(b.len());
+        let mut add = |i: usize, diff: Sample| {
+            let abs = diff.abs();
+            if abs > self.max_difference {
+                self.max_difference = abs;
+                self.max_difference_at = self.num_samples + i as u64;
+            }
+            self.sum_of_squares += (diff as f64) * (diff as f64);
+        };
+        match difference {
+            Some(difference) => {
+                for (i, ((a, b), out)) in a.iter().zip(b).zip(difference.iter_mut()).enumerate() {
+                    *out = a - b;
+                    add(i, *out);
+                }
+            }
+            None => {
+                for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                    add(i, a - b);
+                }
+            }
+        }
+        self.num_samples += len as u64;
+    }
+    /// The result of all comparisons so far
+    pub fn report(&self) -> NullTestReport {
+        let rms_difference = if self.num_samples == 0 {
+            0.0
+        } else {
+            (self.sum_of_squares / self.num_samples as f64).sqrt() as Sample
+        };
+        NullTestReport {
+            max_difference: self.max_difference,
+            max_difference_at: self.max_difference_at,
+            rms_difference,
+            num_samples: self.num_samples,
+        }
+    }
+}
+
+/// Shared state between a [`NullTest`] and its [`NullTestMeter`]
+#[derive(Default)]
+struct NullTestShared {
+    max_difference: AtomicU32,
+    max_difference_at: AtomicU64,
+    rms_difference: AtomicU32,
+    num_samples: AtomicU64,
+}
+
+/// Reads the [`NullTestReport`] of a running [`NullTest`] from any thread.
+#[derive(Clone)]
+pub struct NullTestMeter {
+    shared: Arc<NullTestShared>,
+}
+
+impl NullTestMeter {
+    /// The result of the comparison so far. The fields are updated once per
+    /// block, but not atomically together, so a report read while the
+    /// [`NullTest`] is running may mix values from two consecutive blocks.
+    pub fn report(&self) -> NullTestReport {
+        let shared = &self.shared;
+        NullTestReport {
+            max_difference: Sample::from_bits(shared.max_difference.load(Ordering::Relaxed)),
+            max_difference_at: shared.max_difference_at.load(Ordering::Relaxed),
+            rms_difference: Sample::from_bits(shared.rms_difference.load(Ordering::Relaxed)),
+            num_samples: shared.num_samples.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Subtracts signal "b" from signal "a", outputting the difference and
+/// measuring it for as long as the node is running. The measurement is read
+/// through the [`NullTestMeter`] returned when creating the [`NullTest`].
+///
+/// *inputs*
+/// 0. "a": The first signal
+/// 1. "b": The second signal
+///
+/// *outputs*
+/// 0. "difference": a - b
+pub struct NullTest {
+    accumulator: NullTestAccumulator,
+    shared: Arc<NullTestShared>,
+}
+
+impl NullTest {
+    /// Create a new [`NullTest`] and the [`NullTestMeter`] for reading its result
+    pub fn new() -> (Self, NullTestMeter) {
+        let shared = Arc::new(NullTestShared::default());
+        (
+            Self {
+                accumulator: NullTestAccumulator::new(),
+                shared: shared.clone(),
+            },
+            NullTestMeter { shared },
+        )
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 2, 1))
+    }
+}
+
+impl Gen for NullTest {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let a = ctx.inputs.get_channel(0);
+        let b = ctx.inputs.get_channel(1);
+        let difference = ctx.outputs.iter_mut().next().unwrap();
+        self.accumulator.compare(a, b, Some(difference));
+        let report = self.accumulator.report();
+        let shared = &self.shared;
+        shared
+            .max_difference
+            .store(report.max_difference.to_bits(), Ordering::Relaxed);
+        shared
+            .max_difference_at
+            .store(report.max_difference_at, Ordering::Relaxed);
+        shared
+            .rms_difference
+            .store(report.rms_difference.to_bits(), Ordering::Relaxed);
+        shared
+            .num_samples
+            .store(report.num_samples, Ordering::Relaxed);
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "a",
+            1 => "b",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "difference",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "NullTest"
+    }
+}
+
+/// Upload a [`NullTest`] to the current graph and return a handle to it
+/// together with the [`NullTestMeter`] for reading the result.
+pub fn null_test() -> (Handle<GenericHandle>, NullTestMeter) {
+    let (null_test, meter) = NullTest::new();
+    (null_test.upload(), meter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NullTestAccumulator;
+
+    #[test]
+    fn accumulator_reports_max_and_rms() {
+        let mut acc = NullTestAccumulator::new();
+        acc.compare(&[1.0, 1.0, 1.0], &[1.0, 1.0, 1.0], None);
+        assert!(acc.report().is_within(0.0));
+        let mut difference = [0.0; 2];
+        acc.compare(&[0.5, 1.0], &[0.0, 1.0], Some(&mut difference));
+        assert_eq!(difference, [0.5, 0.0]);
+        let report = acc.report();
+        assert_eq!(report.num_samples, 5);
+        assert_eq!(report.max_difference, 0.5);
+        assert_eq!(report.max_difference_at, 3);
+        assert!((report.rms_difference - (0.25f32 / 5.).sqrt()).abs() < 1e-6);
+        assert!(!report.is_within(0.1));
+    }
+}
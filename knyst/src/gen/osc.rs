@@ -53,7 +53,7 @@ impl Oscillator {
     #[process]
     pub fn process(
         &mut self,
-        freq: &[Sample],
+        #[default(440.)] freq: &[Sample],
         sig: &mut [Sample],
         resources: &mut Resources,
     ) -> GenState {
@@ -414,7 +414,7 @@ impl HandleData for BufferReaderMultiHandle {
     }
 }
 
-fn output_str(num: usize) -> &'static str {
+pub(super) fn output_str(num: usize) -> &'static str {
     match num {
         0 => "output0",
         1 => "output1",
@@ -488,7 +488,7 @@ impl WavetableOscillatorOwned {
             freq: 0.,
         }
     }
-    fn process(&mut self, freq: &[Sample], sig: &mut [Sample]) -> GenState {
+    fn process(&mut self, #[default(440.)] freq: &[Sample], sig: &mut [Sample]) -> GenState {
         assert!(freq.len() == sig.len());
         for (&freq, o) in freq.iter().zip(sig.iter_mut()) {
             self.set_freq(freq);
@@ -523,7 +523,7 @@ impl Phasor {
         self.freq_to_phase_step_mult = 1.0_f64 / (sample_rate.to_f64() - 0.0);
     }
     #[allow(missing_docs)]
-    pub fn process(&mut self, freq: &[Sample], output: &mut [Sample]) -> GenState {
+    pub fn process(&mut self, #[default(440.)] freq: &[Sample], output: &mut [Sample]) -> GenState {
         for (freq, out) in freq.iter().zip(output.iter_mut()) {
             *out = self.phase as Sample;
             let step = *freq as f64 * self.freq_to_phase_step_mult;
@@ -555,7 +555,7 @@ impl AliasingSaw {
         self.freq_to_phase_step_mult = 2.0_f64 / (sample_rate.to_f64());
     }
     #[allow(missing_docs)]
-    pub fn process(&mut self, freq: &[Sample], output: &mut [Sample]) -> GenState {
+    pub fn process(&mut self, #[default(440.)] freq: &[Sample], output: &mut [Sample]) -> GenState {
         for (freq, out) in freq.iter().zip(output.iter_mut()) {
             *out = self.phase as Sample;
             let step = *freq as f64 * self.freq_to_phase_step_mult;
@@ -0,0 +1,245 @@
+//! Nodes for values controlled from outside the graph
+//!
+//! Changing an input constant goes through the scheduler, which is the right
+//! tool for timed changes but adds latency and traffic on the command channel
+//! for every change. A GUI slider which updates at 60 Hz or more doesn't need
+//! any of that. [`Param`] outputs a value which is set through a
+//! [`ParamSetter`] from any thread, without going through the
+//! [`Controller`](crate::controller::Controller). The value is picked up at
+//! the start of the next block and can optionally be smoothed to avoid
+//! zipper noise.
+//!
+//! [`ConstantGen`] outputs a fixed value, which is useful as a single source
+//! for a value used by many nodes.
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    time::Seconds,
+    Resources, Sample,
+};
+
+/// Outputs a fixed value.
+///
+/// *outputs*
+/// 0. "value": The value
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantGen(pub Sample);
+
+impl ConstantGen {
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 0, 1))
+    }
+}
+
+impl Gen for ConstantGen {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        ctx.outputs.fill(self.0);
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "value",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "ConstantGen"
+    }
+}
+
+/// Sets the value of a [`Param`] from any thread. Setting the value is lock
+/// free and cheap enough to do for every event from a GUI.
+#[derive(Clone, Debug)]
+pub struct ParamSetter {
+    /// [`Sample`] stored as bits
+    value: Arc<AtomicU32>,
+}
+
+impl ParamSetter {
+    /// Set the value of the [`Param`]. It is applied at the start of the next
+    /// block, smoothed if the [`Param`] has a smoothing time.
+    pub fn set(&self, value: Sample) {
+        self.value.store(value.to_bits(), Ordering::Relaxed);
+    }
+    /// The latest value set, which the [`Param`] may still be moving towards
+    pub fn get(&self) -> Sample {
+        Sample::from_bits(self.value.load(Ordering::Relaxed))
+    }
+}
+
+/// Outputs a value set through its [`ParamSetter`] from any thread, see the
+/// [module documentation](self).
+///
+/// *outputs*
+/// 0. "value": The value, smoothed if a smoothing time is set
+pub struct Param {
+    target: Arc<AtomicU32>,
+    smoothing: Seconds,
+    smoothing_samples: usize,
+    current_target: Sample,
+    current_value: Sample,
+    step: Sample,
+    steps_left: usize,
+}
+
+impl Param {
+    /// Create a new [`Param`] starting at `initial_value`, together with the
+    /// [`ParamSetter`] for changing its value.
+    pub fn new(initial_value: Sample) -> (Self, ParamSetter) {
+        let target = Arc::new(AtomicU32::new(initial_value.to_bits()));
+        (
+            Self {
+                target: target.clone(),
+                smoothing: Seconds::ZERO,
+                smoothing_samples: 0,
+                current_target: initial_value,
+                current_value: initial_value,
+                step: 0.0,
+                steps_left: 0,
+            },
+            ParamSetter { value: target },
+        )
+    }
+    /// Move linearly to a new value over `time` instead of jumping to it.
+    /// By default there is no smoothing.
+    pub fn smoothing(mut self, time: Seconds) -> Self {
+        self.smoothing = time;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 0, 1))
+    }
+}
+
+impl Gen for Param {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let target = Sample::from_bits(self.target.load(Ordering::Relaxed));
+        if target != self.current_target {
+            self.current_target = target;
+            if self.smoothing_samples == 0 {
+                self.current_value = target;
+                self.steps_left = 0;
+            } else {
+                self.step = (target - self.current_value) / self.smoothing_samples as Sample;
+                self.steps_left = self.smoothing_samples;
+            }
+        }
+        let output = ctx.outputs.iter_mut().next().unwrap();
+        if self.steps_left == 0 {
+            output.fill(self.current_value);
+        } else {
+            for out in output.iter_mut() {
+                if self.steps_left > 0 {
+                    self.steps_left -= 1;
+                    self.current_value = if self.steps_left == 0 {
+                        // Avoid accumulated rounding errors
+                        self.current_target
+                    } else {
+                        self.current_value + self.step
+                    };
+                }
+                *out = self.current_value;
+            }
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.smoothing_samples = self.smoothing.to_samples(sample_rate as u64) as usize;
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "value",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Param"
+    }
+}
+
+/// Upload a [`ConstantGen`] outputting `value` to the current graph and
+/// return a handle to it. To set an input to a constant value without a
+/// node, use [`crate::graph::connection::constant`] or `Handle::set` instead.
+pub fn constant_gen(value: Sample) -> Handle<GenericHandle> {
+    ConstantGen(value).upload()
+}
+
+/// Upload a [`Param`] without smoothing to the current graph and return a
+/// handle to it together with the [`ParamSetter`] for changing its value.
+pub fn param(initial_value: Sample) -> (Handle<GenericHandle>, ParamSetter) {
+    let (param, setter) = Param::new(initial_value);
+    (param.upload(), setter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConstantGen, Param};
+    use crate::{gen::testing::GenTester, time::Seconds, Sample};
+
+    const BLOCK_SIZE: usize = 4;
+    const SR: Sample = 100.;
+
+    #[test]
+    fn setter_changes_value() {
+        let (param, setter) = Param::new(0.5);
+        let mut param = GenTester::new(param, BLOCK_SIZE, SR);
+        param.process_block();
+        assert_eq!(param.output(0), [0.5; BLOCK_SIZE]);
+        setter.set(2.0);
+        assert_eq!(setter.get(), 2.0);
+        param.process_block();
+        assert_eq!(param.output(0), [2.0; BLOCK_SIZE]);
+
+        // 0.06 seconds is 6 samples at this sample rate
+        let (param, setter) = Param::new(0.0);
+        let mut param = GenTester::new(
+            param.smoothing(Seconds::from_seconds_f64(0.06)),
+            BLOCK_SIZE,
+            SR,
+        );
+        setter.set(3.0);
+        param.process_block();
+        assert_eq!(param.output(0), [0.5, 1.0, 1.5, 2.0]);
+        param.process_block();
+        assert_eq!(param.output(0), [2.5, 3.0, 3.0, 3.0]);
+    }
+
+    #[test]
+    fn constant_gen_outputs_its_value() {
+        let mut constant = GenTester::new(ConstantGen(-0.25), BLOCK_SIZE, SR);
+        constant.process_block();
+        assert_eq!(constant.output(0), [-0.25; BLOCK_SIZE]);
+    }
+}
@@ -0,0 +1,419 @@
+//! Synthesised drums for sketches and rhythm oriented examples
+//!
+//! [`KickDrum`], [`SnareDrum`] and [`HiHat`] are each a complete drum voice in
+//! a single node, played by sending a trigger to their "hit" input. The value
+//! of the trigger sets the velocity of the hit, i.e. a trigger of 0.5 plays at
+//! half the amplitude. The sound is configured when creating the drum, e.g.
+//!
+//! ```no_run
+//! # use knyst::prelude::*;
+//! use knyst::gen::percussion::{kick_drum, KickDrum};
+//! let kick = KickDrum::new().freq(45.).decay(0.8).upload();
+//! graph_output(0, kick);
+//! kick.hit_trig();
+//! // Or with the default sound
+//! let kick = kick_drum();
+//! ```
+use knyst_macro::impl_gen;
+
+use super::filter::svf::{SvfFilter, SvfFilterType};
+use super::random::next_randomness_seed;
+use crate as knyst;
+use crate::controller::KnystCommands;
+use crate::prelude::GenState;
+use crate::trig::is_trigger;
+use crate::{Sample, SampleRate, Trig};
+
+const TAU: Sample = std::f64::consts::TAU as Sample;
+/// The amplitude below which an envelope is considered finished
+const SILENT: Sample = 0.0001;
+
+/// Returns the per sample multiplier for an exponential decay reaching -60 dB
+/// after `seconds`.
+fn decay_coeff(seconds: Sample, sample_rate: Sample) -> Sample {
+    if seconds <= 0.0 {
+        0.0
+    } else {
+        (0.001 as Sample).powf(1.0 / (seconds * sample_rate))
+    }
+}
+
+/// Exponentially decaying envelope restarted by every hit
+#[derive(Clone, Copy, Debug)]
+struct Decay {
+    value: Sample,
+    coeff: Sample,
+}
+impl Decay {
+    fn new() -> Self {
+        Self {
+            value: 0.0,
+            coeff: 0.0,
+        }
+    }
+    fn set_decay(&mut self, seconds: Sample, sample_rate: Sample) {
+        self.coeff = decay_coeff(seconds, sample_rate);
+    }
+    fn restart(&mut self) {
+        self.value = 1.0;
+    }
+    #[inline]
+    fn next_sample(&mut self) -> Sample {
+        let value = self.value;
+        self.value = if value < SILENT {
+            0.0
+        } else {
+            value * self.coeff
+        };
+        value
+    }
+}
+
+/// Kick drum made from a sine wave with a downwards pitch sweep and a short
+/// noise click for the attack.
+///
+/// *inputs*
+/// 0. "hit": Trigger to play the drum, the value of the trigger sets the velocity
+///
+/// *outputs*
+/// 0. "output": The drum sound
+pub struct KickDrum {
+    freq: Sample,
+    sweep: Sample,
+    sweep_time: Sample,
+    decay_time: Sample,
+    click: Sample,
+    velocity: Sample,
+    phase: Sample,
+    amp: Decay,
+    pitch: Decay,
+    click_amp: Decay,
+    rng: fastrand::Rng,
+}
+
+#[impl_gen]
+impl KickDrum {
+    /// Create a kick drum with a default sound
+    #[new]
+    pub fn new() -> Self {
+        let mut rng = fastrand::Rng::new();
+        rng.seed(next_randomness_seed());
+        Self {
+            freq: 50.,
+            sweep: 4.,
+            sweep_time: 0.08,
+            decay_time: 0.5,
+            click: 0.3,
+            velocity: 0.0,
+            phase: 0.0,
+            amp: Decay::new(),
+            pitch: Decay::new(),
+            click_amp: Decay::new(),
+            rng,
+        }
+    }
+    /// Set the frequency the pitch sweep ends at in Hz
+    pub fn freq(mut self, freq: Sample) -> Self {
+        self.freq = freq;
+        self
+    }
+    /// Set the frequency the pitch sweep starts at as a multiple of the frequency
+    pub fn sweep(mut self, sweep: Sample) -> Self {
+        self.sweep = sweep;
+        self
+    }
+    /// Set the duration of the pitch sweep in seconds
+    pub fn sweep_time(mut self, seconds: Sample) -> Self {
+        self.sweep_time = seconds;
+        self
+    }
+    /// Set the time in seconds until the drum has decayed by 60 dB
+    pub fn decay(mut self, seconds: Sample) -> Self {
+        self.decay_time = seconds;
+        self
+    }
+    /// Set the amplitude of the click at the start of every hit relative to
+    /// the sine wave
+    pub fn click(mut self, click: Sample) -> Self {
+        self.click = click;
+        self
+    }
+    #[process]
+    #[allow(missing_docs)]
+    pub fn process(
+        &mut self,
+        hit: &[Trig],
+        output: &mut [Sample],
+        sample_rate: SampleRate,
+    ) -> GenState {
+        let sample_rate = *sample_rate;
+        for (&hit, out) in hit.iter().zip(output.iter_mut()) {
+            if is_trigger(hit) {
+                self.velocity = hit;
+                self.phase = 0.0;
+                self.amp.restart();
+                self.pitch.restart();
+                self.click_amp.restart();
+            }
+            let freq = self.freq * (1.0 + (self.sweep - 1.0) * self.pitch.next_sample());
+            self.phase = (self.phase + freq / sample_rate).fract();
+            let tone = (self.phase * TAU).sin() * self.amp.next_sample();
+            let click = (self.rng.f32() as Sample * 2.0 - 1.0) * self.click_amp.next_sample();
+            *out = (tone + click * self.click) / (1.0 + self.click) * self.velocity;
+        }
+        GenState::Continue
+    }
+    #[init]
+    fn init(&mut self, sample_rate: SampleRate) {
+        self.amp.set_decay(self.decay_time, *sample_rate);
+        self.pitch.set_decay(self.sweep_time, *sample_rate);
+        self.click_amp.set_decay(0.005, *sample_rate);
+    }
+}
+
+impl Default for KickDrum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Snare drum made from a sine wave for the body and high pass filtered noise
+/// for the snares.
+///
+/// *inputs*
+/// 0. "hit": Trigger to play the drum, the value of the trigger sets the velocity
+///
+/// *outputs*
+/// 0. "output": The drum sound
+pub struct SnareDrum {
+    freq: Sample,
+    tone_decay_time: Sample,
+    noise_decay_time: Sample,
+    snappy: Sample,
+    velocity: Sample,
+    phase: Sample,
+    tone_amp: Decay,
+    noise_amp: Decay,
+    noise_filter: SvfFilter,
+    rng: fastrand::Rng,
+}
+
+#[impl_gen]
+impl SnareDrum {
+    /// Create a snare drum with a default sound
+    #[new]
+    pub fn new() -> Self {
+        let mut rng = fastrand::Rng::new();
+        rng.seed(next_randomness_seed());
+        Self {
+            freq: 185.,
+            tone_decay_time: 0.15,
+            noise_decay_time: 0.25,
+            snappy: 0.7,
+            velocity: 0.0,
+            phase: 0.0,
+            tone_amp: Decay::new(),
+            noise_amp: Decay::new(),
+            noise_filter: SvfFilter::new(SvfFilterType::High, 1800., 0.7, 0.0),
+            rng,
+        }
+    }
+    /// Set the frequency of the body in Hz
+    pub fn freq(mut self, freq: Sample) -> Self {
+        self.freq = freq;
+        self
+    }
+    /// Set the time in seconds until the body has decayed by 60 dB
+    pub fn tone_decay(mut self, seconds: Sample) -> Self {
+        self.tone_decay_time = seconds;
+        self
+    }
+    /// Set the time in seconds until the snares have decayed by 60 dB
+    pub fn noise_decay(mut self, seconds: Sample) -> Self {
+        self.noise_decay_time = seconds;
+        self
+    }
+    /// Set the amplitude of the snares relative to the body
+    pub fn snappy(mut self, snappy: Sample) -> Self {
+        self.snappy = snappy;
+        self
+    }
+    #[process]
+    #[allow(missing_docs)]
+    pub fn process(
+        &mut self,
+        hit: &[Trig],
+        output: &mut [Sample],
+        sample_rate: SampleRate,
+    ) -> GenState {
+        let sample_rate = *sample_rate;
+        for (&hit, out) in hit.iter().zip(output.iter_mut()) {
+            if is_trigger(hit) {
+                self.velocity = hit;
+                self.phase = 0.0;
+                self.tone_amp.restart();
+                self.noise_amp.restart();
+            }
+            self.phase = (self.phase + self.freq / sample_rate).fract();
+            let tone = (self.phase * TAU).sin() * self.tone_amp.next_sample();
+            let noise = self
+                .noise_filter
+                .process_sample(self.rng.f32() as Sample * 2.0 - 1.0)
+                * self.noise_amp.next_sample();
+            *out = (tone + noise * self.snappy) / (1.0 + self.snappy) * self.velocity;
+        }
+        GenState::Continue
+    }
+    #[init]
+    fn init(&mut self, sample_rate: SampleRate) {
+        self.tone_amp.set_decay(self.tone_decay_time, *sample_rate);
+        self.noise_amp
+            .set_decay(self.noise_decay_time, *sample_rate);
+        self.noise_filter.init(sample_rate);
+        self.noise_filter.reset();
+    }
+}
+
+impl Default for SnareDrum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The frequency ratios of the square wave oscillators of a [`HiHat`], based on
+/// the oscillators of the TR-808 cymbal circuit.
+const HI_HAT_RATIOS: [Sample; 6] = [1.0, 1.4826, 1.8003, 2.5461, 2.6303, 3.8967];
+
+/// Hi-hat made from a bank of inharmonic square wave oscillators, band pass
+/// and high pass filtered, with a short decay. Use a longer decay for an open
+/// hi-hat.
+///
+/// *inputs*
+/// 0. "hit": Trigger to play the drum, the value of the trigger sets the velocity
+///
+/// *outputs*
+/// 0. "output": The drum sound
+pub struct HiHat {
+    freq: Sample,
+    decay_time: Sample,
+    velocity: Sample,
+    phases: [Sample; 6],
+    amp: Decay,
+    band_pass: SvfFilter,
+    high_pass: SvfFilter,
+}
+
+#[impl_gen]
+impl HiHat {
+    /// Create a closed hi-hat with a default sound
+    #[new]
+    pub fn new() -> Self {
+        Self {
+            freq: 205.,
+            decay_time: 0.08,
+            velocity: 0.0,
+            phases: [0.0; 6],
+            amp: Decay::new(),
+            band_pass: SvfFilter::new(SvfFilterType::Band, 10000., 1.0, 0.0),
+            high_pass: SvfFilter::new(SvfFilterType::High, 7000., 0.7, 0.0),
+        }
+    }
+    /// Set the frequency of the lowest oscillator in Hz. The other oscillators
+    /// follow at fixed inharmonic ratios.
+    pub fn freq(mut self, freq: Sample) -> Self {
+        self.freq = freq;
+        self
+    }
+    /// Set the time in seconds until the hi-hat has decayed by 60 dB
+    pub fn decay(mut self, seconds: Sample) -> Self {
+        self.decay_time = seconds;
+        self
+    }
+    #[process]
+    #[allow(missing_docs)]
+    pub fn process(
+        &mut self,
+        hit: &[Trig],
+        output: &mut [Sample],
+        sample_rate: SampleRate,
+    ) -> GenState {
+        let sample_rate = *sample_rate;
+        for (&hit, out) in hit.iter().zip(output.iter_mut()) {
+            if is_trigger(hit) {
+                self.velocity = hit;
+                self.amp.restart();
+            }
+            let mut metal = 0.0;
+            for (phase, ratio) in self.phases.iter_mut().zip(HI_HAT_RATIOS) {
+                *phase = (*phase + self.freq * ratio / sample_rate).fract();
+                metal += if *phase < 0.5 { 1.0 } else { -1.0 };
+            }
+            let metal = self
+                .high_pass
+                .process_sample(self.band_pass.process_sample(metal / 6.0));
+            *out = metal * self.amp.next_sample() * self.velocity;
+        }
+        GenState::Continue
+    }
+    #[init]
+    fn init(&mut self, sample_rate: SampleRate) {
+        self.amp.set_decay(self.decay_time, *sample_rate);
+        self.band_pass.init(sample_rate);
+        self.band_pass.reset();
+        self.high_pass.init(sample_rate);
+        self.high_pass.reset();
+    }
+}
+
+impl Default for HiHat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HiHat, KickDrum, SnareDrum};
+    use crate::{
+        gen::{testing::GenTester, Gen},
+        Sample,
+    };
+
+    /// Plays one hit with the given velocity after one silent block and
+    /// returns the peak of every block of 64 samples.
+    fn block_peaks(drum: impl Gen, velocity: Sample, num_blocks: usize) -> Vec<Sample> {
+        let mut tester = GenTester::new(drum, 64, 44100.);
+        let mut peaks = Vec::with_capacity(num_blocks);
+        for block in 0..num_blocks {
+            tester.input_mut(0)[0] = if block == 1 { velocity } else { 0.0 };
+            tester.process_block();
+            peaks.push(tester.output_peak(0));
+        }
+        peaks
+    }
+
+    #[test]
+    fn drums_sound_when_hit_and_decay() {
+        let drums = [
+            ("kick", block_peaks(KickDrum::new(), 1.0, 800)),
+            ("snare", block_peaks(SnareDrum::new(), 1.0, 400)),
+            ("hihat", block_peaks(HiHat::new(), 1.0, 200)),
+        ];
+        for (name, peaks) in drums {
+            assert_eq!(peaks[0], 0.0, "{name} sounded before being hit");
+            assert!(peaks[1] > 0.1, "{name} peak: {}", peaks[1]);
+            assert!(peaks[1] < 1.5, "{name} peak: {}", peaks[1]);
+            let tail = peaks[peaks.len() - 1];
+            assert!(tail < 0.001, "{name} tail: {tail}");
+        }
+    }
+
+    #[test]
+    fn trigger_value_sets_the_velocity() {
+        let loud = block_peaks(KickDrum::new().click(0.0), 1.0, 8);
+        let quiet = block_peaks(KickDrum::new().click(0.0), 0.5, 8);
+        for (loud, quiet) in loud.iter().zip(quiet.iter()) {
+            assert!((loud * 0.5 - quiet).abs() < 1e-5, "{loud} {quiet}");
+        }
+    }
+}
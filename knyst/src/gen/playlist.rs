@@ -0,0 +1,580 @@
+//! Gapless playback of a queue of buffer regions
+//!
+//! A [`PlaylistPlayer`] plays [`PlaylistItem`]s, regions of buffers in the
+//! [`Resources`], back to back without gaps, optionally crossfading into an
+//! item. The queue is edited while the player is running through the
+//! [`PlaylistEditor`] returned when creating the player, e.g. for an
+//! installation playing a curated sequence of sound files around the clock.
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    buffer::BufferKey,
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    resources::BufferId,
+    time::Seconds,
+    Resources, Sample,
+};
+
+/// The length of the fade out when skipping an item without a crossfade
+const SKIP_FADE_SECONDS: f64 = 0.005;
+
+/// A region of a buffer to be played by a [`PlaylistPlayer`]
+#[derive(Clone, Copy, Debug)]
+pub struct PlaylistItem {
+    /// The buffer to play from
+    pub buffer: BufferId,
+    /// Where in the buffer to start playing
+    pub start: Seconds,
+    /// Where in the buffer to stop playing. Plays to the end of the buffer if None.
+    pub end: Option<Seconds>,
+    /// The amplitude of the item
+    pub gain: Sample,
+    /// The length of the crossfade from the previous item into this one. The
+    /// item starts right after the previous item if None.
+    pub crossfade: Option<Seconds>,
+}
+
+impl PlaylistItem {
+    /// Play all of `buffer` at unity gain without a crossfade
+    pub fn new(buffer: BufferId) -> Self {
+        Self {
+            buffer,
+            start: Seconds::ZERO,
+            end: None,
+            gain: 1.0,
+            crossfade: None,
+        }
+    }
+    /// Set where in the buffer to start playing
+    pub fn start(mut self, start: Seconds) -> Self {
+        self.start = start;
+        self
+    }
+    /// Set where in the buffer to stop playing
+    pub fn end(mut self, end: Seconds) -> Self {
+        self.end = Some(end);
+        self
+    }
+    /// Set the amplitude of the item
+    pub fn gain(mut self, gain: Sample) -> Self {
+        self.gain = gain;
+        self
+    }
+    /// Set the length of the crossfade from the previous item into this one
+    pub fn crossfade(mut self, crossfade: Seconds) -> Self {
+        self.crossfade = Some(crossfade);
+        self
+    }
+}
+
+/// Error from editing the queue of a [`PlaylistPlayer`]
+#[derive(thiserror::Error, Debug)]
+pub enum PlaylistError {
+    /// Too many edits have been sent without the [`PlaylistPlayer`] processing them.
+    #[error("The queue of playlist commands is full.")]
+    CommandQueueFull,
+    /// The queue of items has reached the capacity the [`PlaylistPlayer`] was created with.
+    #[error("The playlist is full.")]
+    PlaylistFull,
+}
+
+enum PlaylistCommand {
+    Push(PlaylistItem),
+    PlayNext(PlaylistItem),
+    Remove(usize),
+    Clear,
+    Skip,
+}
+
+/// Shared state between a [`PlaylistPlayer`] and its [`PlaylistEditor`]
+#[derive(Default)]
+struct PlaylistShared {
+    /// The number of items in the queue of the player. Only written by the player.
+    queue_len: AtomicUsize,
+    /// The number of items sent by the editor but not yet added to the queue
+    /// by the player. Only incremented by the editor and only decremented by
+    /// the player, so that neither overwrites the other's count.
+    pending: AtomicUsize,
+    /// The number of items that have started playing
+    items_started: AtomicU64,
+}
+
+/// Edits the queue of a running [`PlaylistPlayer`]. Edits are applied at the
+/// start of the next block, in the order they were made.
+pub struct PlaylistEditor {
+    command_producer: rtrb::Producer<PlaylistCommand>,
+    shared: Arc<PlaylistShared>,
+    capacity: usize,
+}
+
+impl PlaylistEditor {
+    fn send(&mut self, command: PlaylistCommand) -> Result<(), PlaylistError> {
+        self.command_producer
+            .push(command)
+            .map_err(|_| PlaylistError::CommandQueueFull)
+    }
+    /// Reserve room in the queue for an item and send the command adding it
+    fn send_item(&mut self, command: PlaylistCommand) -> Result<(), PlaylistError> {
+        if self.queue_len() >= self.capacity {
+            return Err(PlaylistError::PlaylistFull);
+        }
+        self.shared.pending.fetch_add(1, Ordering::SeqCst);
+        let result = self.send(command);
+        if result.is_err() {
+            self.shared.pending.fetch_sub(1, Ordering::SeqCst);
+        }
+        result
+    }
+    /// Add an item to the end of the queue
+    pub fn push(&mut self, item: PlaylistItem) -> Result<(), PlaylistError> {
+        self.send_item(PlaylistCommand::Push(item))
+    }
+    /// Add an item to the front of the queue so that it plays after the current item
+    pub fn play_next(&mut self, item: PlaylistItem) -> Result<(), PlaylistError> {
+        self.send_item(PlaylistCommand::PlayNext(item))
+    }
+    /// Remove an item from the queue. Index 0 is the item that plays after
+    /// the current item. Does nothing if there is no item at `index`.
+    pub fn remove(&mut self, index: usize) -> Result<(), PlaylistError> {
+        self.send(PlaylistCommand::Remove(index))
+    }
+    /// Remove all items from the queue. The current item keeps playing.
+    pub fn clear(&mut self) -> Result<(), PlaylistError> {
+        self.send(PlaylistCommand::Clear)
+    }
+    /// Stop the current item and continue with the next, using its
+    /// crossfade if it has one or a short fade out otherwise.
+    pub fn skip(&mut self) -> Result<(), PlaylistError> {
+        self.send(PlaylistCommand::Skip)
+    }
+    /// The number of items waiting to be played, not including the current
+    /// item, but including items sent and not yet added by the player
+    pub fn queue_len(&self) -> usize {
+        self.shared.queue_len.load(Ordering::SeqCst) + self.shared.pending.load(Ordering::SeqCst)
+    }
+    /// The number of items that have started playing since the player was created
+    pub fn items_started(&self) -> u64 {
+        self.shared.items_started.load(Ordering::SeqCst)
+    }
+}
+
+/// An item that is playing
+struct PlaylistVoice {
+    key: BufferKey,
+    gain: Sample,
+    /// Read position in buffer frames
+    position: f64,
+    /// End position in buffer frames
+    end: f64,
+    /// Buffer frames per output sample
+    rate: f64,
+    fade_in_length: f64,
+    fade_in_position: f64,
+    /// Fade out over this many output samples before the end, if > 0
+    fade_out_length: f64,
+}
+
+impl PlaylistVoice {
+    fn new(item: &PlaylistItem, resources: &Resources, sample_rate: Sample) -> Option<Self> {
+        let key = resources.buffer_key_from_id(item.buffer)?;
+        let buffer = resources.buffer(key)?;
+        let buffer_sample_rate = buffer.sample_rate() as u64;
+        let start = item.start.to_samples(buffer_sample_rate) as f64;
+        let end = item.end.map_or(buffer.num_frames(), |end| {
+            (end.to_samples(buffer_sample_rate) as f64).min(buffer.num_frames())
+        });
+        if start >= end {
+            return None;
+        }
+        Some(Self {
+            key,
+            gain: item.gain,
+            position: start,
+            end,
+            rate: buffer_sample_rate as f64 / sample_rate as f64,
+            fade_in_length: 0.0,
+            fade_in_position: 0.0,
+            fade_out_length: 0.0,
+        })
+    }
+    /// The number of output samples left to play
+    fn remaining(&self) -> f64 {
+        (self.end - self.position) / self.rate
+    }
+    /// The equal power fade gain at the current position
+    fn envelope(&self) -> Sample {
+        let mut envelope = 1.0;
+        if self.fade_in_position < self.fade_in_length {
+            envelope *=
+                (self.fade_in_position / self.fade_in_length * std::f64::consts::FRAC_PI_2).sin();
+        }
+        let remaining = self.remaining();
+        if remaining < self.fade_out_length {
+            envelope *= (remaining / self.fade_out_length * std::f64::consts::FRAC_PI_2).sin();
+        }
+        envelope as Sample
+    }
+    /// Add the current frame to the output, returning false when the voice has finished
+    fn process_frame(
+        &mut self,
+        resources: &Resources,
+        ctx: &mut GenContext,
+        num_channels: usize,
+        i: usize,
+    ) -> bool {
+        let Some(buffer) = resources.buffer(self.key) else {
+            return false;
+        };
+        let amp = self.gain * self.envelope();
+        let frame = buffer.get_interleaved(self.position as usize);
+        for channel in 0..num_channels {
+            let value = ctx.outputs.read(channel, i) + frame[channel % frame.len()] * amp;
+            ctx.outputs.write(value, channel, i);
+        }
+        self.position += self.rate;
+        self.fade_in_position += 1.0;
+        self.position < self.end
+    }
+}
+
+/// Plays a queue of buffer regions back to back without gaps.
+///
+/// Create it with [`PlaylistPlayer::new`] which also returns a
+/// [`PlaylistEditor`] for editing the queue while the player is running.
+/// Buffers with fewer channels than the player are repeated over the
+/// outputs, e.g. a mono buffer plays on both channels of a stereo player.
+/// When the queue is empty the player outputs silence until more items are
+/// added. Items which can't be played, e.g. because their buffer doesn't
+/// exist, are removed from the queue when they are reached, also when
+/// looping.
+///
+/// *outputs*
+/// 0..N: One output per channel
+pub struct PlaylistPlayer {
+    num_channels: usize,
+    queue: VecDeque<PlaylistItem>,
+    capacity: usize,
+    looping: bool,
+    current: Option<PlaylistVoice>,
+    /// The voice being crossfaded into
+    next: Option<PlaylistVoice>,
+    command_consumer: rtrb::Consumer<PlaylistCommand>,
+    shared: Arc<PlaylistShared>,
+    sample_rate: Sample,
+}
+
+impl PlaylistPlayer {
+    /// Create a new player with `num_channels` outputs and room for
+    /// `capacity` items in the queue, returning the [`PlaylistEditor`] for
+    /// editing the queue.
+    pub fn new(num_channels: usize, capacity: usize) -> (Self, PlaylistEditor) {
+        let (command_producer, command_consumer) = rtrb::RingBuffer::new(capacity.max(64));
+        let shared = Arc::new(PlaylistShared::default());
+        (
+            Self {
+                num_channels,
+                queue: VecDeque::with_capacity(capacity),
+                capacity,
+                looping: false,
+                current: None,
+                next: None,
+                command_consumer,
+                shared: shared.clone(),
+                sample_rate: 0.,
+            },
+            PlaylistEditor {
+                command_producer,
+                shared,
+                capacity,
+            },
+        )
+    }
+    /// Add items to the queue before the player starts.
+    ///
+    /// # Panics
+    /// If the number of items exceeds the capacity of the player.
+    pub fn items(mut self, items: impl IntoIterator<Item = PlaylistItem>) -> Self {
+        for item in items {
+            assert!(
+                self.queue.len() < self.capacity,
+                "More items than the capacity of the PlaylistPlayer"
+            );
+            self.queue.push_back(item);
+        }
+        self.shared
+            .queue_len
+            .store(self.queue.len(), Ordering::SeqCst);
+        self
+    }
+    /// Set whether items are put back at the end of the queue when they start
+    /// playing, repeating the playlist forever.
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let num_channels = self.num_channels;
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 0, num_channels))
+    }
+    /// Add an item sent by the [`PlaylistEditor`] to the queue. The editor
+    /// checks the capacity, but an item is dropped rather than allocating if
+    /// the queue is full anyway.
+    fn add_item(&mut self, item: PlaylistItem, front: bool) {
+        if self.queue.len() < self.capacity {
+            if front {
+                self.queue.push_front(item);
+            } else {
+                self.queue.push_back(item);
+            }
+        }
+        // Update the length before releasing the reservation so that the
+        // editor never sees too few items
+        self.store_queue_len();
+        self.shared.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+    fn store_queue_len(&self) {
+        self.shared
+            .queue_len
+            .store(self.queue.len(), Ordering::SeqCst);
+    }
+    fn apply_commands(&mut self) {
+        while let Ok(command) = self.command_consumer.pop() {
+            match command {
+                PlaylistCommand::Push(item) => self.add_item(item, false),
+                PlaylistCommand::PlayNext(item) => self.add_item(item, true),
+                PlaylistCommand::Remove(index) => {
+                    self.queue.remove(index);
+                }
+                PlaylistCommand::Clear => self.queue.clear(),
+                PlaylistCommand::Skip => {
+                    if let Some(current) = &mut self.current {
+                        let fade_length = match self.queue.front() {
+                            Some(PlaylistItem {
+                                crossfade: Some(crossfade),
+                                ..
+                            }) => crossfade.to_seconds_f64(),
+                            _ => SKIP_FADE_SECONDS,
+                        } * self.sample_rate as f64;
+                        current.end = current
+                            .end
+                            .min(current.position + fade_length * current.rate);
+                        current.fade_out_length = current.remaining();
+                    }
+                }
+            }
+        }
+        self.store_queue_len();
+    }
+    /// Start the next playable item in the queue, removing items which can't be played
+    fn start_next(&mut self, resources: &Resources) -> Option<PlaylistVoice> {
+        while let Some(item) = self.queue.pop_front() {
+            if let Some(voice) = PlaylistVoice::new(&item, resources, self.sample_rate) {
+                if self.looping {
+                    self.queue.push_back(item);
+                }
+                self.shared.items_started.fetch_add(1, Ordering::SeqCst);
+                self.store_queue_len();
+                return Some(voice);
+            }
+        }
+        self.store_queue_len();
+        None
+    }
+}
+
+impl Gen for PlaylistPlayer {
+    fn process(&mut self, mut ctx: GenContext, resources: &mut Resources) -> GenState {
+        self.apply_commands();
+        for output in ctx.outputs.iter_mut() {
+            output.fill(0.0);
+        }
+        let num_channels = self.num_channels;
+        for i in 0..ctx.block_size() {
+            if self.current.is_none() {
+                self.current = self.start_next(resources);
+            }
+            let Some(current) = &mut self.current else {
+                continue;
+            };
+            // Start crossfading into the next item when the current item is
+            // within the crossfade time from its end.
+            if self.next.is_none() {
+                if let Some(PlaylistItem {
+                    crossfade: Some(crossfade),
+                    ..
+                }) = self.queue.front()
+                {
+                    let remaining = current.remaining();
+                    if remaining <= crossfade.to_seconds_f64() * self.sample_rate as f64 {
+                        current.fade_out_length = remaining;
+                        self.next = self.start_next(resources);
+                        if let Some(next) = &mut self.next {
+                            next.fade_in_length = remaining.min(next.remaining());
+                        }
+                    }
+                }
+            }
+            let current = self.current.as_mut().unwrap();
+            let current_playing = current.process_frame(resources, &mut ctx, num_channels, i);
+            let next_playing = match &mut self.next {
+                Some(next) => next.process_frame(resources, &mut ctx, num_channels, i),
+                None => false,
+            };
+            if !current_playing {
+                self.current = self.next.take();
+            } else if !next_playing {
+                self.next = None;
+            }
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        0
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.num_channels
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        super::osc::output_str(output)
+    }
+
+    fn name(&self) -> &'static str {
+        "PlaylistPlayer"
+    }
+}
+
+/// Upload a [`PlaylistPlayer`] to the current graph and return a handle to it
+/// together with the [`PlaylistEditor`] for editing its queue.
+pub fn playlist_player(
+    num_channels: usize,
+    capacity: usize,
+    items: impl IntoIterator<Item = PlaylistItem>,
+) -> (Handle<GenericHandle>, PlaylistEditor) {
+    let (player, editor) = PlaylistPlayer::new(num_channels, capacity);
+    (player.items(items).upload(), editor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlaylistItem, PlaylistPlayer};
+    use crate::{
+        buffer::Buffer, gen::testing::GenTester, resources::BufferId, time::Seconds, Resources,
+        Sample,
+    };
+
+    const BLOCK_SIZE: usize = 16;
+    const SR: Sample = 1000.;
+
+    fn insert(resources: &mut Resources, value: Sample, len: usize) -> BufferId {
+        let buffer = Buffer::from_vec(vec![value; len], SR as f64);
+        let id = BufferId::new(&buffer);
+        resources.insert_buffer_with_id(buffer, id).unwrap();
+        id
+    }
+
+    fn render(player: &mut GenTester<PlaylistPlayer>, blocks: usize) -> Vec<Sample> {
+        let mut output = vec![];
+        for _ in 0..blocks {
+            player.process_block();
+            output.extend_from_slice(player.output(0));
+        }
+        output
+    }
+
+    #[test]
+    fn plays_regions_back_to_back() {
+        let mut resources = Resources::new(Default::default());
+        let a = insert(&mut resources, 1.0, 20);
+        let b = insert(&mut resources, 2.0, 100);
+        let (player, mut editor) = PlaylistPlayer::new(1, 8);
+        let player = player.items([
+            PlaylistItem::new(a),
+            PlaylistItem::new(b)
+                .start(Seconds::from_seconds_f64(0.05))
+                .end(Seconds::from_seconds_f64(0.06))
+                .gain(0.5),
+        ]);
+        let mut player = GenTester::new(player, BLOCK_SIZE, SR);
+        player.resources = resources;
+        let output = render(&mut player, 2);
+        assert_eq!(&output[..20], &[1.0; 20]);
+        assert_eq!(&output[20..30], &[1.0; 10]);
+        assert_eq!(&output[30..], &[0.0; 2]);
+        assert_eq!(editor.items_started(), 2);
+        assert_eq!(editor.queue_len(), 0);
+        // Edits at runtime, crossfading into the second item
+        editor.push(PlaylistItem::new(a)).unwrap();
+        editor
+            .push(PlaylistItem::new(b).crossfade(Seconds::from_seconds_f64(0.01)))
+            .unwrap();
+        editor
+            .play_next(PlaylistItem::new(b).end(Seconds::from_seconds_f64(0.004)))
+            .unwrap();
+        let output = render(&mut player, 3);
+        assert_eq!(&output[..4], &[2.0; 4]);
+        assert_eq!(&output[4..14], &[1.0; 10]);
+        // Crossfade from 1.0 to 2.0 over the last 10 samples of the first item
+        assert!(output[14..24].iter().all(|&v| (1.0..2.3).contains(&v)));
+        assert!(output[23] > 1.9);
+        assert_eq!(&output[24..48], &[2.0; 24]);
+        assert_eq!(editor.items_started(), 5);
+        editor.skip().unwrap();
+        let output = render(&mut player, 1);
+        assert!(output[..5].iter().all(|&v| v > 0.0 && v <= 2.0));
+        assert!(output[4] < output[0] * 0.5);
+        assert_eq!(&output[5..], &[0.0; 11]);
+    }
+
+    #[test]
+    fn capacity_counts_items_not_yet_added() {
+        let (player, mut editor) = PlaylistPlayer::new(1, 2);
+        let mut player = GenTester::new(player, BLOCK_SIZE, SR);
+        let a = insert(&mut player.resources, 1.0, 1000);
+        editor.push(PlaylistItem::new(a)).unwrap();
+        editor.play_next(PlaylistItem::new(a)).unwrap();
+        assert_eq!(editor.queue_len(), 2);
+        assert!(editor.push(PlaylistItem::new(a)).is_err());
+        // One item starts playing, making room for one more
+        player.process_block();
+        assert_eq!(editor.queue_len(), 1);
+        editor.push(PlaylistItem::new(a)).unwrap();
+        assert!(editor.push(PlaylistItem::new(a)).is_err());
+        player.process_block();
+        assert_eq!(editor.queue_len(), 2);
+        assert!(editor.push(PlaylistItem::new(a)).is_err());
+    }
+
+    #[test]
+    fn looping_drops_unplayable_items() {
+        let mut resources = Resources::new(Default::default());
+        let a = insert(&mut resources, 1.0, 8);
+        let missing = BufferId::new(&Buffer::from_vec(vec![0.0], SR as f64));
+        let (player, editor) = PlaylistPlayer::new(1, 4);
+        let player = player
+            .items([PlaylistItem::new(missing), PlaylistItem::new(a)])
+            .looping(true);
+        let mut player = GenTester::new(player, BLOCK_SIZE, SR);
+        player.resources = resources;
+        assert_eq!(render(&mut player, 2), vec![1.0; 32]);
+        assert_eq!(editor.queue_len(), 1);
+        assert_eq!(editor.items_started(), 4);
+    }
+}
@@ -75,3 +75,119 @@ impl RandomLin {
         GenState::Continue
     }
 }
+
+/// Slow random drift added to a signal, e.g. to make constant control values
+/// such as the frequency of an oscillator feel less static. The drift is a
+/// smooth curve through random values in the range -1 to 1 chosen `rate`
+/// times per second, scaled by `amount`. Rate is sampled at control rate only.
+///
+/// To add drift to an input of an existing node, use
+/// [`KnystCommands::wobble_input`](crate::controller::KnystCommands::wobble_input).
+///
+/// *inputs*
+/// 0. "input": The signal to add the drift to
+/// 1. "amount": The maximum deviation from the input
+/// 2. "rate": The number of new random values per second
+///
+/// *outputs*
+/// 0. "output": The input with the drift added
+pub struct Wobble {
+    rng: fastrand::Rng,
+    /// The last four random values, the curve is between the middle two
+    points: [Sample; 4],
+    phase: Sample,
+    freq_to_phase_inc: Sample,
+}
+
+#[impl_gen]
+impl Wobble {
+    /// Create a new Wobble, seeding it from the global atomic seed.
+    pub fn new() -> Self {
+        let mut rng = fastrand::Rng::with_seed(next_randomness_seed() * 94 + 53);
+        let points = [0.0; 4].map(|_: Sample| rng.f32() as Sample * 2.0 - 1.0);
+        Self {
+            rng,
+            points,
+            phase: 0.0,
+            freq_to_phase_inc: 0.0,
+        }
+    }
+
+    /// Init internal state
+    pub fn init(&mut self, sample_rate: SampleRate) {
+        self.freq_to_phase_inc = 1.0 / *sample_rate;
+    }
+
+    /// Catmull-Rom interpolation between the middle two points
+    #[inline]
+    fn drift(&self) -> Sample {
+        let [p0, p1, p2, p3] = self.points;
+        let t = self.phase;
+        0.5 * (2.0 * p1
+            + (p2 - p0) * t
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t
+            + (3.0 * (p1 - p2) + p3 - p0) * t * t * t)
+    }
+
+    /// Process block
+    pub fn process(
+        &mut self,
+        input: &[Sample],
+        amount: &[Sample],
+        rate: &[Sample],
+        output: &mut [Sample],
+    ) -> GenState {
+        let phase_step = rate[0].max(0.0) * self.freq_to_phase_inc;
+        for ((out, &input), &amount) in output.iter_mut().zip(input).zip(amount) {
+            *out = input + self.drift() * amount;
+            self.phase += phase_step;
+            while self.phase >= 1.0 {
+                self.phase -= 1.0;
+                self.points.rotate_left(1);
+                self.points[3] = self.rng.f32() as Sample * 2.0 - 1.0;
+            }
+        }
+        GenState::Continue
+    }
+}
+
+impl Default for Wobble {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Wobble;
+    use crate::{gen::testing::GenTester, Sample};
+
+    #[test]
+    fn wobble_drifts_slowly_around_the_input() {
+        let block_size = 64;
+        let sample_rate = 44100.;
+        let mut tester = GenTester::new(Wobble::new(), block_size, sample_rate);
+        tester.set_input(0, 440.);
+        tester.set_input(1, 5.);
+        tester.set_input(2, 2.);
+        let mut previous = None;
+        let (mut min, mut max) = (Sample::MAX, Sample::MIN);
+        for _ in 0..(sample_rate as usize * 4 / block_size) {
+            tester.process_block();
+            for &out in tester.output(0) {
+                if let Some(previous) = previous {
+                    // A drift of 5 at 2 Hz never moves this fast
+                    assert!((out - previous as Sample).abs() < 0.01, "{previous} {out}");
+                }
+                previous = Some(out);
+                min = min.min(out);
+                max = max.max(out);
+            }
+        }
+        assert!(
+            min > 440. - 5. * 1.25 && max < 440. + 5. * 1.25,
+            "{min} {max}"
+        );
+        assert!(max - min > 1.0, "{min} {max}");
+    }
+}
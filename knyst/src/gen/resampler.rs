@@ -0,0 +1,392 @@
+//! Streaming sample rate conversion
+//!
+//! [`PolyphaseResampler`] converts a stream of samples from one rate to
+//! another using a windowed sinc filter bank, taking input and producing
+//! output in chunks of any size. It doesn't allocate after it has been
+//! created and can be used on its own, e.g. to bring audio received over the
+//! network or read from a file to the sample rate of the graph.
+//!
+//! [`Resampler`] wraps it in a [`Gen`] for bridging clock domains inside a
+//! graph: the input is treated as a stream whose clock may run slightly faster
+//! or slower than that of the graph, e.g. because it is recorded by a second
+//! sound card, and the "ratio" input sets how many input samples are consumed
+//! per output sample. The "fill" output reports how many input samples are
+//! waiting to be consumed so that the ratio can be adjusted to keep it stable.
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+/// The number of filter phases between two input samples. Coefficients for
+/// positions between two phases are interpolated linearly.
+const NUM_PHASES: usize = 128;
+
+/// The trade-off between quality and CPU use of a [`PolyphaseResampler`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResamplerQuality {
+    /// 8 filter taps. Audible aliasing for signals with a lot of high
+    /// frequency content, but cheap.
+    Low,
+    /// 16 filter taps
+    #[default]
+    Medium,
+    /// 32 filter taps
+    High,
+}
+
+impl ResamplerQuality {
+    fn num_taps(self) -> usize {
+        match self {
+            ResamplerQuality::Low => 8,
+            ResamplerQuality::Medium => 16,
+            ResamplerQuality::High => 32,
+        }
+    }
+}
+
+/// Converts a stream of samples from one sample rate to another, see the
+/// [module documentation](self).
+///
+/// The anti-aliasing filter is designed for the ratio the resampler is created
+/// with. The ratio can be changed afterwards using
+/// [`PolyphaseResampler::set_ratio`], which is meant for small adjustments,
+/// e.g. to compensate for clock drift.
+#[derive(Clone, Debug)]
+pub struct PolyphaseResampler {
+    num_taps: usize,
+    /// `NUM_PHASES + 1` phases of `num_taps` coefficients each
+    coefficients: Vec<Sample>,
+    /// The last `num_taps` input samples, stored twice so that they can always
+    /// be read as one contiguous slice
+    history: Vec<Sample>,
+    write_pos: usize,
+    /// The position of the next output sample after the centre of the
+    /// history, in input samples
+    position: f64,
+    /// Input samples per output sample
+    step: f64,
+}
+
+impl PolyphaseResampler {
+    /// Create a resampler converting from `input_rate` to `output_rate`
+    pub fn new(input_rate: f64, output_rate: f64, quality: ResamplerQuality) -> Self {
+        Self::with_ratio(output_rate / input_rate, quality)
+    }
+    /// Create a resampler producing `ratio` output samples per input sample,
+    /// i.e. `output_rate / input_rate`
+    pub fn with_ratio(ratio: f64, quality: ResamplerQuality) -> Self {
+        assert!(ratio > 0.0, "The resampling ratio must be positive");
+        // When downsampling, the filter has to be wider to cut off at the
+        // lower output Nyquist frequency
+        let scale = ratio.min(1.0);
+        let num_taps = ((quality.num_taps() as f64 / scale).ceil() as usize).next_multiple_of(2);
+        let cutoff = 0.46 * scale;
+        let half = (num_taps / 2) as f64;
+        let mut coefficients = Vec::with_capacity((NUM_PHASES + 1) * num_taps);
+        for phase in 0..=NUM_PHASES {
+            let frac = phase as f64 / NUM_PHASES as f64;
+            let start = coefficients.len();
+            for tap in 0..num_taps {
+                // Distance from the output position to this tap in input samples
+                let d = tap as f64 - (half - 1.0) - frac;
+                let x = 2.0 * cutoff * d;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+                };
+                // Blackman window over the width of the filter
+                let w = (d + half) / (2.0 * half);
+                let window = if (0.0..=1.0).contains(&w) {
+                    0.42 - 0.5 * (std::f64::consts::TAU * w).cos()
+                        + 0.08 * (2.0 * std::f64::consts::TAU * w).cos()
+                } else {
+                    0.0
+                };
+                coefficients.push((sinc * window) as Sample);
+            }
+            // Normalise every phase to unity gain at DC
+            let sum: Sample = coefficients[start..].iter().sum();
+            for c in &mut coefficients[start..] {
+                *c /= sum;
+            }
+        }
+        Self {
+            num_taps,
+            coefficients,
+            history: vec![0.0; num_taps * 2],
+            write_pos: 0,
+            position: 0.0,
+            step: 1.0 / ratio,
+        }
+    }
+    /// Change the number of output samples per input sample
+    pub fn set_ratio(&mut self, ratio: f64) {
+        if ratio > 0.0 {
+            self.step = 1.0 / ratio;
+        }
+    }
+    /// The number of output samples per input sample
+    pub fn ratio(&self) -> f64 {
+        1.0 / self.step
+    }
+    /// The delay through the resampler in input samples
+    pub fn latency(&self) -> usize {
+        self.num_taps / 2
+    }
+    /// Clear the history, as if the resampler had just been created
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.write_pos = 0;
+        self.position = 0.0;
+    }
+    /// Resample as much of `input` as needed to fill `output`, or until the
+    /// input runs out. Returns the number of input samples consumed and the
+    /// number of output samples produced. Input that wasn't consumed has to be
+    /// passed in again at the start of the next call.
+    pub fn process(&mut self, input: &[Sample], output: &mut [Sample]) -> (usize, usize) {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            if produced == output.len() {
+                return (consumed, produced);
+            }
+            while self.position >= 1.0 {
+                let Some(&sample) = input.get(consumed) else {
+                    return (consumed, produced);
+                };
+                self.push(sample);
+                consumed += 1;
+                self.position -= 1.0;
+            }
+            output[produced] = self.interpolate();
+            produced += 1;
+            self.position += self.step;
+        }
+    }
+    fn push(&mut self, sample: Sample) {
+        self.history[self.write_pos] = sample;
+        self.history[self.write_pos + self.num_taps] = sample;
+        self.write_pos = (self.write_pos + 1) % self.num_taps;
+    }
+    fn interpolate(&self) -> Sample {
+        let history = &self.history[self.write_pos..self.write_pos + self.num_taps];
+        let phase = self.position * NUM_PHASES as f64;
+        let index = (phase as usize).min(NUM_PHASES - 1);
+        let t = (phase - index as f64) as Sample;
+        let a = &self.coefficients[index * self.num_taps..(index + 1) * self.num_taps];
+        let b = &self.coefficients[(index + 1) * self.num_taps..(index + 2) * self.num_taps];
+        let mut out_a = 0.0;
+        let mut out_b = 0.0;
+        for ((&x, &a), &b) in history.iter().zip(a).zip(b) {
+            out_a += x * a;
+            out_b += x * b;
+        }
+        out_a + (out_b - out_a) * t
+    }
+}
+
+/// Resamples its input by a variable ratio for bridging clock domains, see
+/// the [module documentation](self). The input is collected in a queue which
+/// the resampler reads from. If the queue runs out the output is silent until
+/// enough input has arrived, and if it overflows the oldest input is dropped.
+///
+/// *inputs*
+/// 0. "input": The signal to resample
+/// 1. "ratio": The number of input samples consumed per output sample, usually close to 1.0
+///
+/// *outputs*
+/// 0. "output": The resampled signal
+/// 1. "fill": The number of input samples in the queue
+pub struct Resampler {
+    resampler: PolyphaseResampler,
+    queue: Vec<Sample>,
+    queue_len: usize,
+    target_fill: usize,
+}
+
+impl Resampler {
+    /// Create a new [`Resampler`] with the given quality
+    pub fn new(quality: ResamplerQuality) -> Self {
+        Self {
+            resampler: PolyphaseResampler::with_ratio(1.0, quality),
+            queue: vec![],
+            queue_len: 0,
+            target_fill: 0,
+        }
+    }
+    /// Set how many input samples are queued before the output starts, and
+    /// after the queue has run out. Higher values add latency, but leave more
+    /// room for the ratio to deviate from the actual clock ratio. The default
+    /// is one block.
+    pub fn target_fill(mut self, samples: usize) -> Self {
+        self.target_fill = samples;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 2, 2))
+    }
+}
+
+impl Default for Resampler {
+    fn default() -> Self {
+        Self::new(ResamplerQuality::default())
+    }
+}
+
+impl Gen for Resampler {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let input = ctx.inputs.get_channel(0);
+        let ratio = ctx.inputs.get_channel(1);
+        // Make room for the new input by dropping the oldest
+        let overflow = (self.queue_len + input.len()).saturating_sub(self.queue.len());
+        if overflow > 0 {
+            self.queue.copy_within(overflow..self.queue_len, 0);
+            self.queue_len -= overflow;
+        }
+        self.queue[self.queue_len..self.queue_len + input.len()].copy_from_slice(input);
+        self.queue_len += input.len();
+
+        let mut outputs = ctx.outputs.iter_mut();
+        let output = outputs.next().unwrap();
+        let fill = outputs.next().unwrap();
+        let ratio = ratio[0];
+        if ratio > 0.0 {
+            self.resampler.set_ratio(1.0 / ratio as f64);
+        }
+        let (consumed, produced) = if self.queue_len >= self.target_fill {
+            self.resampler
+                .process(&self.queue[..self.queue_len], output)
+        } else {
+            (0, 0)
+        };
+        // The queue ran out, wait until it is filled up again
+        output[produced..].fill(0.0);
+        if produced < output.len() {
+            self.resampler.reset();
+        }
+        self.queue.copy_within(consumed..self.queue_len, 0);
+        self.queue_len -= consumed;
+        fill.fill(self.queue_len as Sample);
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        2
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+
+    fn init(&mut self, block_size: usize, _sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        if self.target_fill == 0 {
+            self.target_fill = block_size;
+        }
+        // Enough room for the target fill plus a few blocks of drift
+        self.queue = vec![0.0; self.target_fill + block_size * 4];
+        self.queue_len = 0;
+        self.resampler.reset();
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input",
+            1 => "ratio",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "output",
+            1 => "fill",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Resampler"
+    }
+}
+
+/// Upload a [`Resampler`] with the default quality to the current graph and
+/// return a handle to it. Remember to set the "ratio" input, e.g. to 1.0.
+pub fn resampler() -> Handle<GenericHandle> {
+    Resampler::default().upload()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PolyphaseResampler, Resampler, ResamplerQuality};
+    use crate::{gen::testing::GenTester, Sample};
+
+    fn sine(freq: f64, sample_rate: f64, len: usize) -> Vec<Sample> {
+        (0..len)
+            .map(|i| (std::f64::consts::TAU * freq * i as f64 / sample_rate).sin() as Sample)
+            .collect()
+    }
+
+    #[test]
+    fn converts_between_rates_in_chunks() {
+        for (from, to) in [(44100., 48000.), (48000., 44100.), (48000., 16000.)] {
+            let mut resampler = PolyphaseResampler::new(from, to, ResamplerQuality::High);
+            let input = sine(1000., from, 4800);
+            let mut output = vec![0.0; 6000];
+            let (mut consumed, mut produced) = (0, 0);
+            // Odd chunk sizes to exercise the streaming
+            while consumed < input.len() {
+                let (c, p) = resampler.process(
+                    &input[consumed..(consumed + 37).min(input.len())],
+                    &mut output[produced..(produced + 23).min(6000)],
+                );
+                consumed += c;
+                produced += p;
+            }
+            let expected_len = (input.len() as f64 * to / from) as usize;
+            assert!(
+                produced.abs_diff(expected_len) <= 2,
+                "{produced} {expected_len}"
+            );
+            // Compare to the ideal sine, delayed by the latency. Output sample
+            // n is at input position n * from / to - 1 - latency.
+            let latency = resampler.latency() as f64;
+            for (n, &s) in output.iter().enumerate().take(produced).skip(produced / 4) {
+                let t = n as f64 * from / to - 1.0 - latency;
+                let expected = (std::f64::consts::TAU * 1000. * t / from).sin() as Sample;
+                assert!(
+                    (s - expected).abs() < 0.005,
+                    "{from} {to} {n}: {s} {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gen_passes_signal_at_ratio_one() {
+        const BLOCK_SIZE: usize = 32;
+        let mut resampler =
+            GenTester::new(Resampler::new(ResamplerQuality::Low), BLOCK_SIZE, 48000.);
+        let latency = resampler.gen.resampler.latency();
+        resampler.set_input(1, 1.0);
+        let mut signal = vec![];
+        for block in 0..4 {
+            for (i, s) in resampler.input_mut(0).iter_mut().enumerate() {
+                *s = (block * BLOCK_SIZE + i) as Sample;
+            }
+            resampler.process_block();
+            signal.extend_from_slice(resampler.output(0));
+            // The first output sample doesn't consume any input
+            assert_eq!(resampler.output(1)[BLOCK_SIZE - 1], 1.0);
+        }
+        // A ramp delayed by the latency of the filter
+        for (i, &s) in signal.iter().enumerate().skip(latency * 2) {
+            assert!((s - (i - latency - 1) as Sample).abs() < 1e-2, "{i}: {s}");
+        }
+    }
+}
@@ -0,0 +1,7 @@
+//! Frequency domain [`Gen`](super::Gen)s built on a short-time Fourier transform
+//!
+//! [`Stft`](stft::Stft) takes care of windowing, overlap-add and the FFTs so
+//! that a spectral Gen only has to process one spectrum at a time.
+pub mod blur;
+pub mod freeze;
+pub mod stft;
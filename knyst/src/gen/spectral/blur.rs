@@ -0,0 +1,221 @@
+//! Spectral blur
+//!
+//! Smears the magnitude spectrum of a signal over time and across
+//! neighbouring frequencies, turning it into a washed out, ambient version of
+//! itself.
+use realfft::num_complex::Complex;
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+use super::stft::Stft;
+
+const TAU: Sample = std::f64::consts::TAU as Sample;
+/// Bins quieter than this are considered silent and keep advancing their
+/// phase at the centre frequency of the bin.
+const SILENT_BIN: Sample = 1e-9;
+
+/// The state of a [`SpectralBlur`] which is updated once per spectrum
+struct BlurState {
+    /// 0.0 to 1.0, the amount of the previous magnitude kept per spectrum
+    smear: Sample,
+    /// The number of bins on each side of a bin to average over
+    width: usize,
+    magnitudes: Vec<Sample>,
+    /// Cumulative sum of `magnitudes` for averaging over any width in one pass
+    magnitude_sums: Vec<Sample>,
+    phases: Vec<Sample>,
+    /// The phase advance per spectrum at the centre frequency of every bin
+    bin_phase_advances: Vec<Sample>,
+}
+
+impl BlurState {
+    fn new(stft: &Stft) -> Self {
+        let num_bins = stft.num_bins();
+        let advance = TAU * stft.hop_size() as Sample / stft.fft_size() as Sample;
+        Self {
+            smear: 0.0,
+            width: 0,
+            magnitudes: vec![0.0; num_bins],
+            magnitude_sums: vec![0.0; num_bins + 1],
+            phases: vec![0.0; num_bins],
+            bin_phase_advances: (0..num_bins).map(|k| k as Sample * advance).collect(),
+        }
+    }
+    fn process_spectrum(&mut self, spectrum: &mut [Complex<Sample>]) {
+        let smear = self.smear;
+        let mut sum = 0.0;
+        for (k, bin) in spectrum.iter().enumerate() {
+            let magnitude = bin.norm();
+            self.magnitudes[k] = magnitude + smear * (self.magnitudes[k] - magnitude);
+            if magnitude > SILENT_BIN {
+                self.phases[k] = bin.arg();
+            } else {
+                self.phases[k] = (self.phases[k] + self.bin_phase_advances[k]).rem_euclid(TAU);
+            }
+            sum += self.magnitudes[k];
+            self.magnitude_sums[k + 1] = sum;
+        }
+        let num_bins = spectrum.len();
+        for (k, bin) in spectrum.iter_mut().enumerate() {
+            let low = k.saturating_sub(self.width);
+            let high = (k + self.width + 1).min(num_bins);
+            let magnitude =
+                (self.magnitude_sums[high] - self.magnitude_sums[low]) / (high - low) as Sample;
+            *bin = Complex::from_polar(magnitude, self.phases[k]);
+        }
+    }
+}
+
+/// Blurs the magnitude spectrum over time and frequency. The output is
+/// delayed by the FFT size.
+///
+/// *inputs*
+/// 0. "input": The signal to blur
+/// 1. "smear": 0.0 to 1.0, how much of the previous magnitude spectrum is
+///    kept for every new spectrum. 0.0 is no smearing over time and 1.0 holds
+///    the spectrum forever.
+/// 2. "width": The number of neighbouring bins on each side of every bin to
+///    blur the spectrum over
+///
+/// *outputs*
+/// 0. "output": The blurred signal
+pub struct SpectralBlur {
+    stft: Stft,
+    state: BlurState,
+}
+
+impl SpectralBlur {
+    /// Create a new spectral blur analysing windows of `fft_size` samples
+    /// with an overlap of 4.
+    ///
+    /// # Panics
+    /// If `fft_size` is not divisible by 4
+    pub fn new(fft_size: usize) -> Self {
+        let stft = Stft::new(fft_size, 4);
+        let state = BlurState::new(&stft);
+        Self { stft, state }
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 3, 1))
+    }
+}
+
+impl Gen for SpectralBlur {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let input = ctx.inputs.get_channel(0);
+        let smear = ctx.inputs.get_channel(1);
+        let width = ctx.inputs.get_channel(2);
+        for i in 0..block_size {
+            let state = &mut self.state;
+            state.smear = smear[i].clamp(0.0, 1.0);
+            // Wider than the spectrum is the same as the whole spectrum
+            state.width = (width[i].max(0.0) as usize).min(state.magnitudes.len());
+            let out = self
+                .stft
+                .process_sample(input[i], |spectrum| state.process_spectrum(spectrum));
+            ctx.outputs.write(out, 0, i);
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        3
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, _sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.stft.reset();
+        self.state = BlurState::new(&self.stft);
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input",
+            1 => "smear",
+            2 => "width",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "output",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "SpectralBlur"
+    }
+}
+
+/// Upload a [`SpectralBlur`] to the current graph and return a handle to it.
+pub fn spectral_blur(fft_size: usize) -> Handle<GenericHandle> {
+    SpectralBlur::new(fft_size).upload()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpectralBlur;
+    use crate::{gen::testing::GenTester, Sample};
+
+    /// Plays a sine tone followed by silence, returning the peak of the
+    /// output once the tone has had time to pass through the blur.
+    fn tail_peak(smear: Sample) -> Sample {
+        let block_size = 64;
+        let mut tester = GenTester::new(SpectralBlur::new(1024), block_size, 44100.);
+        tester.set_input(1, smear);
+        tester.set_input(2, 2.0);
+        let mut peak: Sample = 0.0;
+        for block in 0..128 {
+            for (i, input) in tester.input_mut(0).iter_mut().enumerate() {
+                *input = if block < 64 {
+                    ((block * block_size + i) as Sample * 0.1).sin()
+                } else {
+                    0.0
+                };
+            }
+            tester.process_block();
+            // Skip the latency and the windows overlapping the end of the tone
+            if block >= 64 + 2 * 1024 / block_size {
+                peak = peak.max(tester.output_peak(0));
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn smear_sustains_the_spectrum() {
+        let dry_tail = tail_peak(0.0);
+        let smeared_tail = tail_peak(0.9);
+        assert!(dry_tail < 1e-4, "dry tail: {dry_tail}");
+        assert!(smeared_tail > 0.01, "smeared tail: {smeared_tail}");
+    }
+
+    #[test]
+    fn infinite_width_averages_the_whole_spectrum() {
+        let block_size = 64;
+        let mut tester = GenTester::new(SpectralBlur::new(256), block_size, 44100.);
+        tester.set_input(2, Sample::INFINITY);
+        for block in 0..16 {
+            for (i, input) in tester.input_mut(0).iter_mut().enumerate() {
+                *input = ((block * block_size + i) as Sample * 0.1).sin();
+            }
+            tester.process_block();
+            assert!(tester.output(0).iter().all(|s| s.is_finite()));
+        }
+        assert!(tester.output_peak(0) > 0.0);
+    }
+}
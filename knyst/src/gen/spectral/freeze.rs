@@ -0,0 +1,222 @@
+//! Spectral freeze
+//!
+//! Holds the magnitude spectrum of the signal at the time of a trigger while
+//! the phase of every bin keeps advancing at the rate it had when it was
+//! frozen, resulting in an endlessly sustained version of the sound.
+use realfft::num_complex::Complex;
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+use super::stft::Stft;
+
+const TAU: Sample = std::f64::consts::TAU as Sample;
+
+/// The state of a [`SpectralFreeze`] which is updated once per spectrum
+struct FreezeState {
+    frozen: bool,
+    capture_pending: bool,
+    release_pending: bool,
+    /// The phase of every bin in the previous spectrum
+    prev_phases: Vec<Sample>,
+    magnitudes: Vec<Sample>,
+    phases: Vec<Sample>,
+    /// The phase advance per spectrum of every bin at the time it was frozen
+    phase_advances: Vec<Sample>,
+}
+
+impl FreezeState {
+    fn new(num_bins: usize) -> Self {
+        Self {
+            frozen: false,
+            capture_pending: false,
+            release_pending: false,
+            prev_phases: vec![0.0; num_bins],
+            magnitudes: vec![0.0; num_bins],
+            phases: vec![0.0; num_bins],
+            phase_advances: vec![0.0; num_bins],
+        }
+    }
+    fn process_spectrum(&mut self, spectrum: &mut [Complex<Sample>]) {
+        if self.release_pending {
+            self.frozen = false;
+        }
+        let capture = self.capture_pending;
+        self.capture_pending = false;
+        self.release_pending = false;
+        for (k, bin) in spectrum.iter_mut().enumerate() {
+            let phase = bin.arg();
+            let advance = phase - self.prev_phases[k];
+            self.prev_phases[k] = phase;
+            if capture {
+                self.magnitudes[k] = bin.norm();
+                self.phases[k] = phase;
+                self.phase_advances[k] = advance;
+            } else if self.frozen {
+                self.phases[k] = (self.phases[k] + self.phase_advances[k]).rem_euclid(TAU);
+                *bin = Complex::from_polar(self.magnitudes[k], self.phases[k]);
+            }
+        }
+        if capture {
+            self.frozen = true;
+        }
+    }
+}
+
+/// Holds the current magnitude spectrum when triggered while the phases keep
+/// evolving. The output is delayed by the FFT size.
+///
+/// *inputs*
+/// 0. "input": The signal to freeze
+/// 1. "freeze": Trigger to capture the spectrum of the input and hold it,
+///    replacing any spectrum held before
+/// 2. "release": Trigger to return to the live input
+///
+/// *outputs*
+/// 0. "output": The frozen or live signal
+pub struct SpectralFreeze {
+    stft: Stft,
+    state: FreezeState,
+}
+
+impl SpectralFreeze {
+    /// Create a new spectral freeze analysing windows of `fft_size` samples
+    /// with an overlap of 4. Larger sizes give a smoother freeze at the cost
+    /// of latency.
+    ///
+    /// # Panics
+    /// If `fft_size` is not divisible by 4
+    pub fn new(fft_size: usize) -> Self {
+        let stft = Stft::new(fft_size, 4);
+        let state = FreezeState::new(stft.num_bins());
+        Self { stft, state }
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 3, 1))
+    }
+}
+
+impl Gen for SpectralFreeze {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let input = ctx.inputs.get_channel(0);
+        let freeze = ctx.inputs.get_channel(1);
+        let release = ctx.inputs.get_channel(2);
+        for i in 0..block_size {
+            if freeze[i] > 0.0 {
+                self.state.capture_pending = true;
+            }
+            if release[i] > 0.0 {
+                self.state.release_pending = true;
+                self.state.capture_pending = false;
+            }
+            let state = &mut self.state;
+            let out = self
+                .stft
+                .process_sample(input[i], |spectrum| state.process_spectrum(spectrum));
+            ctx.outputs.write(out, 0, i);
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        3
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    fn init(&mut self, _block_size: usize, _sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.stft.reset();
+        self.state = FreezeState::new(self.stft.num_bins());
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input",
+            1 => "freeze",
+            2 => "release",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "output",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "SpectralFreeze"
+    }
+}
+
+/// Upload a [`SpectralFreeze`] to the current graph and return a handle to it.
+pub fn spectral_freeze(fft_size: usize) -> Handle<GenericHandle> {
+    SpectralFreeze::new(fft_size).upload()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpectralFreeze;
+    use crate::{gen::testing::GenTester, Sample};
+
+    const BLOCK_SIZE: usize = 64;
+
+    /// Processes `num_blocks` blocks, returning the peak of the output. The
+    /// input is a sine tone if `tone` is true and silence otherwise.
+    fn run(
+        tester: &mut GenTester<SpectralFreeze>,
+        num_blocks: usize,
+        tone: bool,
+        freeze_trig: bool,
+        release_trig: bool,
+    ) -> Sample {
+        let mut peak: Sample = 0.0;
+        for block in 0..num_blocks {
+            for (i, input) in tester.input_mut(0).iter_mut().enumerate() {
+                *input = if tone {
+                    ((block * BLOCK_SIZE + i) as Sample * 0.1).sin()
+                } else {
+                    0.0
+                };
+            }
+            tester.set_input(1, 0.0);
+            tester.set_input(2, 0.0);
+            if block == 0 && freeze_trig {
+                tester.input_mut(1)[0] = 1.0;
+            }
+            if block == 0 && release_trig {
+                tester.input_mut(2)[0] = 1.0;
+            }
+            tester.process_block();
+            peak = peak.max(tester.output_peak(0));
+        }
+        peak
+    }
+
+    #[test]
+    fn holds_the_spectrum_until_released() {
+        let mut tester = GenTester::new(SpectralFreeze::new(1024), BLOCK_SIZE, 44100.);
+        run(&mut tester, 64, true, false, false);
+        // Freeze the tone and then let the input and the latency run out
+        run(&mut tester, 64, false, true, false);
+        let frozen_peak = run(&mut tester, 64, false, false, false);
+        assert!(
+            frozen_peak > 0.5 && frozen_peak < 1.5,
+            "frozen peak: {frozen_peak}"
+        );
+        run(&mut tester, 64, false, false, true);
+        let released_peak = run(&mut tester, 64, false, false, false);
+        assert!(released_peak < 1e-4, "released peak: {released_peak}");
+    }
+}
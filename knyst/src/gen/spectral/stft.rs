@@ -0,0 +1,186 @@
+//! Short-time Fourier transform with overlap-add resynthesis
+//!
+//! The signal is analysed using a Hann window every `fft_size / overlap`
+//! samples. Each spectrum is passed to a closure which can change it in place
+//! before it is transformed back, windowed again and added to the output. If
+//! the spectrum is left untouched the output is the input delayed by
+//! [`Stft::latency`] samples.
+use std::sync::Arc;
+
+use realfft::{num_complex::Complex, ComplexToReal, RealFftPlanner, RealToComplex};
+
+use crate::{gen::fft::inverse_real_fft, Sample};
+
+/// Sample by sample short-time Fourier transform and overlap-add
+/// resynthesis. Nothing is allocated after creation, so it can be used from
+/// within [`Gen::process`](crate::gen::Gen::process).
+pub struct Stft {
+    fft_size: usize,
+    hop_size: usize,
+    window: Vec<Sample>,
+    /// Scales the output to compensate for the windowing and the
+    /// unnormalised inverse FFT.
+    norm: Sample,
+    /// Ring buffer of the last `fft_size` input samples
+    input: Vec<Sample>,
+    /// Ring buffer of overlap-added output, sharing the position of `input`
+    output: Vec<Sample>,
+    position: usize,
+    hop_counter: usize,
+    fft: Arc<dyn RealToComplex<Sample>>,
+    ifft: Arc<dyn ComplexToReal<Sample>>,
+    frame: Vec<Sample>,
+    spectrum: Vec<Complex<Sample>>,
+    fft_scratch: Vec<Complex<Sample>>,
+    ifft_scratch: Vec<Complex<Sample>>,
+}
+
+impl Stft {
+    /// Create a new STFT analysing a window of `fft_size` samples `overlap`
+    /// times per window.
+    ///
+    /// # Panics
+    /// If `fft_size` is not divisible by `overlap` or if `overlap` is smaller
+    /// than 4, the lowest overlap at which a squared Hann window sums to a
+    /// constant.
+    // `usize::is_multiple_of` needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn new(fft_size: usize, overlap: usize) -> Self {
+        assert!(overlap >= 4, "the overlap has to be at least 4");
+        assert!(
+            fft_size % overlap == 0,
+            "the fft size has to be divisible by the overlap"
+        );
+        let hop_size = fft_size / overlap;
+        let window: Vec<Sample> = (0..fft_size)
+            .map(|i| {
+                0.5 - 0.5
+                    * (std::f64::consts::TAU as Sample * i as Sample / fft_size as Sample).cos()
+            })
+            .collect();
+        // The window is applied both before and after the transform
+        let window_sum = window.iter().map(|w| w * w).sum::<Sample>() / hop_size as Sample;
+        let norm = 1.0 / (window_sum * fft_size as Sample);
+        let mut planner = RealFftPlanner::<Sample>::new();
+        let fft = planner.plan_fft_forward(fft_size);
+        let ifft = planner.plan_fft_inverse(fft_size);
+        Self {
+            fft_size,
+            hop_size,
+            window,
+            norm,
+            input: vec![0.0; fft_size],
+            output: vec![0.0; fft_size],
+            position: 0,
+            hop_counter: 0,
+            frame: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            fft_scratch: fft.make_scratch_vec(),
+            ifft_scratch: ifft.make_scratch_vec(),
+            fft,
+            ifft,
+        }
+    }
+    /// The size of the analysis window in samples
+    pub fn fft_size(&self) -> usize {
+        self.fft_size
+    }
+    /// The number of samples between two analysed spectra
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+    /// The number of bins in a spectrum, from DC up to and including the Nyquist frequency
+    pub fn num_bins(&self) -> usize {
+        self.fft_size / 2 + 1
+    }
+    /// The delay in samples between the input and the output
+    pub fn latency(&self) -> usize {
+        self.fft_size
+    }
+    /// Clear the input and output buffers
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.position = 0;
+        self.hop_counter = 0;
+    }
+    /// Process one sample, returning one output sample. `process_spectrum`
+    /// is called with the spectrum of the latest window every
+    /// [`Stft::hop_size`] samples.
+    #[inline]
+    pub fn process_sample(
+        &mut self,
+        input: Sample,
+        process_spectrum: impl FnOnce(&mut [Complex<Sample>]),
+    ) -> Sample {
+        self.input[self.position] = input;
+        let output = self.output[self.position];
+        self.output[self.position] = 0.0;
+        self.position = (self.position + 1) % self.fft_size;
+        self.hop_counter += 1;
+        if self.hop_counter == self.hop_size {
+            self.hop_counter = 0;
+            self.process_frame(process_spectrum);
+        }
+        output
+    }
+    fn process_frame(&mut self, process_spectrum: impl FnOnce(&mut [Complex<Sample>])) {
+        let (newest, oldest) = self.input.split_at(self.position);
+        for ((frame, input), window) in self
+            .frame
+            .iter_mut()
+            .zip(oldest.iter().chain(newest.iter()))
+            .zip(self.window.iter())
+        {
+            *frame = input * window;
+        }
+        self.fft
+            .process_with_scratch(&mut self.frame, &mut self.spectrum, &mut self.fft_scratch)
+            .expect("buffers are created by the fft");
+        process_spectrum(&mut self.spectrum);
+        inverse_real_fft(
+            self.ifft.as_ref(),
+            &mut self.spectrum,
+            &mut self.frame,
+            &mut self.ifft_scratch,
+        );
+        let (newest, oldest) = self.output.split_at_mut(self.position);
+        for ((output, frame), window) in oldest
+            .iter_mut()
+            .chain(newest.iter_mut())
+            .zip(self.frame.iter())
+            .zip(self.window.iter())
+        {
+            *output += frame * window * self.norm;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stft;
+    use crate::Sample;
+
+    #[test]
+    fn unchanged_spectrum_reconstructs_the_input() {
+        let mut stft = Stft::new(512, 4);
+        let input: Vec<Sample> = (0..4096)
+            .map(|i| (i as Sample * 0.05).sin() * 0.5 + (i as Sample * 0.31).cos() * 0.25)
+            .collect();
+        let output: Vec<Sample> = input
+            .iter()
+            .map(|&x| stft.process_sample(x, |_| {}))
+            .collect();
+        let latency = stft.latency();
+        // The first window only contains part of the signal
+        for i in latency * 2..input.len() {
+            let diff = (output[i] - input[i - latency]).abs();
+            assert!(
+                diff < 1e-4,
+                "sample {i}: {} != {}",
+                output[i],
+                input[i - latency]
+            );
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Shared helper for testing a single [`Gen`] outside of a [`Graph`](crate::graph::Graph).
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    graph::NodeId,
+    node_buffer::NodeBufferRef,
+    Resources, Sample,
+};
+
+/// Owns a [`Gen`] together with its input and output buffers and processes
+/// it one block at a time. Inputs keep their values between blocks.
+pub(crate) struct GenTester<G: Gen> {
+    /// The Gen being tested
+    pub gen: G,
+    /// The resources passed to the Gen
+    pub resources: Resources,
+    inputs: Vec<Sample>,
+    outputs: Vec<Sample>,
+    block_size: usize,
+    sample_rate: Sample,
+}
+
+impl<G: Gen> GenTester<G> {
+    /// Initialises `gen`. All inputs start out at 0.
+    pub fn new(mut gen: G, block_size: usize, sample_rate: Sample) -> Self {
+        gen.init(block_size, sample_rate, NodeId::new(0));
+        Self {
+            inputs: vec![0.0; gen.num_inputs() * block_size],
+            outputs: vec![0.0; gen.num_outputs() * block_size],
+            gen,
+            resources: Resources::new(Default::default()),
+            block_size,
+            sample_rate,
+        }
+    }
+    /// Set every sample of `input` to `value`
+    pub fn set_input(&mut self, input: usize, value: Sample) {
+        self.input_mut(input).fill(value);
+    }
+    /// The samples of `input` for the next block
+    pub fn input_mut(&mut self, input: usize) -> &mut [Sample] {
+        &mut self.inputs[input * self.block_size..(input + 1) * self.block_size]
+    }
+    /// Process one block
+    pub fn process_block(&mut self) -> GenState {
+        let input_buffers = NodeBufferRef::new(
+            self.inputs.as_mut_ptr(),
+            self.gen.num_inputs(),
+            self.block_size,
+        );
+        let mut output_buffers = NodeBufferRef::new(
+            self.outputs.as_mut_ptr(),
+            self.gen.num_outputs(),
+            self.block_size,
+        );
+        self.gen.process(
+            GenContext {
+                inputs: &input_buffers,
+                outputs: &mut output_buffers,
+                sample_rate: self.sample_rate,
+            },
+            &mut self.resources,
+        )
+    }
+    /// The samples of `output` from the last block
+    pub fn output(&self, output: usize) -> &[Sample] {
+        &self.outputs[output * self.block_size..(output + 1) * self.block_size]
+    }
+    /// The peak of `output` in the last block
+    pub fn output_peak(&self, output: usize) -> Sample {
+        self.output(output)
+            .iter()
+            .fold(0.0, |acc: Sample, v| acc.max(v.abs()))
+    }
+}
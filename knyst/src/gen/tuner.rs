@@ -0,0 +1,411 @@
+//! Pitch detection for tuning monophonic instruments and voices
+//!
+//! [`Tuner`] estimates the fundamental frequency of its input using the YIN
+//! algorithm (de Cheveigné and Kawahara, 2002), which is robust against the
+//! octave errors that strong harmonics cause in simpler autocorrelation based
+//! methods. The detected pitch is available both as signals, for use further
+//! down the graph, and as a [`TunerReading`] with the nearest note and the
+//! deviation in cents through a [`TunerMeter`], for displaying in a UI.
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    gen::{Gen, GenContext, GenState},
+    handles::{GenericHandle, Handle},
+    modal_interface::knyst_commands,
+    prelude::KnystCommands,
+    Resources, Sample,
+};
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// A pitch detected by a [`Tuner`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TunerReading {
+    /// The detected fundamental frequency in Hz
+    pub frequency: Sample,
+    /// How periodic the signal is, from 0 to 1. Clean tones from a single
+    /// instrument are usually above 0.9.
+    pub confidence: Sample,
+    /// The MIDI note number of the nearest note
+    pub midi_note: i32,
+    /// The deviation from the nearest note in cents, between -50 and 50
+    pub cents: Sample,
+}
+
+impl TunerReading {
+    fn new(frequency: Sample, confidence: Sample, reference: Sample) -> Self {
+        let (midi_note, cents) = nearest_note(frequency, reference);
+        Self {
+            frequency,
+            confidence,
+            midi_note,
+            cents,
+        }
+    }
+    /// The name of the nearest note without the octave, e.g. "C#"
+    pub fn note_name(&self) -> &'static str {
+        NOTE_NAMES[self.midi_note.rem_euclid(12) as usize]
+    }
+    /// The octave of the nearest note in scientific pitch notation, i.e. A4 is 440 Hz
+    pub fn octave(&self) -> i32 {
+        self.midi_note.div_euclid(12) - 1
+    }
+}
+
+impl Display for TunerReading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{} {:+.1} cents",
+            self.note_name(),
+            self.octave(),
+            self.cents
+        )
+    }
+}
+
+/// Returns the nearest MIDI note and the deviation from it in cents
+fn nearest_note(frequency: Sample, reference: Sample) -> (i32, Sample) {
+    let midi = 69.0 + 12.0 * (frequency / reference).log2();
+    let midi_note = midi.round();
+    (midi_note as i32, (midi - midi_note) * 100.0)
+}
+
+/// Shared state between a [`Tuner`] and its [`TunerMeter`]
+struct TunerShared {
+    /// The bits of the frequency in the high half and of the confidence in
+    /// the low half so that they are always read together. A frequency of 0
+    /// means no pitch was detected.
+    pitch: AtomicU64,
+    /// The frequency of A4
+    reference: AtomicU32,
+}
+
+impl TunerShared {
+    fn store(&self, frequency: Sample, confidence: Sample) {
+        let bits = ((frequency.to_bits() as u64) << 32) | confidence.to_bits() as u64;
+        self.pitch.store(bits, Ordering::Relaxed);
+    }
+    fn reference(&self) -> Sample {
+        Sample::from_bits(self.reference.load(Ordering::Relaxed))
+    }
+}
+
+/// Reads the pitch detected by a running [`Tuner`] from any thread.
+#[derive(Clone)]
+pub struct TunerMeter {
+    shared: Arc<TunerShared>,
+}
+
+impl TunerMeter {
+    /// The latest detected pitch, or None if the input is silent or has no
+    /// clear pitch.
+    pub fn reading(&self) -> Option<TunerReading> {
+        let bits = self.shared.pitch.load(Ordering::Relaxed);
+        let frequency = Sample::from_bits((bits >> 32) as u32);
+        let confidence = Sample::from_bits(bits as u32);
+        if frequency > 0.0 {
+            Some(TunerReading::new(
+                frequency,
+                confidence,
+                self.shared.reference(),
+            ))
+        } else {
+            None
+        }
+    }
+    /// Set the frequency of A4 which notes and cents are relative to. Default: 440
+    pub fn set_reference(&self, frequency: Sample) {
+        self.shared
+            .reference
+            .store(frequency.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Detects the pitch of a monophonic input signal.
+///
+/// The input is analysed in windows of twice the period of the lowest
+/// detectable frequency, so the lower [`Tuner::min_freq`] is set, the slower
+/// the tuner reacts and the more CPU it uses. The default range of 60 to 1500
+/// Hz covers the guitar and most voices, bass instruments need a lower
+/// minimum. The result is held until the next analysis.
+///
+/// *inputs*
+/// 0. "input": The signal to detect the pitch of
+///
+/// *outputs*
+/// 0. "frequency": The detected frequency in Hz, or 0 if no pitch was detected
+/// 1. "cents": The deviation from the nearest note in cents, or 0 if no pitch was detected
+pub struct Tuner {
+    shared: Arc<TunerShared>,
+    min_freq: Sample,
+    max_freq: Sample,
+    threshold: Sample,
+    silence_threshold: Sample,
+    sample_rate: Sample,
+    buffer: Vec<Sample>,
+    buffer_pos: usize,
+    /// The cumulative mean normalized difference function, indexed by lag
+    cmnd: Vec<Sample>,
+    frequency: Sample,
+    cents: Sample,
+}
+
+impl Tuner {
+    /// Create a new [`Tuner`] and the [`TunerMeter`] for reading its result
+    pub fn new() -> (Self, TunerMeter) {
+        let shared = Arc::new(TunerShared {
+            pitch: AtomicU64::new(0),
+            reference: AtomicU32::new((440.0 as Sample).to_bits()),
+        });
+        (
+            Self {
+                shared: shared.clone(),
+                min_freq: 60.,
+                max_freq: 1500.,
+                threshold: 0.15,
+                silence_threshold: 0.003,
+                sample_rate: 0.,
+                buffer: vec![],
+                buffer_pos: 0,
+                cmnd: vec![],
+                frequency: 0.,
+                cents: 0.,
+            },
+            TunerMeter { shared },
+        )
+    }
+    /// Set the lowest frequency that can be detected. If it is above
+    /// [`Tuner::max_freq`] the two are swapped. Default: 60
+    pub fn min_freq(mut self, freq: Sample) -> Self {
+        self.min_freq = freq;
+        self
+    }
+    /// Set the highest frequency that can be detected. If it is below
+    /// [`Tuner::min_freq`] the two are swapped. Default: 1500
+    pub fn max_freq(mut self, freq: Sample) -> Self {
+        self.max_freq = freq;
+        self
+    }
+    /// Set the YIN threshold between 0 and 1. Lower values reject noisy
+    /// signals more strictly. Default: 0.15
+    pub fn threshold(mut self, threshold: Sample) -> Self {
+        self.threshold = threshold;
+        self
+    }
+    /// Set the RMS amplitude below which the input is considered silent. Default: 0.003 (about -50 dB)
+    pub fn silence_threshold(mut self, amplitude: Sample) -> Self {
+        self.silence_threshold = amplitude;
+        self
+    }
+    /// Upload to the current graph, returning a handle to the new node
+    pub fn upload(self) -> Handle<GenericHandle> {
+        let node_id = knyst_commands().push_without_inputs(self);
+        Handle::new(GenericHandle::new(node_id, 1, 2))
+    }
+    /// Run YIN on the buffer, returning the frequency and confidence
+    fn analyse(&mut self) -> Option<(Sample, Sample)> {
+        let max_lag = self.cmnd.len() - 1;
+        let window = self.buffer.len() - max_lag;
+        let energy: Sample = self.buffer.iter().map(|v| v * v).sum();
+        if (energy / self.buffer.len() as Sample).sqrt() < self.silence_threshold {
+            return None;
+        }
+        let min_lag = ((self.sample_rate / self.max_freq) as usize).clamp(2, max_lag - 1);
+        self.cmnd[0] = 1.0;
+        let mut running_sum = 0.0;
+        for lag in 1..=max_lag {
+            let difference: Sample = self.buffer[..window]
+                .iter()
+                .zip(&self.buffer[lag..lag + window])
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            running_sum += difference;
+            self.cmnd[lag] = if running_sum > 0.0 {
+                difference * lag as Sample / running_sum
+            } else {
+                1.0
+            };
+        }
+        // The first dip below the threshold is the fundamental, the
+        // following dips are its multiples.
+        let mut lag = min_lag;
+        while self.cmnd[lag] >= self.threshold {
+            lag += 1;
+            if lag >= max_lag {
+                return None;
+            }
+        }
+        while lag + 1 < max_lag && self.cmnd[lag + 1] < self.cmnd[lag] {
+            lag += 1;
+        }
+        // Parabolic interpolation for sub sample accuracy
+        let (a, b, c) = (self.cmnd[lag - 1], self.cmnd[lag], self.cmnd[lag + 1]);
+        let denominator = a + c - 2.0 * b;
+        let offset = if denominator.abs() > Sample::EPSILON {
+            (0.5 * (a - c) / denominator).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        Some((
+            self.sample_rate / (lag as Sample + offset),
+            (1.0 - b).clamp(0.0, 1.0),
+        ))
+    }
+}
+
+impl Gen for Tuner {
+    fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let input = ctx.inputs.get_channel(0);
+        let mut outputs = ctx.outputs.iter_mut();
+        let frequency_out = outputs.next().unwrap();
+        let cents_out = outputs.next().unwrap();
+        for i in 0..block_size {
+            self.buffer[self.buffer_pos] = input[i];
+            self.buffer_pos += 1;
+            if self.buffer_pos == self.buffer.len() {
+                self.buffer_pos = 0;
+                match self.analyse() {
+                    Some((frequency, confidence)) => {
+                        self.frequency = frequency;
+                        self.cents = nearest_note(frequency, self.shared.reference()).1;
+                        self.shared.store(frequency, confidence);
+                    }
+                    None => {
+                        self.frequency = 0.0;
+                        self.cents = 0.0;
+                        self.shared.store(0.0, 0.0);
+                    }
+                }
+            }
+            frequency_out[i] = self.frequency;
+            cents_out[i] = self.cents;
+        }
+        GenState::Continue
+    }
+
+    fn num_inputs(&self) -> usize {
+        1
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+
+    fn init(&mut self, _block_size: usize, sample_rate: Sample, _node_id: crate::graph::NodeId) {
+        self.sample_rate = sample_rate;
+        if self.min_freq > self.max_freq {
+            std::mem::swap(&mut self.min_freq, &mut self.max_freq);
+        }
+        // At least 3 lags are needed for the parabolic interpolation
+        let max_lag = ((sample_rate / self.min_freq).ceil() as usize + 1).max(3);
+        self.buffer = vec![0.0; max_lag * 2];
+        self.buffer_pos = 0;
+        self.cmnd = vec![1.0; max_lag + 1];
+        self.frequency = 0.0;
+        self.cents = 0.0;
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        match input {
+            0 => "input",
+            _ => "",
+        }
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        match output {
+            0 => "frequency",
+            1 => "cents",
+            _ => "",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Tuner"
+    }
+}
+
+/// Upload a [`Tuner`] to the current graph and return a handle to it
+/// together with the [`TunerMeter`] for reading the detected pitch.
+pub fn tuner() -> (Handle<GenericHandle>, TunerMeter) {
+    let (tuner, meter) = Tuner::new();
+    (tuner.upload(), meter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tuner;
+    use crate::{
+        gen::{testing::GenTester, Gen},
+        graph::NodeId,
+        Sample,
+    };
+
+    const BLOCK_SIZE: usize = 64;
+    const SR: Sample = 44100.;
+
+    /// Run one second of a signal through the tuner, returning the last value of each output
+    fn run(tuner: &mut GenTester<Tuner>, signal: impl Fn(Sample) -> Sample) -> (Sample, Sample) {
+        tuner.gen.init(BLOCK_SIZE, SR, NodeId::new(0));
+        for block in 0..(SR as usize / BLOCK_SIZE) {
+            for (i, sample) in tuner.input_mut(0).iter_mut().enumerate() {
+                *sample = signal((block * BLOCK_SIZE + i) as Sample / SR);
+            }
+            tuner.process_block();
+        }
+        (
+            tuner.output(0)[BLOCK_SIZE - 1],
+            tuner.output(1)[BLOCK_SIZE - 1],
+        )
+    }
+
+    #[test]
+    fn detects_notes_and_cents() {
+        let tau = std::f64::consts::TAU as Sample;
+        let (tuner, meter) = Tuner::new();
+        let mut tuner = GenTester::new(tuner, BLOCK_SIZE, SR);
+        // A4, 10 cents sharp
+        let freq = 440. * (2.0 as Sample).powf(10. / 1200.);
+        let (frequency, cents) = run(&mut tuner, |t| (t * freq * tau).sin() * 0.5);
+        assert!((frequency - freq).abs() < 0.5, "{frequency}");
+        assert!((cents - 10.).abs() < 1.0, "{cents}");
+        let reading = meter.reading().unwrap();
+        assert_eq!(reading.midi_note, 69);
+        assert_eq!(reading.to_string().split(' ').next(), Some("A4"));
+        // Low E on a guitar as a sawtooth with strong harmonics, 20 cents flat
+        let freq = 82.407 * (2.0 as Sample).powf(-20. / 1200.);
+        run(&mut tuner, |t| ((t * freq).fract() * 2.0 - 1.0) * 0.5);
+        let reading = meter.reading().unwrap();
+        assert_eq!((reading.note_name(), reading.octave()), ("E", 2));
+        assert!((reading.cents + 20.).abs() < 2.0, "{reading}");
+        // Silence
+        let (frequency, _) = run(&mut tuner, |_| 0.0);
+        assert_eq!(frequency, 0.0);
+        assert!(meter.reading().is_none());
+    }
+
+    #[test]
+    fn swapped_or_extreme_ranges_do_not_panic() {
+        let tau = std::f64::consts::TAU as Sample;
+        let signal = |t: Sample| (t * 440. * tau).sin() * 0.5;
+        let (tuner, meter) = Tuner::new();
+        let mut tuner = GenTester::new(tuner.min_freq(1500.).max_freq(60.), BLOCK_SIZE, SR);
+        let (frequency, _) = run(&mut tuner, signal);
+        assert!((frequency - 440.).abs() < 0.5, "{frequency}");
+        assert_eq!(meter.reading().unwrap().midi_note, 69);
+        // A range above the Nyquist frequency can't detect anything
+        let (tuner, _) = Tuner::new();
+        let mut tuner = GenTester::new(tuner.min_freq(30000.).max_freq(40000.), BLOCK_SIZE, SR);
+        run(&mut tuner, signal);
+    }
+}
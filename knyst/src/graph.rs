@@ -5,12 +5,10 @@
 //!
 //! ```
 //! # use knyst::prelude::*;
-//! let graph_settings = GraphSettings {
-//!     block_size: 64,
-//!     sample_rate: 44100.,
-//!     num_outputs: 2,
-//!     ..Default::default()
-//! };
+//! let graph_settings = GraphSettings::default()
+//!     .block_size(64)
+//!     .sample_rate(44100.)
+//!     .num_outputs(2);
 //! let mut graph = Graph::new(graph_settings);
 //! // Adding a node gives you an address to that node
 //! let sine_node_address = graph.push(WavetableOscillatorOwned::new(Wavetable::sine()));
@@ -38,13 +36,18 @@ pub mod connection;
 mod graph_gen;
 mod node;
 pub mod run_graph;
+pub mod scheduler_strategy;
 pub use crate::node_buffer::NodeBufferRef;
 pub use connection::Connection;
 use connection::ConnectionError;
-use node::Node;
-pub use run_graph::{RunGraph, RunGraphSettings};
-
-use crate::inspection::{EdgeInspection, EdgeSource, GraphInspection, NodeInspection};
+use node::{ConstantRamp, Node};
+pub use run_graph::{InputMonitor, RunGraph, RunGraphSettings};
+use scheduler_strategy::GraphTopology;
+pub use scheduler_strategy::SchedulerStrategy;
+
+use crate::inspection::{
+    EdgeInspection, EdgeSource, GraphInspection, GraphInspector, NodeInspection,
+};
 use crate::scheduling::MusicalTimeMap;
 use crate::time::{Beats, Seconds};
 use rtrb::RingBuffer;
@@ -54,7 +57,7 @@ use std::cell::UnsafeCell;
 use std::collections::{HashMap, HashSet};
 use std::mem;
 use std::sync::atomic::Ordering;
-use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
@@ -219,6 +222,9 @@ pub struct SimultaneousChanges {
     pub time: Time,
     /// What changes to apply at the same time
     pub changes: Vec<NodeChanges>,
+    /// Overrides the scheduling latency of the graph for these changes if
+    /// set. Only applies to [`Time::DurationFromNow`] and [`Time::Beats`].
+    pub latency: Option<Duration>,
 }
 impl SimultaneousChanges {
     /// Empty `Self` set to be scheduled as soon as possible
@@ -226,6 +232,15 @@ impl SimultaneousChanges {
         Self {
             time: Time::Immediately,
             changes: vec![],
+            latency: None,
+        }
+    }
+    /// Empty `Self` set to be scheduled at `time`
+    pub fn at(time: Time) -> Self {
+        Self {
+            time,
+            changes: vec![],
+            latency: None,
         }
     }
     /// Empty `Self` set to be scheduled a specified wall clock duration from now + latency.
@@ -233,6 +248,7 @@ impl SimultaneousChanges {
         Self {
             time: Time::DurationFromNow(duration),
             changes: vec![],
+            latency: None,
         }
     }
     /// Empty `Self` set to be scheduled a specified beat time.
@@ -240,8 +256,17 @@ impl SimultaneousChanges {
         Self {
             time: Time::Beats(beats),
             changes: vec![],
+            latency: None,
         }
     }
+    /// Use `latency` instead of the scheduling latency of the graph for these
+    /// changes, e.g. a very short latency for live triggers or a long one for
+    /// sequenced material. See [`Graph::minimum_scheduling_latency`] for the
+    /// shortest latency that can be achieved reliably.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
     /// Push a new [`NodeChanges`] into the list of changes that will be scheduled.
     pub fn push(&mut self, node_changes: NodeChanges) -> &mut Self {
         self.changes.push(node_changes);
@@ -431,6 +456,10 @@ struct Task {
     /// The node key may be used to send a message to the Graph to free the node in this Task
     node_key: NodeKey,
     input_constants: *mut [Sample],
+    /// Ramps for dezippering changes to the input constants, owned by the Node
+    input_ramps: *mut [ConstantRamp],
+    /// The length of a constant change ramp, 0 if constant changes should not be dezippered
+    dezipper_samples: usize,
     /// inputs to copy from the graph inputs (whole buffers) in the form `(from_graph_input_index, to_node_input_index)`
     graph_inputs_to_copy: Vec<(usize, usize)>,
     /// list of tuples of single floats in the form `(from, to)` where the `from` points to an output of a different node and the `to` points to the input buffer.
@@ -451,8 +480,19 @@ impl Task {
     fn init_constants(&mut self) {
         // Copy all constants
         let node_constants = unsafe { &*self.input_constants };
-        for (channel, &constant) in node_constants.iter().enumerate() {
-            self.input_buffers.fill_channel(constant, channel);
+        let input_ramps = unsafe { &mut *self.input_ramps };
+        for (channel, (&constant, ramp)) in node_constants
+            .iter()
+            .zip(input_ramps.iter_mut())
+            .enumerate()
+        {
+            if ramp.is_running() {
+                for i in 0..self.input_buffers.block_size() {
+                    self.input_buffers.write(ramp.next(constant), channel, i);
+                }
+            } else {
+                self.input_buffers.fill_channel(constant, channel);
+            }
         }
     }
     #[inline]
@@ -461,8 +501,13 @@ impl Task {
             ScheduledChangeKind::Constant { index, value } => {
                 let node_constants = unsafe { &mut *self.input_constants };
                 node_constants[index] = value;
+                let ramp = unsafe { &mut (*self.input_ramps)[index] };
+                // The input buffer only contains constants at this point so
+                // this is the value the ramp should start from.
+                let previous_value = self.input_buffers.read(index, start_sample_in_block);
+                ramp.start(previous_value, value, self.dezipper_samples);
                 for i in start_sample_in_block..self.input_buffers.block_size() {
-                    self.input_buffers.write(value, index, i);
+                    self.input_buffers.write(ramp.next(value), index, i);
                 }
             }
             ScheduledChangeKind::Trigger { index } => {
@@ -583,12 +628,78 @@ impl std::fmt::Debug for OutputTask {
 }
 unsafe impl Send for OutputTask {}
 
+/// The number of nodes and connections in a [`Graph`] and its quotas, see
+/// [`Graph::counts`] and [`GraphSettings::max_nodes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GraphCounts {
+    /// The graph the counts are for
+    pub graph_id: GraphId,
+    /// The number of nodes, excluding nodes waiting to be freed
+    pub nodes: usize,
+    /// The number of connections, counting every channel as one connection
+    pub connections: usize,
+    /// The maximum number of nodes allowed, if any
+    pub max_nodes: Option<usize>,
+    /// The maximum number of connections allowed, if any
+    pub max_connections: Option<usize>,
+}
+
+/// The value of an input constant of a node together with its default, see
+/// [`Graph::input_constants`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputConstant {
+    /// The label of the input
+    pub name: &'static str,
+    /// The latest value set for the constant, including changes scheduled for later
+    pub value: Sample,
+    /// The value of the constant when the node was created, see [`Gen::input_default`]
+    pub default: Sample,
+}
+
+impl InputConstant {
+    /// Returns true if the constant has its default value
+    pub fn is_default(&self) -> bool {
+        self.value == self.default
+    }
+}
+
+/// Why a node was freed, see [`NodeDoneEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeDoneKind {
+    /// The node freed itself by returning [`GenState::FreeSelf`] or
+    /// [`GenState::FreeSelfMendConnections`], e.g. when an envelope finished.
+    FreedSelf,
+    /// The node was freed from outside of the audio thread, e.g. using [`Graph::free_node`].
+    Freed,
+}
+
+/// Sent to everyone who registered interest in a node using
+/// [`Graph::notify_when_done`] when that node is freed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeDoneEvent {
+    /// The node that was freed
+    pub node: NodeId,
+    /// Why the node was freed
+    pub kind: NodeDoneKind,
+    /// The time since the graph started running of the start of the block in
+    /// which the node freed itself, or of the next block to be processed if it
+    /// was freed from outside of the audio thread.
+    pub time: Seconds,
+}
+
 /// Error pushing a new node (Gen or Graph) to a Graph
 #[allow(missing_docs)]
 #[derive(thiserror::Error, Debug)]
 pub enum PushError {
     #[error("The graph was not started and the given start time of a Gen could therefore not be calculated: `{0:?}`.")]
     InvalidStartTimeOnUnstartedGraph(Time),
+    #[error("The graph {graph_id} already contains the maximum of {max_nodes} nodes. The GenOrGraph that was pushed is returned.")]
+    NodeQuotaExceeded {
+        g: GenOrGraphEnum,
+        graph_id: GraphId,
+        max_nodes: usize,
+    },
     #[error("The target graph (`{target_graph}`) was not found. The GenOrGraph that was pushed is returned.")]
     GraphNotFound {
         g: GenOrGraphEnum,
@@ -638,6 +749,24 @@ pub enum ScheduleError {
     },
 }
 
+/// Error adding or removing an ordering constraint between two nodes, see [`Graph::order_nodes`]
+#[allow(missing_docs)]
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum OrderError {
+    #[error("The nodes are in different graphs. Only nodes in the same graph can be ordered.")]
+    DifferentGraphs,
+    #[error("The graph containing the NodeId provided was not found: {0:?}")]
+    GraphNotFound(NodeId),
+    #[error("The node was not found, it may have been freed already: {0:?}")]
+    NodeNotFound(NodeId),
+    #[error("A node cannot be ordered relative to itself.")]
+    SameNode,
+    #[error("The constraint conflicts with the connections or constraints already in the graph since the node that should run first depends on the node that should run after it.")]
+    Cycle,
+    #[error("The scheduler strategy {strategy} of graph {graph_id} returned an invalid node order. The graph fell back to depth first order.")]
+    InvalidStrategyOrder { strategy: String, graph_id: GraphId },
+}
+
 /// Holds either a boxed [`Gen`] or a [`Graph`]
 #[allow(missing_docs)]
 pub enum GenOrGraphEnum {
@@ -891,9 +1020,171 @@ impl Gen for ClosureGen {
     }
 }
 
+type StateProcessFn<S> = Box<
+    dyn (FnMut(&mut S, &NodeBufferRef, &mut NodeBufferRef, StateGenContext) -> GenState) + Send,
+>;
+type StateInitFn<S> = Box<dyn FnMut(&mut S, Sample, usize) + Send>;
+
+/// Information passed to the process closure of a [`StateClosureGen`] in
+/// addition to the state and the input and output buffers.
+pub struct StateGenContext<'a> {
+    /// The sample rate of the [`Graph`] that the Gen is in.
+    pub sample_rate: Sample,
+    /// The current block size
+    pub block_size: usize,
+    /// The [`Resources`] of the [`Graph`]
+    pub resources: &'a mut Resources,
+}
+
+/// Convenience struct to create a [`Gen`] from a closure and a state owned by
+/// the Gen. The state is passed to the closure mutably every block which
+/// removes the need for capturing and sharing variables for prototyping
+/// custom DSP.
+///
+/// An init closure receiving the state, the sample rate and the block size
+/// can be added using [`StateClosureGen::init`]. It is called when the node
+/// is pushed to a [`Graph`], before the first block is processed.
+///
+/// Inputs and outputs are declared the same way as for [`ClosureGen`].
+///
+/// # Example
+/// ```
+/// use knyst::prelude::*;
+/// // A one pole lowpass filter
+/// struct Lowpass {
+///     last: Sample,
+///     coeff: Sample,
+/// }
+/// let lowpass = gen_with_state(
+///     Lowpass { last: 0.0, coeff: 0.0 },
+///     |state, inputs, outputs, ctx| {
+///         let input = inputs.get_channel(0);
+///         let out = outputs.iter_mut().next().unwrap();
+///         for (o, i) in out.iter_mut().zip(input.iter()).take(ctx.block_size) {
+///             state.last += (i - state.last) * state.coeff;
+///             *o = state.last;
+///         }
+///         GenState::Continue
+///     },
+/// )
+/// .init(|state, sample_rate, _block_size| {
+///     state.coeff = 1.0 - (-std::f32::consts::TAU * 1000.0 / sample_rate).exp();
+/// })
+/// .input("in")
+/// .output("out")
+/// .name("Lowpass");
+/// ```
+pub struct StateClosureGen<S> {
+    state: S,
+    process_fn: StateProcessFn<S>,
+    init_fn: Option<StateInitFn<S>>,
+    outputs: Vec<&'static str>,
+    inputs: Vec<&'static str>,
+    name: &'static str,
+}
+/// Alias for [`StateClosureGen::new`]. See [`StateClosureGen`] for more information.
+pub fn gen_with_state<S: Send + 'static>(
+    initial_state: S,
+    process: impl (FnMut(&mut S, &NodeBufferRef, &mut NodeBufferRef, StateGenContext) -> GenState)
+        + 'static
+        + Send,
+) -> StateClosureGen<S> {
+    StateClosureGen {
+        state: initial_state,
+        process_fn: Box::new(process),
+        init_fn: None,
+        outputs: Default::default(),
+        inputs: Default::default(),
+        name: "StateClosureGen",
+    }
+}
+impl<S: Send + 'static> StateClosureGen<S> {
+    /// Create a [`StateClosureGen`] with the given state and closure, 0
+    /// outputs and 0 inputs. Add inputs/outputs with the respective functions.
+    pub fn new(
+        initial_state: S,
+        process: impl (FnMut(&mut S, &NodeBufferRef, &mut NodeBufferRef, StateGenContext) -> GenState)
+            + 'static
+            + Send,
+    ) -> Self {
+        gen_with_state(initial_state, process)
+    }
+    /// Set a closure to be called with the state, the sample rate and the
+    /// block size when the Gen is initialised.
+    pub fn init(mut self, init: impl FnMut(&mut S, Sample, usize) + 'static + Send) -> Self {
+        self.init_fn = Some(Box::new(init));
+        self
+    }
+    /// Adds an output. The order of outputs depends on the order they are added.
+    pub fn output(mut self, output_name: &'static str) -> Self {
+        self.outputs.push(output_name);
+        self
+    }
+    /// Adds an input. The order of inputs depends on the order they are added.
+    pub fn input(mut self, input_name: &'static str) -> Self {
+        self.inputs.push(input_name);
+        self
+    }
+    /// Set the name of the StateClosureGen.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+}
+
+impl<S: Send + 'static> Gen for StateClosureGen<S> {
+    fn process(&mut self, ctx: GenContext, resources: &mut Resources) -> GenState {
+        let block_size = ctx.block_size();
+        let state_ctx = StateGenContext {
+            sample_rate: ctx.sample_rate,
+            block_size,
+            resources,
+        };
+        (self.process_fn)(&mut self.state, ctx.inputs, ctx.outputs, state_ctx)
+    }
+
+    fn num_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    fn init(&mut self, block_size: usize, sample_rate: Sample, _node_id: NodeId) {
+        if let Some(init_fn) = &mut self.init_fn {
+            init_fn(&mut self.state, sample_rate, block_size);
+        }
+    }
+
+    fn input_desc(&self, input: usize) -> &'static str {
+        self.inputs.get(input).unwrap_or(&"")
+    }
+
+    fn output_desc(&self, output: usize) -> &'static str {
+        self.outputs.get(output).unwrap_or(&"")
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Copy channel names into an existing list of strings, reusing its allocations
+fn copy_channel_names(target: &mut Vec<String>, names: &[&'static str]) {
+    target.truncate(names.len());
+    for (target, name) in target.iter_mut().zip(names) {
+        target.clear();
+        target.push_str(name);
+    }
+    for name in &names[target.len()..] {
+        target.push(name.to_string());
+    }
+}
+
 new_key_type! {
     /// Node identifier in a specific Graph. For referring to a Node outside of the context of a Graph, use NodeId instead.
-    struct NodeKey;
+    pub(crate) struct NodeKey;
 }
 
 /// Describes the oversampling applied to a graph
@@ -956,9 +1247,35 @@ pub struct GraphSettings {
     /// Ring buffers are used pass information back and forth between the audio
     /// thread (GraphGen) and the Graph.
     pub ring_buffer_size: usize,
+    /// If set, changes to input constants are interpolated linearly over this
+    /// duration to avoid clicks. Individual inputs can opt out through
+    /// [`Gen::input_dezipper`].
+    pub dezipper: Option<Duration>,
+    /// If set, pushing a node to a graph that already contains this many
+    /// nodes, including internal feedback nodes, fails with
+    /// [`PushError::NodeQuotaExceeded`]. A feedback connection which needs a
+    /// new feedback node fails with [`ConnectionError::NodeQuotaExceeded`].
+    /// Guards against unbounded growth, e.g. from a bug in generative code
+    /// spawning nodes. Nodes in inner graphs are counted by their own graph.
+    pub max_nodes: Option<usize>,
+    /// If set, making a connection that would result in the graph having
+    /// more than this many connections fails with
+    /// [`ConnectionError::ConnectionQuotaExceeded`]. Every channel of a
+    /// connection, including feedback connections, counts as one connection.
+    /// Connections to constants don't count.
+    pub max_connections: Option<usize>,
+    /// Plans the order in which the nodes of the graph are processed. The
+    /// default is [`DepthFirst`](scheduler_strategy::DepthFirst), see
+    /// [`scheduler_strategy`] for the alternatives.
+    pub scheduler_strategy: Arc<dyn SchedulerStrategy>,
 }
 
 impl GraphSettings {
+    /// Set the name to a new value
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
     /// Set the num_inputs to a new value
     pub fn num_inputs(mut self, num_inputs: usize) -> Self {
         self.num_inputs = num_inputs;
@@ -974,11 +1291,58 @@ impl GraphSettings {
         self.oversampling = oversampling;
         self
     }
-    /// Set the oversampling to a new value
+    /// Set the block_size to a new value
     pub fn block_size(mut self, block_size: usize) -> Self {
         self.block_size = block_size;
         self
     }
+    /// Set the max_node_inputs to a new value
+    pub fn max_node_inputs(mut self, max_node_inputs: usize) -> Self {
+        self.max_node_inputs = max_node_inputs;
+        self
+    }
+    /// Set the num_nodes to a new value
+    pub fn num_nodes(mut self, num_nodes: usize) -> Self {
+        self.num_nodes = num_nodes;
+        self
+    }
+    /// Set the sample_rate to a new value
+    pub fn sample_rate(mut self, sample_rate: Sample) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+    /// Set the ring_buffer_size to a new value
+    pub fn ring_buffer_size(mut self, ring_buffer_size: usize) -> Self {
+        self.ring_buffer_size = ring_buffer_size;
+        self
+    }
+    /// Interpolate changes to input constants over `duration`
+    pub fn dezipper(mut self, duration: Duration) -> Self {
+        self.dezipper = Some(duration);
+        self
+    }
+    /// Limit the number of nodes in the graph, see [`GraphSettings::max_nodes`]
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+    /// Limit the number of connections in the graph, see [`GraphSettings::max_connections`]
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+    /// Set the strategy for planning the order in which nodes are processed,
+    /// see [`GraphSettings::scheduler_strategy`]
+    pub fn scheduler_strategy(mut self, strategy: impl SchedulerStrategy + 'static) -> Self {
+        self.scheduler_strategy = Arc::new(strategy);
+        self
+    }
+    /// Set a strategy shared with other graphs or chosen at runtime, see
+    /// [`GraphSettings::scheduler_strategy`]
+    pub fn shared_scheduler_strategy(mut self, strategy: Arc<dyn SchedulerStrategy>) -> Self {
+        self.scheduler_strategy = strategy;
+        self
+    }
 }
 
 impl Default for GraphSettings {
@@ -993,10 +1357,26 @@ impl Default for GraphSettings {
             sample_rate: 48000.0,
             oversampling: Oversampling::X1,
             ring_buffer_size: 1000,
+            dezipper: None,
+            max_nodes: None,
+            max_connections: None,
+            scheduler_strategy: Arc::new(scheduler_strategy::DepthFirst),
         }
     }
 }
 
+/// The shortest scheduling latency at which changes can reliably be applied
+/// on time for a graph with the given block size and sample rate.
+///
+/// Changes are applied by the audio thread at the start of every block and the
+/// audio thread clock can be up to one block ahead of the wall clock, so
+/// anything scheduled less than two blocks into the future risks being applied
+/// late. Scheduling with a shorter latency is allowed, but the change will
+/// then be applied as soon as possible rather than at the exact time.
+pub fn minimum_scheduling_latency(block_size: usize, sample_rate: Sample) -> Duration {
+    Duration::from_secs_f64((block_size * 2) as f64 / sample_rate as f64)
+}
+
 /// Hold on to an allocation and drop it when we're done. Can be easily wrapped
 /// in an Arc. This ensures we free the memory.
 struct OwnedRawBuffer {
@@ -1035,12 +1415,10 @@ impl Drop for OwnedRawBuffer {
 /// ```
 /// use knyst::prelude::*;
 /// use knyst::graph::RunGraph;
-/// let graph_settings = GraphSettings {
-///     block_size: 64,
-///     sample_rate: 44100.,
-///     num_outputs: 2,
-///     ..Default::default()
-/// };
+/// let graph_settings = GraphSettings::default()
+///     .block_size(64)
+///     .sample_rate(44100.)
+///     .num_outputs(2);
 /// let mut graph = Graph::new(graph_settings);
 /// let resources = Resources::new(ResourcesSettings::default());
 /// let (mut run_graph, _, _) = RunGraph::new(&mut graph, resources, RunGraphSettings::default())?;
@@ -1074,10 +1452,18 @@ pub struct Graph {
     node_keys_pending_removal: HashSet<NodeKey>,
     /// A list of input edges for every node, sharing the same index as the node
     node_input_edges: SecondaryMap<NodeKey, Vec<Edge>>,
+    /// Nodes which have to be run before the node, without being connected to it
+    node_order_constraints: SecondaryMap<NodeKey, Vec<NodeKey>>,
     node_input_index_to_name: SecondaryMap<NodeKey, Vec<&'static str>>,
     node_input_name_to_index: SecondaryMap<NodeKey, HashMap<&'static str, usize>>,
     node_output_index_to_name: SecondaryMap<NodeKey, Vec<&'static str>>,
     node_output_name_to_index: SecondaryMap<NodeKey, HashMap<&'static str, usize>>,
+    /// The latest value set for every input constant of every node, including
+    /// changes scheduled for later. The constants in the nodes are owned by
+    /// the audio thread once the graph is running.
+    node_input_constants: SecondaryMap<NodeKey, Vec<Sample>>,
+    /// The default value of every input constant of every node, see [`Gen::input_default`]
+    node_input_defaults: SecondaryMap<NodeKey, Vec<Sample>>,
     /// List of feedback input edges for every node. The NodeKey in the tuple is the index of the FeedbackNode doing the buffering
     node_feedback_edges: SecondaryMap<NodeKey, Vec<FeedbackEdge>>,
     node_feedback_node_key: SecondaryMap<NodeKey, NodeKey>,
@@ -1085,6 +1471,8 @@ pub struct Graph {
     node_ids: SecondaryMap<NodeKey, NodeId>,
     /// If a node can be freed or not. A node can be made immortal to avoid accidentally removing it.
     node_mortality: SecondaryMap<NodeKey, bool>,
+    /// Channels to notify when a node is freed
+    node_done_senders: SecondaryMap<NodeKey, Vec<crossbeam_channel::Sender<NodeDoneEvent>>>,
     node_order: Vec<NodeKey>,
     disconnected_nodes: Vec<NodeKey>,
     feedback_node_indices: Vec<NodeKey>,
@@ -1098,12 +1486,25 @@ pub struct Graph {
     graph_input_to_output_edges: Vec<InterGraphEdge>,
     /// If changes have been made that require recalculating the graph this will be set to true.
     recalculation_required: bool,
+    /// The number of edit batches that have been started but not committed.
+    /// Changes are only sent to the GraphGen when this is 0.
+    edit_batch_depth: usize,
     num_inputs: usize,
     num_outputs: usize,
     block_size: usize,
     sample_rate: Sample,
     oversampling: Oversampling,
     ring_buffer_size: usize,
+    dezipper: Option<Duration>,
+    max_nodes: Option<usize>,
+    max_connections: Option<usize>,
+    /// The number of connections in the graph, kept up to date by every
+    /// change to the edges, see [`Graph::num_connections`]
+    num_connections: usize,
+    scheduler_strategy: Arc<dyn SchedulerStrategy>,
+    /// Set when the [`SchedulerStrategy`] returned an invalid order while
+    /// the graph was updated, see [`Graph::take_scheduler_error`]
+    scheduler_error: Option<OrderError>,
     initiated: bool,
     /// Used for processing every node, index using \[input_num\]\[sample_in_block\]
     // inputs_buffers: Vec<Box<[Sample]>>,
@@ -1112,10 +1513,7 @@ pub struct Graph {
     max_node_inputs: usize,
     graph_gen_communicator: Option<GraphGenCommunicator>,
     /// For storing changes made before the graph is started. When the GraphGen is created, the changes will be scheduled on the scheduler.
-    scheduled_changes_queue: Vec<(
-        Vec<(NodeKey, ScheduledChangeKind, Option<TimeOffset>)>,
-        Time,
-    )>,
+    scheduled_changes_queue: Vec<SchedulingQueueItem>,
 }
 
 impl Default for Graph {
@@ -1137,6 +1535,10 @@ impl Graph {
             sample_rate,
             oversampling,
             ring_buffer_size,
+            dezipper,
+            max_nodes,
+            max_connections,
+            scheduler_strategy,
         } = options;
         let inputs_buffers_ptr = Box::<[Sample]>::into_raw(
             vec![0.0 as Sample; block_size * oversampling.as_usize() * max_node_inputs]
@@ -1156,14 +1558,18 @@ impl Graph {
             name,
             nodes,
             node_input_edges,
+            node_order_constraints: SecondaryMap::with_capacity(num_nodes),
             node_input_index_to_name: SecondaryMap::with_capacity(num_nodes),
             node_input_name_to_index: SecondaryMap::with_capacity(num_nodes),
             node_output_index_to_name: SecondaryMap::with_capacity(num_nodes),
             node_output_name_to_index: SecondaryMap::with_capacity(num_nodes),
+            node_input_constants: SecondaryMap::with_capacity(num_nodes),
+            node_input_defaults: SecondaryMap::with_capacity(num_nodes),
             node_feedback_node_key: SecondaryMap::with_capacity(num_nodes),
             node_feedback_edges,
             node_ids: SecondaryMap::with_capacity(num_nodes),
             node_mortality: SecondaryMap::with_capacity(num_nodes),
+            node_done_senders: SecondaryMap::with_capacity(num_nodes),
             node_order: Vec::with_capacity(num_nodes),
             disconnected_nodes: vec![],
             node_keys_to_free_when_safe: vec![],
@@ -1181,8 +1587,15 @@ impl Graph {
             inputs_buffers_ptr,
             max_node_inputs,
             ring_buffer_size,
+            dezipper,
+            max_nodes,
+            max_connections,
+            num_connections: 0,
+            scheduler_strategy,
+            scheduler_error: None,
             graph_gen_communicator: None,
             recalculation_required: false,
+            edit_batch_depth: 0,
             buffers_to_free_when_safe: vec![],
             new_inputs_buffers_ptr: false,
             graph_input_to_output_edges,
@@ -1223,12 +1636,82 @@ impl Graph {
             sample_rate: self.sample_rate,
             oversampling: self.oversampling,
             ring_buffer_size: self.ring_buffer_size,
+            dezipper: self.dezipper,
+            max_nodes: self.max_nodes,
+            max_connections: self.max_connections,
+            scheduler_strategy: self.scheduler_strategy.clone(),
         }
     }
     /// Returns a number including both active nodes and nodes waiting to be safely freed
     pub fn num_stored_nodes(&self) -> usize {
         self.get_nodes().len()
     }
+    /// Returns the number of nodes in the graph, excluding nodes waiting to be
+    /// freed. This is the number of nodes counted towards
+    /// [`GraphSettings::max_nodes`].
+    pub fn num_active_nodes(&self) -> usize {
+        self.get_nodes().len() - self.node_keys_pending_removal.len()
+    }
+    /// Returns the number of connections in the graph, counting every channel
+    /// as one connection. This is the number of connections counted towards
+    /// [`GraphSettings::max_connections`].
+    pub fn num_connections(&self) -> usize {
+        self.num_connections
+    }
+    /// Count the connections by going through every edge, to check the
+    /// running count in tests.
+    #[cfg(test)]
+    fn count_connections(&self) -> usize {
+        // A feedback connection is stored as a feedback edge to the feedback
+        // node and an internal edge from the feedback node to the sink. Only
+        // the feedback edge is counted.
+        let node_edges = self
+            .node_input_edges
+            .values()
+            .map(|edges| self.num_counted_input_edges(edges))
+            .sum::<usize>();
+        node_edges
+            + self
+                .node_feedback_edges
+                .values()
+                .map(Vec::len)
+                .sum::<usize>()
+            + self.graph_input_edges.values().map(Vec::len).sum::<usize>()
+            + self.output_edges.len()
+            + self.graph_input_to_output_edges.len()
+    }
+    /// Set the maximum number of nodes in the graph, see [`GraphSettings::max_nodes`].
+    /// Nodes already in the graph are not affected.
+    pub fn set_max_nodes(&mut self, max_nodes: Option<usize>) {
+        self.max_nodes = max_nodes;
+    }
+    /// Set the maximum number of connections in the graph, see
+    /// [`GraphSettings::max_connections`]. Connections already in the graph
+    /// are not affected.
+    pub fn set_max_connections(&mut self, max_connections: Option<usize>) {
+        self.max_connections = max_connections;
+    }
+    /// Returns the current node and connection counts of this graph together
+    /// with its quotas.
+    pub fn counts(&self) -> GraphCounts {
+        GraphCounts {
+            graph_id: self.id,
+            nodes: self.num_active_nodes(),
+            connections: self.num_connections(),
+            max_nodes: self.max_nodes,
+            max_connections: self.max_connections,
+        }
+    }
+    /// Returns the counts of the graph with the given id if it is this graph
+    /// or any graph inside it. See [`Graph::counts`].
+    pub fn counts_of_graph(&self, graph_id: GraphId) -> Option<GraphCounts> {
+        if graph_id == self.id {
+            return Some(self.counts());
+        }
+        self.graphs_per_node
+            .values()
+            .find_map(|graph| graph.counts_of_graph(graph_id))
+    }
     #[allow(missing_docs)]
     pub fn id(&self) -> GraphId {
         self.id
@@ -1271,6 +1754,170 @@ impl Graph {
         }
         Ok(())
     }
+    /// Send a [`NodeDoneEvent`] through `sender` when the node is freed,
+    /// either because it freed itself or because it was freed manually. The
+    /// node can be in this graph or in any graph inside it.
+    pub fn notify_when_done(
+        &mut self,
+        node_id: NodeId,
+        sender: crossbeam_channel::Sender<NodeDoneEvent>,
+    ) -> Result<(), ScheduleError> {
+        if node_id.graph_id() == self.id {
+            let Some(key) = Self::key_from_id(&self.node_ids, node_id) else {
+                return Err(ScheduleError::NodeNotFound);
+            };
+            if !self.get_nodes().contains_key(key) || self.node_keys_pending_removal.contains(&key)
+            {
+                return Err(ScheduleError::NodeNotFound);
+            }
+            match self.node_done_senders.entry(key) {
+                Some(entry) => entry.or_default().push(sender),
+                None => return Err(ScheduleError::NodeNotFound),
+            }
+            return Ok(());
+        }
+        for (_key, graph) in &mut self.graphs_per_node {
+            match graph.notify_when_done(node_id, sender.clone()) {
+                Ok(_) => {
+                    return Ok(());
+                }
+                Err(e) => match e {
+                    ScheduleError::GraphNotFound(_) => (),
+                    _ => {
+                        return Err(e);
+                    }
+                },
+            }
+        }
+        Err(ScheduleError::GraphNotFound(node_id))
+    }
+    /// Returns the order in which the nodes in this graph are processed,
+    /// excluding internal feedback nodes. The order is deterministic for a
+    /// given sequence of changes to the graph.
+    ///
+    /// Changes that have not been committed are included in the order, but
+    /// won't be applied on the audio thread until [`Graph::commit_changes`]
+    /// is called.
+    pub fn node_order(&mut self) -> Vec<NodeId> {
+        if self.recalculation_required {
+            self.calculate_node_order_or_store_error();
+        }
+        self.node_order
+            .iter()
+            .filter_map(|&key| self.node_ids.get(key).copied())
+            .collect()
+    }
+    /// Pin `before` to be processed before `after` even if the nodes are not
+    /// connected, e.g. when `before` writes to a shared tap or bus that `after`
+    /// reads. Both nodes need to be in the same graph, which can be this graph
+    /// or any graph inside it.
+    ///
+    /// The constraint is removed when either node is freed.
+    pub fn order_nodes(&mut self, before: NodeId, after: NodeId) -> Result<(), OrderError> {
+        if before.graph_id() != after.graph_id() {
+            return Err(OrderError::DifferentGraphs);
+        }
+        if before == after {
+            return Err(OrderError::SameNode);
+        }
+        if before.graph_id() == self.id {
+            let (before_key, after_key) = self.order_constraint_keys(before, after)?;
+            if self.node_depends_on(before_key, after_key) {
+                return Err(OrderError::Cycle);
+            }
+            let constraints = &mut self.node_order_constraints[after_key];
+            if !constraints.contains(&before_key) {
+                constraints.push(before_key);
+                self.recalculation_required = true;
+            }
+            return Ok(());
+        }
+        for (_key, graph) in &mut self.graphs_per_node {
+            match graph.order_nodes(before, after) {
+                Err(OrderError::GraphNotFound(_)) => (),
+                result => return result,
+            }
+        }
+        Err(OrderError::GraphNotFound(before))
+    }
+    /// Remove a constraint added using [`Graph::order_nodes`]. Removing a
+    /// constraint that doesn't exist is not an error.
+    pub fn remove_node_order(&mut self, before: NodeId, after: NodeId) -> Result<(), OrderError> {
+        if before.graph_id() != after.graph_id() {
+            return Err(OrderError::DifferentGraphs);
+        }
+        if before.graph_id() == self.id {
+            let (before_key, after_key) = self.order_constraint_keys(before, after)?;
+            let constraints = &mut self.node_order_constraints[after_key];
+            if let Some(i) = constraints.iter().position(|&key| key == before_key) {
+                constraints.remove(i);
+                self.recalculation_required = true;
+            }
+            return Ok(());
+        }
+        for (_key, graph) in &mut self.graphs_per_node {
+            match graph.remove_node_order(before, after) {
+                Err(OrderError::GraphNotFound(_)) => (),
+                result => return result,
+            }
+        }
+        Err(OrderError::GraphNotFound(before))
+    }
+    fn order_constraint_keys(
+        &self,
+        before: NodeId,
+        after: NodeId,
+    ) -> Result<(NodeKey, NodeKey), OrderError> {
+        let key = |id| {
+            Self::key_from_id(&self.node_ids, id)
+                .filter(|key| {
+                    self.get_nodes().contains_key(*key)
+                        && !self.node_keys_pending_removal.contains(key)
+                })
+                .ok_or(OrderError::NodeNotFound(id))
+        };
+        Ok((key(before)?, key(after)?))
+    }
+    /// Returns true if `node` has to be processed after `dependency` because
+    /// of connections or ordering constraints.
+    fn node_depends_on(&self, node: NodeKey, dependency: NodeKey) -> bool {
+        let mut visited = HashSet::new();
+        let mut nodes_to_process = vec![node];
+        while let Some(key) = nodes_to_process.pop() {
+            if key == dependency {
+                return true;
+            }
+            if !visited.insert(key) {
+                continue;
+            }
+            if let Some(edges) = self.node_input_edges.get(key) {
+                nodes_to_process.extend(edges.iter().map(|edge| edge.source));
+            }
+            if let Some(constraints) = self.node_order_constraints.get(key) {
+                nodes_to_process.extend(constraints.iter().copied());
+            }
+        }
+        false
+    }
+    /// Send a [`NodeDoneEvent`] to everyone waiting for the node to be freed.
+    /// `timestamp` is in samples since the start of the graph.
+    fn send_node_done(&mut self, node_key: NodeKey, kind: NodeDoneKind, timestamp: u64) {
+        if let Some(senders) = self.node_done_senders.remove(node_key) {
+            let Some(node) = self.id_from_key(node_key) else {
+                return;
+            };
+            let sample_rate = self.sample_rate as u64 * self.oversampling.as_usize() as u64;
+            let event = NodeDoneEvent {
+                node,
+                kind,
+                time: Seconds::from_samples(timestamp, sample_rate),
+            };
+            for sender in senders {
+                // The receiver may have been dropped which is fine
+                sender.send(event).ok();
+            }
+        }
+    }
     /// Push something implementing [`Gen`] or a [`Graph`] to self creating a
     /// new node whose address is returned.
     pub fn push(&mut self, to_node: impl Into<GenOrGraphEnum>) -> NodeId {
@@ -1358,6 +2005,15 @@ impl Graph {
         start_time: Time,
     ) -> Result<(), PushError> {
         if graph_id == self.id {
+            if let Some(max_nodes) = self.max_nodes {
+                if self.num_active_nodes() >= max_nodes {
+                    return Err(PushError::NodeQuotaExceeded {
+                        g: to_node.into(),
+                        graph_id: self.id,
+                        max_nodes,
+                    });
+                }
+            }
             let (graph, gen) =
                 to_node
                     .into()
@@ -1366,7 +2022,7 @@ impl Graph {
 
             let mut scheduler_ts = false;
             if let Some(ggc) = &mut self.graph_gen_communicator {
-                if let Some(ts) = ggc.scheduler.time_to_frames_timestamp(start_time) {
+                if let Some(ts) = ggc.scheduler.time_to_frames_timestamp(start_time, None) {
                     start_timestamp = ts;
                     scheduler_ts = true;
                 }
@@ -1398,12 +2054,17 @@ impl Graph {
                         start_ts,
                         latency_in_samples,
                         musical_time_map,
+                        clock,
                         ..
                     } = &mut ggc.scheduler
                     {
                         let clock_update = ClockUpdate {
                             timestamp: ggc.timestamp.clone(),
                             clock_sample_rate: self.sample_rate,
+                            external_clock: clock.as_ref().map(|clock| clock.clock.clone()),
+                            external_clock_jumps: clock
+                                .as_ref()
+                                .map_or(0, |clock| clock.clock.jumps.load(Ordering::SeqCst)),
                         };
                         let latency = Duration::from_secs_f64(
                             *latency_in_samples / (self.sample_rate as f64),
@@ -1514,6 +2175,7 @@ impl Graph {
             .enumerate()
             .map(|(i, &name)| (name, i))
             .collect();
+        let input_defaults = node.input_defaults();
         node.init(
             self.block_size * self.oversampling.as_usize(),
             self.sample_rate * (self.oversampling.as_usize() as Sample),
@@ -1521,6 +2183,7 @@ impl Graph {
         );
         let key = self.get_nodes_mut().insert(node);
         self.node_input_edges.insert(key, vec![]);
+        self.node_order_constraints.insert(key, vec![]);
         self.node_feedback_edges.insert(key, vec![]);
         self.graph_input_edges.insert(key, vec![]);
         self.node_input_index_to_name
@@ -1531,6 +2194,9 @@ impl Graph {
             .insert(key, output_index_to_name);
         self.node_output_name_to_index
             .insert(key, output_name_to_index);
+        self.node_input_constants
+            .insert(key, input_defaults.clone());
+        self.node_input_defaults.insert(key, input_defaults);
         self.node_mortality.insert(key, true);
 
         self.node_ids.insert(key, node_id.clone());
@@ -1739,6 +2405,7 @@ impl Graph {
                         || feedback_edges[i].feedback_destination == node_key
                     {
                         feedback_edges.remove(i);
+                        self.num_connections -= 1;
                     } else {
                         i += 1;
                     }
@@ -1774,31 +2441,52 @@ impl Graph {
         if !self.node_mortality[node_key] {
             return Err(FreeError::ImmortalNode);
         }
+        let timestamp = self
+            .graph_gen_communicator
+            .as_ref()
+            .map_or(0, |ggc| ggc.timestamp.load(Ordering::SeqCst));
+        self.send_node_done(node_key, NodeDoneKind::Freed, timestamp);
 
         self.recalculation_required = true;
 
+        let is_feedback_node = self.feedback_node_indices.contains(&node_key);
         // Remove all edges leading to the node
-        self.node_input_edges.remove(node_key);
-        self.graph_input_edges.remove(node_key);
+        if let Some(input_edges) = self.node_input_edges.remove(node_key) {
+            self.num_connections -= self.num_counted_input_edges(&input_edges);
+        }
+        if let Some(graph_input_edges) = self.graph_input_edges.remove(node_key) {
+            self.num_connections -= graph_input_edges.len();
+        }
         // feedback from the freed node requires removing the feedback node and all edges from the feedback node
-        self.node_feedback_edges.remove(node_key);
+        if let Some(feedback_edges) = self.node_feedback_edges.remove(node_key) {
+            self.num_connections -= feedback_edges.len();
+        }
         // Remove all edges leading from the node to other nodes
         for (_k, input_edges) in &mut self.node_input_edges {
             let mut i = 0;
             while i < input_edges.len() {
                 if input_edges[i].source == node_key {
                     input_edges.remove(i);
+                    if !is_feedback_node {
+                        self.num_connections -= 1;
+                    }
                 } else {
                     i += 1;
                 }
             }
         }
+        // Remove all ordering constraints involving the node
+        self.node_order_constraints.remove(node_key);
+        for (_k, constraints) in &mut self.node_order_constraints {
+            constraints.retain(|&key| key != node_key);
+        }
         // Remove all edges leading from the node to the Graph output
         {
             let mut i = 0;
             while i < self.output_edges.len() {
                 if self.output_edges[i].source == node_key {
                     self.output_edges.remove(i);
+                    self.num_connections -= 1;
                 } else {
                     i += 1;
                 }
@@ -1861,18 +2549,48 @@ impl Graph {
             if let Some(clock_update) = &clock_update {
                 ggc.send_clock_update(clock_update.clone()); // Make sure all the clocks in the GraphGens are in sync.
             }
+            let clock = clock_update
+                .as_ref()
+                .and_then(|clock_update| clock_update.external_clock.clone())
+                .map(|clock| SchedulerClock {
+                    total_jump: clock.total_jump.load(Ordering::SeqCst),
+                    clock,
+                });
             ggc.scheduler.start(
                 self.sample_rate * (self.oversampling.as_usize() as Sample),
                 self.block_size * self.oversampling.as_usize(),
                 latency,
                 start_ts,
                 musical_time_map.clone(),
+                clock,
             );
         }
         for (_key, graph) in &mut self.graphs_per_node {
             graph.start_scheduler(latency, start_ts, clock_update, musical_time_map);
         }
     }
+    /// Returns the scheduling latency the graph was started with, or None if
+    /// the graph has not been started yet.
+    pub fn scheduling_latency(&self) -> Option<Duration> {
+        if let Some(ggc) = &self.graph_gen_communicator {
+            if let Scheduler::Running {
+                latency_in_samples,
+                sample_rate,
+                ..
+            } = &ggc.scheduler
+            {
+                return Some(Duration::from_secs_f64(
+                    *latency_in_samples / (*sample_rate as f64),
+                ));
+            }
+        }
+        None
+    }
+    /// Returns the shortest scheduling latency that reliably lets changes be
+    /// applied at the requested time. See [`minimum_scheduling_latency`].
+    pub fn minimum_scheduling_latency(&self) -> Duration {
+        minimum_scheduling_latency(self.block_size, self.sample_rate)
+    }
     /// Returns the current audio thread time in Beats based on the
     /// MusicalTimeMap, or None if it is not available (e.g. if the Graph has
     /// not been started yet).
@@ -1889,110 +2607,167 @@ impl Graph {
     /// Generate inspection metadata for this graph and all sub graphs. Can be
     /// used to generate static or dynamic inspection and manipulation tools.
     pub fn generate_inspection(&self) -> GraphInspection {
+        let mut inspection = GraphInspection::empty();
+        self.generate_inspection_into(&mut inspection);
+        inspection
+    }
+    /// Like [`Graph::generate_inspection`], but reuses the buffers of an
+    /// existing [`GraphInspection`] to minimise allocations.
+    pub fn generate_inspection_into(&self, inspection: &mut GraphInspection) {
+        let mut inspector =
+            GraphInspector::new(std::mem::replace(inspection, GraphInspection::empty()));
+        while !self.inspect_chunk(&mut inspector, usize::MAX) {}
+        *inspection = inspector.into_inspection();
+    }
+    /// Inspect at most `max_nodes` more nodes of this graph, starting a new
+    /// inspection if `inspector` isn't in the middle of one. Returns true
+    /// when the inspection is finished and available through
+    /// [`GraphInspector::inspection`]. Inner graphs are inspected in full
+    /// together with the node they belong to.
+    ///
+    /// This allows a [`GraphInspection`] of a graph with a very large number
+    /// of nodes to be generated over time without stalling e.g. the
+    /// processing of other changes to the graph.
+    pub fn inspect_chunk(&self, inspector: &mut GraphInspector, max_nodes: usize) -> bool {
         let real_nodes = self.get_nodes();
-        // Maps a node key to the index in the Vec
-        let mut node_key_processed = Vec::with_capacity(real_nodes.len());
-        let mut nodes = Vec::with_capacity(real_nodes.len());
-        for (node_key, node) in real_nodes {
-            let graph_inspection = self
-                .graphs_per_node
-                .get(node_key)
-                .map(|graph| graph.generate_inspection());
-
-            nodes.push(NodeInspection {
-                name: node.name.to_string(),
-                address: self.node_ids
-                    .get(node_key)
-                    .expect("All nodes should have their ids stored.")
-                    .clone(),
-                input_channels: self.node_input_index_to_name
-                    .get(node_key)
-                    .expect(
-                        "All nodes should have a list of input channel names made when pushed to the graph."
-                    )
-                    .iter()
-                    .map(|&s| s.to_string())
-                    .collect(),
-                output_channels: self.node_output_index_to_name
+        if !inspector.in_progress || inspector.graph_id != self.id {
+            // Take a snapshot of which nodes should be included
+            inspector.keys.clear();
+            inspector.removed.clear();
+            inspector.indices.clear();
+            for (index, node_key) in real_nodes.keys().enumerate() {
+                let address = *self
+                    .node_ids
                     .get(node_key)
-                    .expect(
-                        "All nodes should have a list of output channel names made when pushed to the graph."
-                    )
-                    .iter()
-                    .map(|&s| s.to_string())
-                    .collect(),
-                // Leave empty for now, fill later
-                input_edges: vec![],
-                graph_inspection,
-            });
-            node_key_processed.push(node_key);
+                    .expect("All nodes should have their ids stored.");
+                inspector.keys.push((node_key, address));
+                inspector.indices.insert(node_key, index);
+            }
+            inspector.inspection.nodes.truncate(inspector.keys.len());
+            inspector.next = 0;
+            inspector.in_progress = true;
+            inspector.graph_id = self.id;
         }
-        // Convert edges to the inspection native format of indices to the list of nodes
-        for (node_key, _node) in real_nodes {
-            let mut input_edges = Vec::new();
+        let end = inspector
+            .next
+            .saturating_add(max_nodes)
+            .min(inspector.keys.len());
+        for index in inspector.next..end {
+            let (node_key, address) = inspector.keys[index];
+            if index == inspector.inspection.nodes.len() {
+                inspector.inspection.nodes.push(NodeInspection {
+                    name: String::new(),
+                    address,
+                    input_channels: vec![],
+                    output_channels: vec![],
+                    input_edges: vec![],
+                    graph_inspection: None,
+                });
+            }
+            let node_inspection = &mut inspector.inspection.nodes[index];
+            node_inspection.address = address;
+            let Some(node) = real_nodes.get(node_key) else {
+                // The node has been freed since the inspection started. Keep
+                // its index so that the indices of the other nodes stay valid.
+                node_inspection.name.clear();
+                node_inspection.input_channels.clear();
+                node_inspection.output_channels.clear();
+                node_inspection.input_edges.clear();
+                node_inspection.graph_inspection = None;
+                inspector.removed.push(index);
+                continue;
+            };
+            node_inspection.name.clear();
+            node_inspection.name.push_str(node.name);
+            copy_channel_names(
+                &mut node_inspection.input_channels,
+                self.node_input_index_to_name.get(node_key).expect(
+                    "All nodes should have a list of input channel names made when pushed to the graph.",
+                ),
+            );
+            copy_channel_names(
+                &mut node_inspection.output_channels,
+                self.node_output_index_to_name.get(node_key).expect(
+                    "All nodes should have a list of output channel names made when pushed to the graph.",
+                ),
+            );
+            // Convert edges to the inspection native format of indices to the
+            // list of nodes. Edges from nodes pushed after the inspection
+            // started are left out.
+            node_inspection.input_edges.clear();
             if let Some(edges) = self.node_input_edges.get(node_key) {
                 for edge in edges {
-                    let index = node_key_processed
-                        .iter()
-                        .position(|&k| k == edge.source)
-                        .unwrap();
-                    input_edges.push(EdgeInspection {
-                        source: EdgeSource::Node(index),
-                        from_index: edge.from_output_index,
-                        to_index: edge.to_input_index,
-                    });
+                    if let Some(&source_index) = inspector.indices.get(edge.source) {
+                        node_inspection.input_edges.push(EdgeInspection {
+                            source: EdgeSource::Node(source_index),
+                            from_index: edge.from_output_index,
+                            to_index: edge.to_input_index,
+                        });
+                    }
                 }
             }
             if let Some(edges) = self.graph_input_edges.get(node_key) {
                 for edge in edges {
-                    input_edges.push(EdgeInspection {
+                    node_inspection.input_edges.push(EdgeInspection {
                         source: EdgeSource::Graph,
                         from_index: edge.from_output_index,
                         to_index: edge.to_input_index,
                     });
                 }
             }
-            if let Some(index) = node_key_processed.iter().position(|&k| k == node_key) {
-                nodes[index].input_edges = input_edges;
+            match self.graphs_per_node.get(node_key) {
+                Some(graph) => graph.generate_inspection_into(
+                    node_inspection
+                        .graph_inspection
+                        .get_or_insert_with(GraphInspection::empty),
+                ),
+                None => node_inspection.graph_inspection = None,
             }
         }
-        let mut graph_output_input_edges = Vec::new();
+        inspector.next = end;
+        if inspector.next < inspector.keys.len() {
+            return false;
+        }
+        // All nodes have been inspected, fill in the graph wide data
+        let indices = &inspector.indices;
+        let inspection = &mut inspector.inspection;
+        inspection.graph_output_input_edges.clear();
         for edge in &self.output_edges {
-            if let Some(index) = node_key_processed.iter().position(|&k| k == edge.source) {
-                graph_output_input_edges.push(EdgeInspection {
+            if let Some(&index) = indices.get(edge.source) {
+                inspection.graph_output_input_edges.push(EdgeInspection {
                     source: EdgeSource::Node(index),
                     from_index: edge.from_output_index,
                     to_index: edge.to_input_index,
                 });
             }
         }
-        let unconnected_nodes = self
-            .disconnected_nodes
-            .iter()
-            .filter_map(|&disconnected_key| {
-                node_key_processed
-                    .iter()
-                    .position(|&key| key == disconnected_key)
-            })
-            .collect();
+        inspection.unconnected_nodes.clear();
+        inspection.unconnected_nodes.extend(
+            self.disconnected_nodes
+                .iter()
+                .filter_map(|&disconnected_key| indices.get(disconnected_key).copied()),
+        );
         // Note: keys from `node_keys_to_free_when_safe` very rarely cannot be found in the `node_key_processed`. Why?
-        let nodes_pending_removal = self
-            .node_keys_to_free_when_safe
-            .iter()
-            .filter_map(|&(freed_key, _)| {
-                node_key_processed.iter().position(|&key| key == freed_key)
-            })
-            .collect();
-
-        GraphInspection {
-            nodes,
-            unconnected_nodes,
-            nodes_pending_removal,
-            graph_output_input_edges,
-            num_inputs: self.num_inputs,
-            num_outputs: self.num_outputs,
-            graph_id: self.id,
-        }
+        inspection.nodes_pending_removal.clear();
+        inspection.nodes_pending_removal.extend(
+            self.node_keys_to_free_when_safe
+                .iter()
+                .filter_map(|&(freed_key, _)| indices.get(freed_key).copied()),
+        );
+        inspection
+            .nodes_pending_removal
+            .extend_from_slice(&inspector.removed);
+        inspection.node_order.clear();
+        inspection.node_order.extend(
+            self.node_order
+                .iter()
+                .filter_map(|&ordered_key| indices.get(ordered_key).copied()),
+        );
+        inspection.num_inputs = self.num_inputs;
+        inspection.num_outputs = self.num_outputs;
+        inspection.graph_id = self.id;
+        inspector.in_progress = false;
+        true
     }
 
     /// Schedule changes to input channel constants. The changes will only be
@@ -2004,6 +2779,16 @@ impl Graph {
         &mut self,
         node_changes: Vec<NodeChanges>,
         time: Time,
+    ) -> Result<(), ScheduleError> {
+        self.schedule_changes_with_latency(node_changes, time, None)
+    }
+    /// Like [`Graph::schedule_changes`], but `latency` replaces the scheduling
+    /// latency the graph was started with if it is `Some`.
+    pub fn schedule_changes_with_latency(
+        &mut self,
+        node_changes: Vec<NodeChanges>,
+        time: Time,
+        latency: Option<Duration>,
     ) -> Result<(), ScheduleError> {
         // assert all changes are for the same graph
         if node_changes.is_empty() {
@@ -2053,10 +2838,13 @@ impl Graph {
                         }
 
                         let change_kind = match change {
-                            Change::Constant(value) => ScheduledChangeKind::Constant {
-                                index,
-                                value: *value,
-                            },
+                            Change::Constant(value) => {
+                                self.node_input_constants[key][index] = *value;
+                                ScheduledChangeKind::Constant {
+                                    index,
+                                    value: *value,
+                                }
+                            }
                             Change::Trigger => ScheduledChangeKind::Trigger { index },
                         };
                         scheduler_changes.push((key, change_kind, time_offset));
@@ -2069,7 +2857,11 @@ impl Graph {
                 // Try to find the graph containing the node by asking all the graphs in this graph to free the node
                 let mut found_graph = false;
                 for (_key, graph) in &mut self.graphs_per_node {
-                    match graph.schedule_changes(vec![node_changes.clone()], time) {
+                    match graph.schedule_changes_with_latency(
+                        vec![node_changes.clone()],
+                        time,
+                        latency,
+                    ) {
                         Ok(_) => {
                             found_graph = true;
                             break;
@@ -2088,9 +2880,11 @@ impl Graph {
             }
         }
         if let Some(ggc) = &mut self.graph_gen_communicator {
-            ggc.scheduler.schedule(scheduler_changes, time);
+            ggc.scheduler
+                .schedule_with_latency(scheduler_changes, time, latency);
         } else {
-            self.scheduled_changes_queue.push((scheduler_changes, time));
+            self.scheduled_changes_queue
+                .push((scheduler_changes, time, latency));
         }
         Ok(())
     }
@@ -2126,15 +2920,21 @@ impl Graph {
                     });
                 }
                 let change_kind = match change.value {
-                    Change::Constant(c) => ScheduledChangeKind::Constant { index, value: c },
+                    Change::Constant(c) => {
+                        self.node_input_constants[key][index] = c;
+                        ScheduledChangeKind::Constant { index, value: c }
+                    }
                     Change::Trigger => ScheduledChangeKind::Trigger { index },
                 };
                 if let Some(ggc) = &mut self.graph_gen_communicator {
                     ggc.scheduler
                         .schedule(vec![(key, change_kind, None)], change.time);
                 } else {
-                    self.scheduled_changes_queue
-                        .push((vec![(key, change_kind, None)], change.time));
+                    self.scheduled_changes_queue.push((
+                        vec![(key, change_kind, None)],
+                        change.time,
+                        None,
+                    ));
                 }
             }
         }
@@ -2157,6 +2957,49 @@ impl Graph {
         }
         Ok(())
     }
+    /// Returns the input constants of a node in this graph or any graph
+    /// inside it, or None if the node can't be found or is being freed.
+    ///
+    /// The values are the latest ones set through connections to constants
+    /// and scheduled changes, including changes scheduled for later than now.
+    /// Inputs which are connected to other nodes still have a constant which
+    /// is added to the input, except that a non-zero default is set to 0
+    /// when a node is connected to the input.
+    pub fn input_constants(&self, node: NodeId) -> Option<Vec<InputConstant>> {
+        if node.graph_id() == self.id {
+            let key = Self::key_from_id(&self.node_ids, node)?;
+            if !self.get_nodes().contains_key(key) || self.node_keys_pending_removal.contains(&key)
+            {
+                return None;
+            }
+            let constants = self.node_input_index_to_name[key]
+                .iter()
+                .zip(&self.node_input_constants[key])
+                .zip(&self.node_input_defaults[key])
+                .map(|((&name, &value), &default)| InputConstant {
+                    name,
+                    value,
+                    default,
+                })
+                .collect();
+            return Some(constants);
+        }
+        self.graphs_per_node
+            .values()
+            .find_map(|graph| graph.input_constants(node))
+    }
+    /// Schedule the input constant to be set back to its default value as
+    /// soon as possible, see [`Gen::input_default`].
+    pub fn reset_input(&mut self, input: NodeInput) -> Result<(), ScheduleError> {
+        let constants = self.input_constants(input.node).unwrap_or_default();
+        let default = match input.channel {
+            NodeChannel::Label(label) => constants.iter().find(|constant| constant.name == label),
+            NodeChannel::Index(index) => constants.get(index),
+        }
+        .map_or(0.0, |constant| constant.default);
+        // A node or input which doesn't exist is reported by `schedule_change`
+        self.schedule_change(ParameterChange::now(input, default))
+    }
     /// Disconnect the given connection if it exists. Will return Ok if the Connection doesn't exist, but the data inside it is correct and the graph could be found.
     ///
     /// Disconnecting a constant means setting that constant input to 0. Disconnecting a feedback edge will remove the feedback node under the hood if there are no remaining edges to it. Disconnecting a Connection::Clear will do the same thing as "connecting" it: clear edges according to its parameters.
@@ -2289,6 +3132,7 @@ impl Graph {
                             && edge_list[i].to_input_index < to_index + channels
                         {
                             edge_list.remove(i);
+                            self.num_connections -= 1;
                         } else {
                             i += 1;
                         }
@@ -2306,6 +3150,7 @@ impl Graph {
                             && feedback_edge_list[i].to_input_index < from_index + channels
                         {
                             feedback_edge_list.remove(i);
+                            self.num_connections -= 1;
                         } else {
                             i += 1;
                         }
@@ -2372,6 +3217,9 @@ impl Graph {
                     } else {
                         0
                     };
+                    if let Some(constant) = self.node_input_constants[sink_key].get_mut(input) {
+                        *constant = 0.0;
+                    }
                     if let Some(ggc) = &mut self.graph_gen_communicator {
                         ggc.scheduler.schedule(
                             vec![(
@@ -2446,6 +3294,7 @@ impl Graph {
                         && edge_list[i].to_input_index < to_index + channels
                     {
                         edge_list.remove(i);
+                        self.num_connections -= 1;
                     } else {
                         i += 1;
                     }
@@ -2504,6 +3353,7 @@ impl Graph {
                         && edge_list[i].to_input_index < to_index + channels
                     {
                         edge_list.remove(i);
+                        self.num_connections -= 1;
                     } else {
                         i += 1;
                     }
@@ -2530,6 +3380,7 @@ impl Graph {
                         && edge.to_input_index < (to_output_channel + channels)
                     {
                         self.graph_input_to_output_edges.remove(i);
+                        self.num_connections -= 1;
                         self.recalculation_required = true;
                     }
                 }
@@ -2555,6 +3406,7 @@ impl Graph {
                         && edge.to_input_index < (to_output_channel + channels)
                     {
                         self.graph_input_to_output_edges.remove(i);
+                        self.num_connections -= 1;
                         self.recalculation_required = true;
                     }
                 }
@@ -2693,6 +3545,7 @@ impl Graph {
                     .unwrap()
                     .len();
                 let num_sink_inputs = self.node_input_index_to_name.get(sink_key).unwrap().len();
+                self.check_connection_quota(channels, &connection)?;
                 if !feedback {
                     let edge_list = &mut self.node_input_edges[sink_key];
                     for i in 0..channels {
@@ -2710,6 +3563,16 @@ impl Graph {
                         if let Some(&index) = self.node_feedback_node_key.get(source_key) {
                             index
                         } else {
+                            // The feedback node counts towards the node quota
+                            if let Some(max_nodes) = self.max_nodes {
+                                if self.num_active_nodes() >= max_nodes {
+                                    return Err(ConnectionError::NodeQuotaExceeded {
+                                        connection: Box::new(connection.clone()),
+                                        graph_id: self.id,
+                                        max_nodes,
+                                    });
+                                }
+                            }
                             let feedback_node = FeedbackGen::node(num_source_outputs);
                             let mut feedback_node_address = NodeId::new(self.id);
                             let key = self.push_node(feedback_node, &mut feedback_node_address);
@@ -2738,6 +3601,11 @@ impl Graph {
                         });
                     }
                 }
+                // A feedback connection is counted by its feedback edges
+                self.num_connections += channels;
+                for i in 0..channels {
+                    self.clear_input_default(sink_key, (to_index + i) % num_sink_inputs);
+                }
 
                 self.recalculation_required = true;
             }
@@ -2781,6 +3649,9 @@ impl Graph {
                     } else {
                         0
                     };
+                    if let Some(constant) = self.node_input_constants[sink_key].get_mut(input) {
+                        *constant = value;
+                    }
                     if let Some(ggc) = &mut self.graph_gen_communicator {
                         ggc.scheduler.schedule(
                             vec![(
@@ -2849,6 +3720,7 @@ impl Graph {
                 } else {
                     0
                 };
+                self.check_connection_quota(channels, &connection)?;
                 for i in 0..channels {
                     self.output_edges.push(Edge {
                         source: source_key,
@@ -2856,6 +3728,7 @@ impl Graph {
                         to_input_index: (to_index + i) % self.num_outputs,
                     });
                 }
+                self.num_connections += channels;
 
                 self.recalculation_required = true;
             }
@@ -2907,6 +3780,7 @@ impl Graph {
                 if channels + to_index > self.node_input_index_to_name[sink_key].len() {
                     return Err(ConnectionError::DestinationChannelOutOfBounds);
                 }
+                self.check_connection_quota(channels, &connection)?;
                 for i in 0..channels {
                     self.graph_input_edges[sink_key].push(Edge {
                         source: sink_key,
@@ -2914,6 +3788,7 @@ impl Graph {
                         to_input_index: to_index + i,
                     });
                 }
+                self.num_connections += channels;
 
                 self.recalculation_required = true;
             }
@@ -2975,6 +3850,7 @@ impl Graph {
                                                 == input_edge.from_output_index
                                         {
                                             feedback_edges.remove(i);
+                                            self.num_connections -= 1;
                                         } else {
                                             i += 1;
                                         }
@@ -2989,7 +3865,10 @@ impl Graph {
                         let mut i = 0;
                         while i > edges.len() {
                             if edges[i].to_input_index == index {
-                                edges.remove(i);
+                                let edge = edges.remove(i);
+                                if !self.feedback_node_indices.contains(&edge.source) {
+                                    self.num_connections -= 1;
+                                }
                             } else {
                                 i += 1;
                             }
@@ -3007,6 +3886,7 @@ impl Graph {
                                             == input_edge.from_output_index
                                     {
                                         feedback_edges.remove(i);
+                                        self.num_connections -= 1;
                                     } else {
                                         i += 1;
                                     }
@@ -3016,6 +3896,8 @@ impl Graph {
                                 }
                             }
                         }
+                        self.num_connections -=
+                            self.num_counted_input_edges(&self.node_input_edges[node_key]);
                         self.node_input_edges[node_key].clear();
                     }
                     for na in nodes_to_free {
@@ -3023,11 +3905,21 @@ impl Graph {
                     }
                 }
                 if graph_inputs {
+                    self.num_connections -= self.graph_input_edges[node_key].len();
                     self.graph_input_edges[node_key].clear();
                 }
                 if input_constants {
                     // Clear input constants by scheduling them all to be set to 0 now
                     let num_node_inputs = self.get_nodes_mut()[node_key].num_inputs();
+                    let constants = &mut self.node_input_constants[node_key];
+                    match channel_index {
+                        Some(index) => {
+                            if let Some(constant) = constants.get_mut(index) {
+                                *constant = 0.0;
+                            }
+                        }
+                        None => constants.fill(0.0),
+                    }
                     if let Some(ggc) = &mut self.graph_gen_communicator {
                         // The GraphGen has been created so we have to be more careful
                         if let Some(index) = channel_index {
@@ -3061,11 +3953,13 @@ impl Graph {
                                 if let Some(index) = channel_index {
                                     if edges[i].from_output_index == index {
                                         edges.remove(i);
+                                        self.num_connections -= 1;
                                     } else {
                                         i += 1;
                                     }
                                 } else {
                                     edges.remove(i);
+                                    self.num_connections -= 1;
                                 }
                             } else {
                                 i += 1;
@@ -3080,11 +3974,13 @@ impl Graph {
                             if let Some(index) = channel_index {
                                 if self.output_edges[i].from_output_index == index {
                                     self.output_edges.remove(i);
+                                    self.num_connections -= 1;
                                 } else {
                                     i += 1;
                                 }
                             } else {
                                 self.output_edges.remove(i);
+                                self.num_connections -= 1;
                             }
                         } else {
                             i += 1;
@@ -3102,12 +3998,14 @@ impl Graph {
                     return try_connect_to_graphs(connection.clone());
                 }
                 // TODO Check for duplicates
+                self.check_connection_quota(channels, &connection)?;
                 for i in 0..channels {
                     self.graph_input_to_output_edges.push(InterGraphEdge {
                         from_output_index: from_input_channel + i,
                         to_input_index: to_output_channel + i,
                     })
                 }
+                self.num_connections += channels;
 
                 self.recalculation_required = true;
             }
@@ -3115,6 +4013,61 @@ impl Graph {
         }
         Ok(())
     }
+    /// Set the input constant to 0 if it is still at a non-zero default, so
+    /// that a node connected to the input replaces the default instead of
+    /// being added to it.
+    fn clear_input_default(&mut self, key: NodeKey, input: usize) {
+        let default = self.node_input_defaults[key][input];
+        let constant = &mut self.node_input_constants[key][input];
+        if default == 0.0 || *constant != default {
+            return;
+        }
+        *constant = 0.0;
+        if let Some(ggc) = &mut self.graph_gen_communicator {
+            ggc.scheduler.schedule(
+                vec![(
+                    key,
+                    ScheduledChangeKind::Constant {
+                        index: input,
+                        value: 0.0,
+                    },
+                    None,
+                )],
+                Time::Immediately,
+            );
+        } else {
+            // No GraphGen exists so we can set the constant directly.
+            self.get_nodes_mut()[key].set_constant(0.0, input);
+        }
+    }
+    /// The number of edges in `edges` which count as connections. Edges from
+    /// feedback nodes don't count since their feedback edges do.
+    fn num_counted_input_edges(&self, edges: &[Edge]) -> usize {
+        edges
+            .iter()
+            .filter(|edge| !self.feedback_node_indices.contains(&edge.source))
+            .count()
+    }
+    /// Returns an error if adding `new_connections` connections to this graph
+    /// would exceed its connection quota.
+    fn check_connection_quota(
+        &self,
+        new_connections: usize,
+        connection: &Connection,
+    ) -> Result<(), ConnectionError> {
+        if let Some(max_connections) = self.max_connections {
+            let num_connections = self.num_connections;
+            if num_connections + new_connections > max_connections {
+                return Err(ConnectionError::ConnectionQuotaExceeded {
+                    connection: Box::new(connection.clone()),
+                    graph_id: self.id,
+                    max_connections,
+                    num_connections,
+                });
+            }
+        }
+        Ok(())
+    }
     fn input_index_from_label(&self, node: NodeKey, label: &'static str) -> Option<usize> {
         if let Some(&index) = self
             .node_input_name_to_index
@@ -3178,6 +4131,19 @@ impl Graph {
                     break;
                 }
             }
+            // Nodes pinned to run before this node are treated like inputs
+            if !found_unvisited {
+                if let Some(constraints) = self.node_order_constraints.get(node_index) {
+                    for &before in constraints {
+                        if !visited.contains(&before) {
+                            nodes_to_process.push(before);
+                            visited.insert(before);
+                            found_unvisited = true;
+                            break;
+                        }
+                    }
+                }
+            }
             if !found_unvisited {
                 node_order.push(nodes_to_process.pop().unwrap());
             }
@@ -3220,9 +4186,11 @@ impl Graph {
         last_connected_output_node_index
     }
     /// Calculate the node order of the graph based on the outputs
-    /// Post-ordered depth first search
+    /// Post-ordered depth first search, then reordered by the
+    /// [`SchedulerStrategy`] of the graph. If the strategy returns an invalid
+    /// order, the depth first order is kept and an error is returned.
     /// NB: Not real-time safe
-    pub fn calculate_node_order(&mut self) {
+    pub fn calculate_node_order(&mut self) -> Result<(), OrderError> {
         self.node_order.clear();
         // Add feedback nodes first, their order doesn't matter
         self.node_order.extend(self.feedback_node_indices.iter());
@@ -3300,8 +4268,19 @@ impl Graph {
                 remaining_nodes.push(node_key);
             }
         }
-        self.node_order.extend(remaining_nodes.iter());
+        // Order the remaining nodes so that connections and ordering
+        // constraints between them are respected.
+        for &node_key in &remaining_nodes {
+            if !visited.contains(&node_key) {
+                nodes_to_process.clear();
+                visited.insert(node_key);
+                nodes_to_process.push(node_key);
+                let stack = self.depth_first_search(&mut visited, &mut nodes_to_process);
+                self.node_order.extend(stack);
+            }
+        }
         self.disconnected_nodes = remaining_nodes;
+        let result = self.apply_scheduler_strategy();
         // debug
         // let nodes = self.get_nodes();
         // for (i, n) in self.node_order.iter().enumerate() {
@@ -3310,6 +4289,79 @@ impl Graph {
         // }
         // dbg!(&self.node_order);
         // dbg!(&self.disconnected_nodes);
+        result
+    }
+    /// Calculate the node order where the error can't be returned, keeping it
+    /// for [`Graph::take_scheduler_error`] instead.
+    fn calculate_node_order_or_store_error(&mut self) {
+        if let Err(e) = self.calculate_node_order() {
+            self.scheduler_error = Some(e);
+        }
+    }
+    /// Returns the error from the last time the [`SchedulerStrategy`] of this
+    /// graph or one of its inner graphs returned an invalid node order while
+    /// the graph was updated, if any, and clears it. The graph falls back to
+    /// depth first order in that case. The [`Controller`](crate::controller::Controller)
+    /// passes these errors to its error handler.
+    pub fn take_scheduler_error(&mut self) -> Option<OrderError> {
+        if let Some(e) = self.scheduler_error.take() {
+            return Some(e);
+        }
+        self.graphs_per_node
+            .values_mut()
+            .find_map(|graph| graph.take_scheduler_error())
+    }
+    /// Reorder the nodes after the feedback nodes, which are in depth first
+    /// order, according to the [`SchedulerStrategy`] of the graph.
+    /// NB: Not real-time safe
+    fn apply_scheduler_strategy(&mut self) -> Result<(), OrderError> {
+        if self.scheduler_strategy.is_depth_first() {
+            return Ok(());
+        }
+        let num_feedback_nodes = self.feedback_node_indices.len();
+        let node_keys = &self.node_order[num_feedback_nodes..];
+        let index_of: HashMap<NodeKey, usize> = node_keys
+            .iter()
+            .enumerate()
+            .map(|(index, &key)| (key, index))
+            .collect();
+        let nodes = self.get_nodes();
+        let names = node_keys.iter().map(|&key| nodes[key].name).collect();
+        let inputs = node_keys
+            .iter()
+            .map(|&key| {
+                let edge_sources = self.node_input_edges[key].iter().map(|edge| edge.source);
+                let constraints = self
+                    .node_order_constraints
+                    .get(key)
+                    .into_iter()
+                    .flatten()
+                    .copied();
+                // Feedback nodes are not part of the topology
+                edge_sources
+                    .chain(constraints)
+                    .filter_map(|source| index_of.get(&source).copied())
+                    .collect()
+            })
+            .collect();
+        let mut graph_outputs = vec![false; node_keys.len()];
+        for edge in &self.output_edges {
+            if let Some(&index) = index_of.get(&edge.source) {
+                graph_outputs[index] = true;
+            }
+        }
+        let topology = GraphTopology::new(names, inputs, graph_outputs);
+        let order = self.scheduler_strategy.order(&topology);
+        if !topology.is_valid_order(&order) {
+            return Err(OrderError::InvalidStrategyOrder {
+                strategy: format!("{:?}", self.scheduler_strategy),
+                graph_id: self.id,
+            });
+        }
+        let reordered: Vec<NodeKey> = order.iter().map(|&index| node_keys[index]).collect();
+        self.node_order.truncate(num_feedback_nodes);
+        self.node_order.extend(reordered);
+        Ok(())
     }
     /// Returns the block size of the [`Graph`], not corrected for oversampling.
     pub fn block_size(&self) -> usize {
@@ -3327,6 +4379,10 @@ impl Graph {
         // Safety: No other thread will access the SlotMap. All we're doing with the buffers is taking pointers; there's no manipulation.
         let nodes = unsafe { &mut *self.nodes.get() };
         let first_sample = self.inputs_buffers_ptr.ptr.cast::<Sample>();
+        let dezipper_samples = self.dezipper.map_or(0, |duration| {
+            (duration.as_secs_f64() * self.sample_rate as f64 * self.oversampling.as_usize() as f64)
+                as usize
+        });
         for &node_key in &self.node_order {
             let num_inputs = nodes[node_key].num_inputs();
             let mut input_buffers = NodeBufferRef::new(
@@ -3392,6 +4448,7 @@ impl Graph {
                 inputs_to_copy,
                 graph_inputs_to_copy,
                 input_buffers,
+                dezipper_samples,
             ));
         }
         tasks
@@ -3439,20 +4496,21 @@ impl Graph {
             applied: Arc::new(AtomicBool::new(false)),
             tasks,
             output_tasks,
-            new_inputs_buffers_ptr: Some(self.inputs_buffers_ptr.clone()),
+            // The GraphGen receives the current inputs buffers directly
+            new_inputs_buffers_ptr: None,
             input_to_output_tasks: self.generate_input_to_output_tasks().into_boxed_slice(),
         };
         // let task_data = Box::into_raw(Box::new(task_data));
         // let task_data_ptr = Arc::new(AtomicPtr::new(task_data));
         let (free_node_queue_producer, free_node_queue_consumer) =
-            RingBuffer::<(NodeKey, GenState)>::new(self.ring_buffer_size);
+            RingBuffer::<(NodeKey, GenState, u64)>::new(self.ring_buffer_size);
         let (new_task_data_producer, new_task_data_consumer) =
             RingBuffer::<TaskData>::new(self.ring_buffer_size);
         let (task_data_to_be_dropped_producer, task_data_to_be_dropped_consumer) =
             RingBuffer::<TaskData>::new(self.ring_buffer_size);
         let mut scheduler = Scheduler::new();
-        for (schedule_changes, time) in self.scheduled_changes_queue.drain(..) {
-            scheduler.schedule(schedule_changes, time);
+        for (schedule_changes, time, latency) in self.scheduled_changes_queue.drain(..) {
+            scheduler.schedule_with_latency(schedule_changes, time, latency);
         }
 
         let scheduler_buffer_size = self.ring_buffer_size;
@@ -3469,6 +4527,7 @@ impl Graph {
             task_data_to_be_dropped_consumer,
             new_task_data_producer,
             next_change_flag: task_data.applied.clone(),
+            task_data_in_flight: 1,
             timestamp: Arc::new(AtomicU64::new(0)),
         };
 
@@ -3496,7 +4555,7 @@ impl Graph {
 
     /// Initialise buffers, return parameters to zero etc.
     fn init(&mut self) {
-        self.calculate_node_order();
+        self.calculate_node_order_or_store_error();
         let block_size = self.block_size;
         let sample_rate = self.sample_rate;
         let oversampling = self.oversampling;
@@ -3514,12 +4573,26 @@ impl Graph {
     }
 
     /// Applies the latest changes to connections and added nodes in the graph on the audio thread and updates the scheduler.
+    ///
+    /// The changes are sent as a complete new list of tasks which the audio
+    /// thread swaps in at the start of a block, returning the old list to be
+    /// dropped here. Nothing is sent while an edit batch is open, see
+    /// [`Graph::start_edit_batch`].
     pub fn commit_changes(&mut self) {
+        if self.edit_batch_depth > 0 {
+            return;
+        }
         if self.graph_gen_communicator.is_some() {
             // We need to run free_old to know if there are nodes to free and hence a recalculation required.
             self.free_old();
-            if self.recalculation_required {
-                self.calculate_node_order();
+            // If too many task lists are waiting to be returned, try again
+            // next update rather than risk the audio thread having to drop one
+            let can_send_tasks = self
+                .graph_gen_communicator
+                .as_mut()
+                .is_some_and(|ggc| ggc.can_send_tasks());
+            if self.recalculation_required && can_send_tasks {
+                self.calculate_node_order_or_store_error();
                 let output_tasks = self.generate_output_tasks().into_boxed_slice();
                 let input_to_output_tasks =
                     self.generate_input_to_output_tasks().into_boxed_slice();
@@ -3537,6 +4610,7 @@ impl Graph {
                         new_inputs_buffers_ptr,
                     );
                 }
+                self.new_inputs_buffers_ptr = false;
                 self.recalculation_required = false;
             }
         }
@@ -3546,14 +4620,57 @@ impl Graph {
     }
 
     /// This function needs to be run regularly to be sure that scheduled changes are carried out.
+    ///
+    /// While an edit batch is open only the new task lists are held back,
+    /// scheduled changes are still sent.
     pub fn update(&mut self) {
         self.commit_changes();
+        self.update_scheduler();
+    }
+    /// Send scheduled changes that are due to the audio thread, for this
+    /// graph and all its inner graphs.
+    fn update_scheduler(&mut self) {
         if let Some(ggc) = &mut self.graph_gen_communicator {
             ggc.update();
         }
         for (_key, graph) in &mut self.graphs_per_node {
-            graph.update();
+            graph.update_scheduler();
+        }
+    }
+
+    /// Start a batch of edits. Until the batch is committed using
+    /// [`Graph::commit_edit_batch`], changes to this graph and its inner graphs
+    /// are held back and the audio thread keeps running the graph as it was.
+    /// The edits of a batch then reach the audio thread together at the start
+    /// of a block, so that it never runs e.g. a node that has been added but
+    /// not yet connected.
+    ///
+    /// Batches can be nested, in which case the edits are sent when the
+    /// outermost batch is committed. Scheduled changes are still sent while a
+    /// batch is open, but a change to a node added in the batch is dropped if
+    /// the batch reaches the audio thread too long after the time the change
+    /// was scheduled for, so keep batches short.
+    ///
+    /// The guarantee holds per graph: every graph swaps in its own new task
+    /// list at the start of one of its blocks. The edits to an inner graph
+    /// and the edits to the graph containing it may therefore be applied a
+    /// block apart, e.g. a new inner graph node may run for a block before an
+    /// edit in the outer graph that depends on it.
+    pub fn start_edit_batch(&mut self) {
+        self.edit_batch_depth += 1;
+    }
+    /// Commit a batch of edits started with [`Graph::start_edit_batch`]. If
+    /// it was the outermost batch, the edits are sent to the audio thread the
+    /// next time the graph is updated.
+    pub fn commit_edit_batch(&mut self) {
+        if self.edit_batch_depth == 0 {
+            eprintln!("Warning: Committing an edit batch that was never started.");
         }
+        self.edit_batch_depth = self.edit_batch_depth.saturating_sub(1);
+    }
+    /// Returns true if an edit batch has been started and not yet committed
+    pub fn is_in_edit_batch(&self) -> bool {
+        self.edit_batch_depth > 0
     }
 
     /// Check if there are any old nodes or other resources that have been
@@ -3566,7 +4683,14 @@ impl Graph {
         } else {
             vec![]
         };
-        for (key, state) in free_queue {
+        for (key, state, timestamp) in free_queue {
+            // Only report nodes that will actually be freed now
+            if self.get_nodes().contains_key(key)
+                && self.node_mortality[key]
+                && !self.node_keys_pending_removal.contains(&key)
+            {
+                self.send_node_done(key, NodeDoneKind::FreedSelf, timestamp);
+            }
             match state {
                 GenState::FreeSelf => {
                     // If the node key cannot be found it was probably freed
@@ -3665,6 +4789,9 @@ struct ScheduledChange {
     /// blocks it has to be removed to make space. This counter counts the blocks
     /// since the change first expired.
     removal_countdown: u8,
+    /// The sum of all transport jumps of the [`ExternalClock`] at the time
+    /// `timestamp` was calculated
+    clock_jump: i64,
 }
 #[derive(Clone, Copy, Debug)]
 enum ScheduledChangeKind {
@@ -3711,6 +4838,7 @@ enum ScheduledChangeKind {
 type SchedulingQueueItem = (
     Vec<(NodeKey, ScheduledChangeKind, Option<TimeOffset>)>,
     Time,
+    Option<Duration>,
 );
 
 /// The Scheduler handles scheduled changes and communicates parameter changes
@@ -3738,6 +4866,8 @@ enum Scheduler {
         scheduling_queue: Vec<ScheduledChange>,
         latency_in_samples: f64,
         musical_time_map: Arc<RwLock<MusicalTimeMap>>,
+        /// Set if the graph may be run by a host providing its own frame position
+        clock: Option<SchedulerClock>,
     },
 }
 
@@ -3754,6 +4884,7 @@ impl Scheduler {
         latency: Duration,
         audio_thread_start_ts: Instant,
         musical_time_map: Arc<RwLock<MusicalTimeMap>>,
+        clock: Option<SchedulerClock>,
     ) {
         match self {
             Scheduler::Stopped {
@@ -3776,31 +4907,48 @@ impl Scheduler {
                     scheduling_queue: vec![],
                     latency_in_samples: latency.as_secs_f64() * (sample_rate as f64),
                     musical_time_map,
+                    clock,
                 };
-                for (changes, time) in scheduling_queue {
-                    new_scheduler.schedule(changes, time);
+                for (changes, time, latency) in scheduling_queue {
+                    new_scheduler.schedule_with_latency(changes, time, latency);
                 }
                 *self = new_scheduler;
             }
             Scheduler::Running { .. } => (),
         }
     }
-    /// Converts a [`Time`] to a number of frames from the start time of the graph
-    fn time_to_frames_timestamp(&mut self, time: Time) -> Option<u64> {
+    /// Converts a [`Time`] to a number of frames from the start time of the
+    /// graph. `latency_override` replaces the latency of the scheduler if set.
+    fn time_to_frames_timestamp(
+        &mut self,
+        time: Time,
+        latency_override: Option<Duration>,
+    ) -> Option<u64> {
         match self {
             Scheduler::Stopped { .. } => None,
             Scheduler::Running {
                 start_ts,
                 sample_rate,
-                latency_in_samples: latency,
+                latency_in_samples,
                 musical_time_map,
+                scheduling_queue,
+                clock,
                 ..
             } => {
+                // When a host provides the frame position, the current
+                // position of the graph is the only reliable "now".
+                let now = clock
+                    .as_mut()
+                    .and_then(|clock| clock.update(scheduling_queue, *sample_rate as Sample))
+                    .unwrap_or_else(|| start_ts.elapsed().as_secs_f64() * (*sample_rate as f64));
+                let latency = match latency_override {
+                    Some(latency) => latency.as_secs_f64() * (*sample_rate as f64),
+                    None => *latency_in_samples,
+                };
                 Some(match time {
                     Time::DurationFromNow(duration_from_now) => {
-                        ((start_ts.elapsed() + duration_from_now).as_secs_f64()
-                            * (*sample_rate as f64)
-                            + *latency) as u64
+                        (now + duration_from_now.as_secs_f64() * (*sample_rate as f64) + latency)
+                            as u64
                     }
                     Time::Seconds(seconds) => seconds.to_samples(*sample_rate),
                     Time::Beats(mt) => {
@@ -3809,7 +4957,7 @@ impl Scheduler {
                         let duration_from_start =
                             Duration::from_secs_f64(mtm.musical_time_to_secs_f64(mt));
                         let timestamp = (duration_from_start.as_secs_f64() * (*sample_rate as f64)
-                            + *latency) as u64;
+                            + latency) as u64;
                         timestamp
                     }
                     Time::Immediately => 0,
@@ -3822,15 +4970,27 @@ impl Scheduler {
         changes: Vec<(NodeKey, ScheduledChangeKind, Option<TimeOffset>)>,
         time: Time,
     ) {
-        let timestamp = self.time_to_frames_timestamp(time);
+        self.schedule_with_latency(changes, time, None)
+    }
+    fn schedule_with_latency(
+        &mut self,
+        changes: Vec<(NodeKey, ScheduledChangeKind, Option<TimeOffset>)>,
+        time: Time,
+        latency: Option<Duration>,
+    ) {
+        let timestamp = self.time_to_frames_timestamp(time, latency);
         match self {
-            Scheduler::Stopped { scheduling_queue } => scheduling_queue.push((changes, time)),
+            Scheduler::Stopped { scheduling_queue } => {
+                scheduling_queue.push((changes, time, latency))
+            }
             Scheduler::Running {
                 sample_rate,
                 max_duration_to_send: _,
                 scheduling_queue,
+                clock,
                 ..
             } => {
+                let clock_jump = clock.as_ref().map_or(0, |clock| clock.total_jump);
                 // timestamp will be Some if the Scheduler is running
                 let timestamp = timestamp.unwrap();
                 let offset_to_frames = |time_offset: Option<TimeOffset>| {
@@ -3867,6 +5027,7 @@ impl Scheduler {
                         key,
                         kind: change_kind,
                         removal_countdown: 0,
+                        clock_jump,
                     });
                 }
             }
@@ -3902,8 +5063,15 @@ impl Scheduler {
             Scheduler::Running {
                 max_duration_to_send,
                 scheduling_queue,
+                sample_rate,
+                clock,
                 ..
             } => {
+                // Keep changes that are not yet sent at the same distance
+                // from the playhead when the host transport jumps.
+                if let Some(clock) = clock {
+                    clock.update(scheduling_queue, *sample_rate as Sample);
+                }
                 // scheduled updates should always be sorted before they are sent, in case there are several changes to the same thing
                 scheduling_queue.sort_unstable_by_key(|s| s.timestamp);
 
@@ -3942,12 +5110,138 @@ struct ClockUpdate {
     /// The sample rate of the graph that the timestamp above comes from. Used
     /// to convert between timestamps in different sample rates.
     clock_sample_rate: Sample,
+    /// The clock of the [`RunGraph`] running the top level graph, shared by all its inner graphs.
+    external_clock: Option<Arc<ExternalClock>>,
+    /// The number of jumps of `external_clock` which are already included in `timestamp`
+    external_clock_jumps: u64,
+}
+
+/// The frame position of the top level graph as provided by a host through
+/// [`RunGraph::process_block_at`]. Shared between the [`RunGraph`] and the
+/// GraphGen and [`Scheduler`] of every graph in the graph tree.
+#[derive(Debug)]
+pub(crate) struct ExternalClock {
+    /// Set when the host starts providing frame positions
+    enabled: AtomicBool,
+    /// Odd while `position` and `total_jump` are being written
+    seq: AtomicU64,
+    /// The frame at the start of the next block to be processed
+    position: AtomicU64,
+    /// The sum of all transport jumps in frames
+    total_jump: AtomicI64,
+    /// The frame at the start of the block after the latest jump
+    jump_frame: AtomicU64,
+    /// Incremented on every transport jump
+    jumps: AtomicU64,
+    /// The sample rate of the top level graph
+    sample_rate: Sample,
+}
+
+impl ExternalClock {
+    pub(crate) fn new(sample_rate: Sample) -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            seq: AtomicU64::new(0),
+            position: AtomicU64::new(0),
+            total_jump: AtomicI64::new(0),
+            jump_frame: AtomicU64::new(0),
+            jumps: AtomicU64::new(0),
+            sample_rate,
+        }
+    }
+    pub(crate) fn enable(&self) {
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+    /// Register a jump of the host transport from `expected_frame` to `frame`
+    pub(crate) fn jump(&self, expected_frame: u64, frame: u64) {
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        self.position.store(frame, Ordering::SeqCst);
+        self.total_jump
+            .fetch_add(frame as i64 - expected_frame as i64, Ordering::SeqCst);
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        self.jump_frame.store(frame, Ordering::SeqCst);
+        self.jumps.fetch_add(1, Ordering::SeqCst);
+    }
+    /// Set the frame at the start of the next block
+    pub(crate) fn set_position(&self, frame: u64) {
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        self.position.store(frame, Ordering::SeqCst);
+        self.seq.fetch_add(1, Ordering::SeqCst);
+    }
+    /// Returns the position and the sum of all jumps, or None if the host
+    /// doesn't provide the position. Never blocks the writer.
+    // `u64::is_multiple_of` needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn position(&self) -> Option<(u64, i64)> {
+        if !self.enabled.load(Ordering::SeqCst) {
+            return None;
+        }
+        loop {
+            let seq = self.seq.load(Ordering::SeqCst);
+            let position = self.position.load(Ordering::SeqCst);
+            let total_jump = self.total_jump.load(Ordering::SeqCst);
+            if seq % 2 == 0 && seq == self.seq.load(Ordering::SeqCst) {
+                return Some((position, total_jump));
+            }
+            std::hint::spin_loop();
+        }
+    }
+    /// Convert a number of frames at the top level sample rate to `sample_rate`
+    fn convert(&self, frames: f64, sample_rate: Sample) -> f64 {
+        if sample_rate == self.sample_rate {
+            frames
+        } else {
+            frames / self.sample_rate as f64 * sample_rate as f64
+        }
+    }
+    /// Move `change` to the timeline after all jumps so far, keeping its
+    /// distance to the playhead
+    fn rebase(&self, change: &mut ScheduledChange, total_jump: i64, sample_rate: Sample) {
+        if change.clock_jump != total_jump {
+            // A timestamp of 0 means as soon as possible
+            if change.timestamp != 0 {
+                let delta =
+                    self.convert((total_jump - change.clock_jump) as f64, sample_rate) as i64;
+                change.timestamp = (change.timestamp as i64 + delta).max(0) as u64;
+            }
+            change.clock_jump = total_jump;
+        }
+    }
+}
+
+/// The [`ExternalClock`] as seen by a [`Scheduler`]
+struct SchedulerClock {
+    clock: Arc<ExternalClock>,
+    /// The value of [`ExternalClock::total_jump`] the scheduling queue was last adjusted to
+    total_jump: i64,
+}
+
+impl SchedulerClock {
+    /// Adjust the queue to any new jumps and return the current position in
+    /// samples at `sample_rate`, if the host provides it.
+    fn update(
+        &mut self,
+        scheduling_queue: &mut [ScheduledChange],
+        sample_rate: Sample,
+    ) -> Option<f64> {
+        let (position, total_jump) = self.clock.position()?;
+        if total_jump != self.total_jump {
+            for change in scheduling_queue.iter_mut() {
+                self.clock.rebase(change, total_jump, sample_rate);
+            }
+            self.total_jump = total_jump;
+        }
+        Some(self.clock.convert(position as f64, sample_rate))
+    }
 }
 
 struct ScheduleReceiver {
     rb_consumer: rtrb::Consumer<ScheduledChange>,
     schedule_queue: Vec<ScheduledChange>,
     clock_update_consumer: rtrb::Consumer<ClockUpdate>,
+    external_clock: Option<Arc<ExternalClock>>,
+    /// The number of transport jumps of the external clock that have been handled
+    external_clock_jumps: u64,
 }
 impl ScheduleReceiver {
     fn new(
@@ -3959,11 +5253,17 @@ impl ScheduleReceiver {
             rb_consumer,
             schedule_queue: Vec::with_capacity(capacity),
             clock_update_consumer,
+            external_clock: None,
+            external_clock_jumps: 0,
         }
     }
     fn clock_update(&mut self, sample_rate: Sample) -> Option<u64> {
         let mut new_timestamp = None;
         while let Ok(clock) = self.clock_update_consumer.pop() {
+            if let Some(external_clock) = clock.external_clock {
+                self.external_clock_jumps = clock.external_clock_jumps;
+                self.external_clock = Some(external_clock);
+            }
             let samples = clock.timestamp.load(Ordering::SeqCst);
             if sample_rate == clock.clock_sample_rate {
                 new_timestamp = Some(samples);
@@ -3976,8 +5276,27 @@ impl ScheduleReceiver {
         }
         new_timestamp
     }
+    /// Returns the new sample counter if the host transport jumped since the
+    /// last call. Queued changes are moved to keep their distance to the playhead.
+    fn transport_jump(&mut self, sample_rate: Sample) -> Option<u64> {
+        let clock = self.external_clock.as_ref()?;
+        let jumps = clock.jumps.load(Ordering::SeqCst);
+        if jumps == self.external_clock_jumps {
+            return None;
+        }
+        self.external_clock_jumps = jumps;
+        let total_jump = clock.total_jump.load(Ordering::SeqCst);
+        for change in &mut self.schedule_queue {
+            clock.rebase(change, total_jump, sample_rate);
+        }
+        let frame = clock.jump_frame.load(Ordering::SeqCst);
+        Some(clock.convert(frame as f64, sample_rate) as u64)
+    }
     /// TODO: Return only a slice of changes that should be applied this block and then remove them all at once.
-    fn changes(&mut self) -> &mut Vec<ScheduledChange> {
+    ///
+    /// Changes are moved to keep their distance to the playhead if the host
+    /// transport has jumped since they were scheduled.
+    fn changes(&mut self, sample_rate: Sample) -> &mut Vec<ScheduledChange> {
         let num_new_changes = self.rb_consumer.slots();
         if num_new_changes > 0 {
             // Only try to read so many changes there is room for in the queue
@@ -3991,6 +5310,12 @@ impl ScheduleReceiver {
                     for change in chunk {
                         self.schedule_queue.push(change);
                     }
+                    if let Some(clock) = &self.external_clock {
+                        let total_jump = clock.total_jump.load(Ordering::SeqCst);
+                        for change in &mut self.schedule_queue {
+                            clock.rebase(change, total_jump, sample_rate);
+                        }
+                    }
 
                     self.schedule_queue.sort_unstable_by_key(|s| s.timestamp);
                 }
@@ -4017,6 +5342,7 @@ struct TaskData {
     output_tasks: Box<[OutputTask]>,
     input_to_output_tasks: Box<[InputToOutputTask]>,
     // if the inputs buffers have been replaced, replace the Arc to them in the GraphGen as well. This avoids the scenario of the buffers being dropped if the Graph is dropped, but the GraphGen is still running.
+    // When the TaskData is returned to be dropped, it holds the Arc to the buffers previously used by the GraphGen instead.
     new_inputs_buffers_ptr: Option<Arc<OwnedRawBuffer>>,
 }
 
@@ -4038,9 +5364,13 @@ struct GraphGenCommunicator {
     /// corresponds to the update when that node was removed from the Tasks
     /// list.
     next_change_flag: Arc<AtomicBool>,
-    free_node_queue_consumer: rtrb::Consumer<(NodeKey, GenState)>,
+    /// Nodes that freed themselves and the timestamp of the block in which they did it
+    free_node_queue_consumer: rtrb::Consumer<(NodeKey, GenState, u64)>,
     task_data_to_be_dropped_consumer: rtrb::Consumer<TaskData>,
     new_task_data_producer: rtrb::Producer<TaskData>,
+    /// The number of TaskData sent to the GraphGen that haven't been returned
+    /// to be dropped yet, including the one the GraphGen is using.
+    task_data_in_flight: usize,
 }
 
 unsafe impl Send for GraphGenCommunicator {}
@@ -4056,10 +5386,20 @@ impl GraphGenCommunicator {
         if let Ok(chunk) = chunk {
             for td in chunk {
                 drop(td);
+                self.task_data_in_flight -= 1;
             }
         }
     }
 
+    /// Returns true if new tasks can be sent while guaranteeing that there
+    /// will be room to return every TaskData in flight, so that the GraphGen
+    /// never has to drop one on the audio thread.
+    fn can_send_tasks(&mut self) -> bool {
+        self.free_old();
+        // All TaskData in flight except the one in use may end up waiting to be dropped
+        self.task_data_in_flight <= self.task_data_to_be_dropped_consumer.buffer().capacity()
+    }
+
     fn send_clock_update(&mut self, clock_update: ClockUpdate) {
         self.clock_update_producer.push(clock_update).unwrap();
     }
@@ -4086,10 +5426,11 @@ impl GraphGenCommunicator {
             new_inputs_buffers_ptr,
             input_to_output_tasks,
         };
-        if let Err(e) = self.new_task_data_producer.push(td) {
-            eprintln!(
+        match self.new_task_data_producer.push(td) {
+            Ok(()) => self.task_data_in_flight += 1,
+            Err(e) => eprintln!(
                 "Unable to push new TaskData to the GraphGen. Please increase RingBuffer size. {e}"
-            )
+            ),
         }
     }
 
@@ -4099,7 +5440,7 @@ impl GraphGenCommunicator {
         self.scheduler
             .update(timestamp, &mut self.scheduled_change_producer);
     }
-    fn get_nodes_to_free(&mut self) -> Vec<(NodeKey, GenState)> {
+    fn get_nodes_to_free(&mut self) -> Vec<(NodeKey, GenState, u64)> {
         let num_items = self.free_node_queue_consumer.slots();
         let chunk = self.free_node_queue_consumer.read_chunk(num_items);
         if let Ok(chunk) = chunk {
@@ -4187,12 +5528,10 @@ struct FeedbackEdge {
 /// use knyst::prelude::*;
 /// use knyst::wavetable::*;
 /// use knyst::graph::RunGraph;
-/// let graph_settings = GraphSettings {
-///     block_size: 64,
-///     sample_rate: 44100.,
-///     num_outputs: 2,
-///     ..Default::default()
-/// };
+/// let graph_settings = GraphSettings::default()
+///     .block_size(64)
+///     .sample_rate(44100.)
+///     .num_outputs(2);
 /// let mut graph = Graph::new(graph_settings);
 /// let resources = Resources::new(ResourcesSettings::default());
 /// let (mut run_graph, _, _) = RunGraph::new(&mut graph, resources, RunGraphSettings::default())?;
@@ -641,6 +641,19 @@ pub enum ConnectionError {
     SinkNodeNotPushed,
     #[error("The connection change required freeing a node, but the node could not be freed.")]
     NodeFree(#[from] FreeError),
+    #[error("The graph {graph_id} has {num_connections} connections and adding the connection would exceed its maximum of {max_connections}. Connection: {connection}")]
+    ConnectionQuotaExceeded {
+        connection: Box<Connection>,
+        graph_id: GraphId,
+        max_connections: usize,
+        num_connections: usize,
+    },
+    #[error("The feedback connection needs a new feedback node, but the graph {graph_id} already contains the maximum of {max_nodes} nodes. Connection: {connection}")]
+    NodeQuotaExceeded {
+        connection: Box<Connection>,
+        graph_id: GraphId,
+        max_nodes: usize,
+    },
 }
 
 /// Describe a node's input or output channel by index or label
@@ -27,7 +27,7 @@ pub(super) fn make_graph_gen(
     num_outputs: usize,
     num_inputs: usize,
     timestamp: Arc<AtomicU64>,
-    free_node_queue_producer: Producer<(NodeKey, GenState)>,
+    free_node_queue_producer: Producer<(NodeKey, GenState, u64)>,
     schedule_receiver: ScheduleReceiver,
     arc_nodes: Arc<UnsafeCell<SlotMap<NodeKey, Node>>>,
     task_data_to_be_dropped_producer: rtrb::Producer<TaskData>,
@@ -527,7 +527,7 @@ pub(super) struct GraphGen {
     sample_counter: u64,
     timestamp: Arc<AtomicU64>,
     schedule_receiver: ScheduleReceiver,
-    free_node_queue_producer: rtrb::Producer<(NodeKey, GenState)>,
+    free_node_queue_producer: rtrb::Producer<(NodeKey, GenState, u64)>,
     task_data_to_be_dropped_producer: rtrb::Producer<TaskData>,
     new_task_data_consumer: rtrb::Consumer<TaskData>,
 }
@@ -548,16 +548,35 @@ impl Gen for GraphGen {
                 {
                     self.sample_counter = new_sample_counter;
                 }
+                if let Some(new_sample_counter) =
+                    self.schedule_receiver.transport_jump(self.sample_rate)
+                {
+                    self.sample_counter = new_sample_counter;
+                }
                 let mut do_empty_buffer = None;
                 let mut do_mend_connections = None;
                 let num_new_task_data = self.new_task_data_consumer.slots();
                 if num_new_task_data > 0 {
                     if let Ok(td_chunk) = self.new_task_data_consumer.read_chunk(num_new_task_data)
                     {
+                        // If several TaskData arrived since the last block,
+                        // they are all swapped in so that every one of them
+                        // is returned, but only the latest one is used.
                         for td in td_chunk {
                             // Setting `applied` to true signals that the new TaskData have been received and old data can be dropped
                             td.applied.store(true, Ordering::SeqCst);
-                            let old_td = std::mem::replace(&mut self.current_task_data, td);
+                            let mut old_td = std::mem::replace(&mut self.current_task_data, td);
+                            if let Some(inputs_buffers_ptr) =
+                                self.current_task_data.new_inputs_buffers_ptr.take()
+                            {
+                                // Return the old buffers with the old
+                                // TaskData so that they are never deallocated
+                                // on the audio thread.
+                                old_td.new_inputs_buffers_ptr = Some(std::mem::replace(
+                                    &mut self._arc_inputs_buffers_ptr,
+                                    inputs_buffers_ptr,
+                                ));
+                            }
                             match self.task_data_to_be_dropped_producer.push(old_td) {
                           Ok(_) => (),
                           Err(e) => eprintln!("RingBuffer for TaskData to be dropped was full. Please increase the size of the RingBuffer. The GraphGen will drop the TaskData here instead. e: {e}"),
@@ -571,16 +590,11 @@ impl Gen for GraphGen {
                     applied: _,
                     tasks,
                     output_tasks,
-                    new_inputs_buffers_ptr,
+                    new_inputs_buffers_ptr: _,
                     input_to_output_tasks,
                 } = task_data;
 
-                if let Some(inputs_buffers_ptr) = new_inputs_buffers_ptr.take() {
-                    // The old buffers will be kept alive until the Arc has been dropped in the GraphGen
-                    self._arc_inputs_buffers_ptr = inputs_buffers_ptr;
-                }
-
-                let changes = self.schedule_receiver.changes();
+                let changes = self.schedule_receiver.changes(self.sample_rate);
 
                 // Run the tasks
                 for task in tasks.iter_mut() {
@@ -624,12 +638,16 @@ impl Gen for GraphGen {
                             // node will still exist, return FreeSelf and get
                             // added to the queue next block.
                             self.free_node_queue_producer
-                                .push((task.node_key, GenState::FreeSelf))
+                                .push((task.node_key, GenState::FreeSelf, self.sample_counter))
                                 .ok();
                         }
                         GenState::FreeSelfMendConnections => {
                             self.free_node_queue_producer
-                                .push((task.node_key, GenState::FreeSelfMendConnections))
+                                .push((
+                                    task.node_key,
+                                    GenState::FreeSelfMendConnections,
+                                    self.sample_counter,
+                                ))
                                 .ok();
                         }
                         GenState::FreeGraph(from_sample_nr) => {
@@ -15,6 +15,8 @@ pub(super) struct Node {
     pub(super) name: &'static str,
     /// index by input_channel
     input_constants: *mut [Sample],
+    /// index by input_channel
+    input_ramps: *mut [ConstantRamp],
     /// index by `output_channel * block_size + sample_index`
     output_buffers: *mut [Sample],
     output_buffers_first_ptr: *mut Sample,
@@ -35,11 +37,18 @@ impl Node {
 
         let output_buffers_first_ptr = std::ptr::null_mut();
 
+        let input_ramps: Vec<ConstantRamp> = (0..gen.num_inputs())
+            .map(|i| ConstantRamp::new(gen.input_dezipper(i)))
+            .collect();
         Node {
             name,
             input_constants: Box::into_raw(
-                vec![0.0 as Sample; gen.num_inputs()].into_boxed_slice(),
+                (0..gen.num_inputs())
+                    .map(|i| gen.input_default(i))
+                    .collect::<Vec<Sample>>()
+                    .into_boxed_slice(),
             ),
+            input_ramps: Box::into_raw(input_ramps.into_boxed_slice()),
             gen: Box::into_raw(gen),
             output_buffers,
             output_buffers_first_ptr,
@@ -57,6 +66,7 @@ impl Node {
         inputs_to_copy: Vec<(*mut Sample, *mut Sample, usize, CopyOrAdd)>,
         graph_inputs_to_copy: Vec<(usize, usize)>,
         input_buffers: NodeBufferRef,
+        dezipper_samples: usize,
     ) -> Task {
         Task {
             node_key,
@@ -64,6 +74,8 @@ impl Node {
             graph_inputs_to_copy,
             input_buffers,
             input_constants: self.input_constants,
+            input_ramps: self.input_ramps,
+            dezipper_samples,
             gen: self.gen,
             output_buffers_first_ptr: self.output_buffers_first_ptr,
             block_size: self.block_size,
@@ -150,6 +162,11 @@ impl Node {
         }
         list
     }
+    pub fn input_defaults(&self) -> Vec<Sample> {
+        (0..self.num_inputs())
+            .map(|i| unsafe { (*self.gen).input_default(i) })
+            .collect()
+    }
     pub(super) fn input_desc(&self, input: usize) -> &'static str {
         unsafe { (*self.gen).input_desc(input) }
     }
@@ -162,6 +179,52 @@ impl Drop for Node {
     fn drop(&mut self) {
         drop(unsafe { Box::from_raw(self.gen) });
         drop(unsafe { Box::from_raw(self.input_constants) });
+        drop(unsafe { Box::from_raw(self.input_ramps) });
         drop(unsafe { Box::from_raw(self.output_buffers) });
     }
 }
+
+/// Linear ramp towards a new input constant, used to dezipper constant changes.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct ConstantRamp {
+    /// False if the Gen opted out of dezippering for this input
+    enabled: bool,
+    current: Sample,
+    step: Sample,
+    remaining: usize,
+}
+impl ConstantRamp {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            current: 0.0,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+    /// Start a new ramp from `from` reaching `to` after `num_samples`. Does nothing if dezippering is disabled for the input.
+    #[inline]
+    pub(super) fn start(&mut self, from: Sample, to: Sample, num_samples: usize) {
+        if self.enabled && num_samples > 0 {
+            self.current = from;
+            self.step = (to - from) / num_samples as Sample;
+            self.remaining = num_samples;
+        }
+    }
+    #[inline]
+    pub(super) fn is_running(&self) -> bool {
+        self.remaining > 0
+    }
+    /// Returns the next value of the ramp, or `target` if the ramp is done.
+    #[inline]
+    pub(super) fn next(&mut self, target: Sample) -> Sample {
+        if self.remaining > 1 {
+            self.remaining -= 1;
+            self.current += self.step;
+            self.current
+        } else {
+            self.remaining = 0;
+            target
+        }
+    }
+}
@@ -2,9 +2,19 @@
 //! provides access to its outputs. Used internally by implementations of
 //! `AudioBackend`, but it can also be used directly for custom environments or
 //! for offline processing.
+//!
+//! When running inside another audio engine or a plugin host, the host can
+//! provide the frame position of every block through
+//! [`RunGraph::process_block_at`] instead of the graph keeping its own count.
+//!
+//! Live inputs can be monitored without any added latency through an
+//! [`InputMonitor`], see [`RunGraph::input_monitor`].
 
 use std::{
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
     time::{Duration, Instant},
 };
 
@@ -19,7 +29,7 @@ use rtrb::RingBuffer;
 
 use crate::{scheduling::MusicalTimeMap, Resources};
 
-use super::{node::Node, Graph, NodeBufferRef, NodeId, Sample};
+use super::{node::Node, ClockUpdate, ExternalClock, Graph, NodeBufferRef, NodeId, Sample};
 
 /// Wrapper around a [`Graph`] `Node` with convenience methods to run the
 /// Graph, either from an audio thread or for non-real time purposes.
@@ -33,6 +43,20 @@ pub struct RunGraph {
     output_node_buffer_ref: NodeBufferRef,
     resources_command_receiver: rtrb::Consumer<ResourcesCommand>,
     resources_response_sender: rtrb::Producer<ResourcesResponse>,
+    external_clock: Arc<ExternalClock>,
+    /// The frame position expected by [`RunGraph::process_block_at`] for the next block
+    next_frame: u64,
+    input_monitor: InputMonitor,
+    /// The (graph output, direct input) gains of the end of the previous block
+    input_monitor_gains: (Sample, Sample),
+    shutdown: Option<ShutdownFade>,
+}
+
+/// The state of a shutdown requested through [`ResourcesCommand::Shutdown`]
+struct ShutdownFade {
+    fade_frames: usize,
+    frames_faded: usize,
+    resources_returned: bool,
 }
 
 impl RunGraph {
@@ -84,11 +108,20 @@ impl RunGraph {
                 // raw pointer.
                 let output_node_buffer_ref = graph_node.output_buffers();
 
+                let input_monitor =
+                    InputMonitor::new(graph_node.num_inputs(), graph_node.num_outputs());
                 let scheduler_start_time_stamp = Instant::now();
+                let external_clock = Arc::new(ExternalClock::new(graph_sample_rate));
+                let clock_update = ClockUpdate {
+                    timestamp: Arc::new(AtomicU64::new(0)),
+                    clock_sample_rate: graph_sample_rate,
+                    external_clock: Some(external_clock.clone()),
+                    external_clock_jumps: 0,
+                };
                 graph.start_scheduler(
                     settings.scheduling_latency,
                     scheduler_start_time_stamp,
-                    &None,
+                    &Some(clock_update),
                     &musical_time_map,
                 );
                 // Run a first update to make sure any queued changes get sent to the GraphGen
@@ -107,6 +140,11 @@ impl RunGraph {
                         output_node_buffer_ref,
                         resources_command_receiver,
                         resources_response_sender,
+                        external_clock,
+                        next_frame: 0,
+                        input_monitor,
+                        input_monitor_gains: (1.0, 0.0),
+                        shutdown: None,
                     },
                     resources_command_sender,
                     resources_response_receiver,
@@ -128,7 +166,17 @@ impl RunGraph {
     pub fn run_resources_communication(&mut self, max_commands_to_process: usize) {
         let mut i = 0;
         while let Ok(command) = self.resources_command_receiver.pop() {
-            if let Err(e) = self
+            if let ResourcesCommand::Shutdown { fade_out } = command {
+                if self.shutdown.is_none() {
+                    self.shutdown = Some(ShutdownFade {
+                        fade_frames: ((fade_out.as_secs_f64() * self.graph_sample_rate as f64)
+                            .round() as usize)
+                            .max(1),
+                        frames_faded: 0,
+                        resources_returned: false,
+                    });
+                }
+            } else if let Err(e) = self
                 .resources_response_sender
                 .push(self.resources.apply_command(command))
             {
@@ -141,16 +189,143 @@ impl RunGraph {
                 break;
             }
         }
+        self.return_resources_after_shutdown();
+    }
+    /// Once the output has faded out after a [`ResourcesCommand::Shutdown`],
+    /// send all buffers and wavetables back as long as there is space in the
+    /// ring buffer, continuing the next time if there isn't.
+    fn return_resources_after_shutdown(&mut self) {
+        let Some(shutdown) = &mut self.shutdown else {
+            return;
+        };
+        if shutdown.resources_returned || shutdown.frames_faded < shutdown.fade_frames {
+            return;
+        }
+        while self.resources_response_sender.slots() > 0 {
+            let response = if let Some((id, buffer)) = self.resources.take_any_buffer() {
+                ResourcesResponse::ReturnBuffer(id, buffer)
+            } else if let Some((id, wavetable)) = self.resources.take_any_wavetable() {
+                ResourcesResponse::ReturnWavetable(id, wavetable)
+            } else {
+                shutdown.resources_returned = true;
+                ResourcesResponse::ShutdownComplete
+            };
+            // There is at least one free slot so this cannot fail
+            self.resources_response_sender.push(response).ok();
+            if shutdown.resources_returned {
+                break;
+            }
+        }
     }
     /// Run the Graph for one block using the inputs currently stored in the
     /// input buffer. The results can be accessed through the output buffer
     /// through [`RunGraph::graph_output_buffers`].
+    ///
+    /// If the [`InputMonitor`] is enabled, the inputs are mixed into the
+    /// outputs after the graph has been run.
     pub fn process_block(&mut self) {
         self.graph_node.process(
             &self.input_node_buffer_ref,
             self.graph_sample_rate,
             &mut self.resources,
         );
+        self.apply_input_monitor();
+        self.apply_shutdown_fade();
+    }
+    fn apply_shutdown_fade(&mut self) {
+        let Some(shutdown) = &mut self.shutdown else {
+            return;
+        };
+        let block_size = self.output_node_buffer_ref.block_size();
+        let first_frame = shutdown.frames_faded;
+        let fade_frames = shutdown.fade_frames;
+        for output in 0..self.output_node_buffer_ref.channels() {
+            // Safety: The channel index is within the number of outputs of the graph
+            let out_buffer = unsafe { self.output_node_buffer_ref.get_channel_mut(output) };
+            for (i, out) in out_buffer.iter_mut().enumerate() {
+                let frame = (first_frame + i + 1).min(fade_frames);
+                *out *= 1.0 - frame as Sample / fade_frames as Sample;
+            }
+        }
+        shutdown.frames_faded = (first_frame + block_size).min(fade_frames);
+    }
+    /// Returns a handle for controlling the monitoring of the inputs
+    /// directly on the outputs.
+    ///
+    /// A performer playing through the graph, e.g. a singer through a reverb
+    /// or a guitarist through an amp simulation, hears themselves with the
+    /// latency of all the processing in the graph. Monitoring mixes the
+    /// inputs of the graph into the outputs directly in the audio callback,
+    /// after the graph has been run for the same block, so the monitored
+    /// signal has no latency in addition to that of the audio driver. The
+    /// graph keeps receiving the same inputs and processes the effected
+    /// signal as usual.
+    ///
+    /// The [`InputMonitor`] can be cloned and sent to other threads. Changes
+    /// are applied at the next block, ramped over the block to avoid clicks.
+    pub fn input_monitor(&self) -> InputMonitor {
+        self.input_monitor.clone()
+    }
+    fn apply_input_monitor(&mut self) {
+        let monitor = &self.input_monitor.inner;
+        let target_gains = if monitor.enabled.load(Ordering::Relaxed) {
+            let mix = Sample::from_bits(monitor.mix.load(Ordering::Relaxed));
+            let level = Sample::from_bits(monitor.level.load(Ordering::Relaxed));
+            (1.0 - mix, mix * level)
+        } else {
+            (1.0, 0.0)
+        };
+        let (last_wet, last_dry) = self.input_monitor_gains;
+        self.input_monitor_gains = target_gains;
+        if (last_wet, last_dry) == (1.0, 0.0) && target_gains == (1.0, 0.0) {
+            return;
+        }
+        let (wet, dry) = target_gains;
+        let block_size = self.block_size();
+        let num_inputs = monitor.num_inputs;
+        let ramp_step = 1.0 / block_size as Sample;
+        for output in 0..monitor.num_outputs {
+            // Safety: The channel index is within the number of outputs of the graph
+            let out_buffer = unsafe { self.output_node_buffer_ref.get_channel_mut(output) };
+            for (i, out) in out_buffer.iter_mut().enumerate() {
+                let t = (i + 1) as Sample * ramp_step;
+                *out *= last_wet + (wet - last_wet) * t;
+            }
+            for input in 0..num_inputs {
+                if !monitor.routes[input * monitor.num_outputs + output].load(Ordering::Relaxed) {
+                    continue;
+                }
+                let in_buffer = self.input_node_buffer_ref.get_channel(input);
+                for (i, (out, input)) in out_buffer.iter_mut().zip(in_buffer).enumerate() {
+                    let t = (i + 1) as Sample * ramp_step;
+                    *out += *input * (last_dry + (dry - last_dry) * t);
+                }
+            }
+        }
+    }
+    /// Like [`RunGraph::process_block`], but `frame` is the position of the
+    /// first sample of the block as provided by a host, e.g. a plugin host or
+    /// another audio engine the graph is embedded in. The position of the
+    /// graph follows the host instead of its own sample count.
+    ///
+    /// If `frame` isn't the frame after the previous block, the host
+    /// transport has jumped. The graph and all its inner graphs then continue
+    /// from the new position and changes that are scheduled but not yet
+    /// applied keep their timing relative to the playhead, i.e. a change that
+    /// was due 10 ms later is applied 10 ms after the jump. Once this has been
+    /// called, [`Time::DurationFromNow`](super::Time) is relative to the
+    /// position provided by the host rather than to the wall clock.
+    ///
+    /// `frame` is at the sample rate of the top level graph. Don't mix calls
+    /// to this method with calls to [`RunGraph::process_block`].
+    pub fn process_block_at(&mut self, frame: u64) {
+        self.external_clock.enable();
+        if frame != self.next_frame {
+            self.external_clock.jump(self.next_frame, frame);
+        }
+        self.next_frame = frame + self.block_size() as u64;
+        self.process_block();
+        self.external_clock.set_position(self.next_frame);
     }
     /// Return a reference to the buffer holding the output of the [`Graph`].
     /// Channels which have no [`Connection`]/graph edge to them will be 0.0.
@@ -186,6 +361,95 @@ impl Drop for RunGraph {
 }
 unsafe impl Send for RunGraph {}
 
+#[derive(Debug)]
+struct InputMonitorState {
+    enabled: AtomicBool,
+    /// [`Sample`] stored as bits
+    mix: AtomicU32,
+    /// [`Sample`] stored as bits
+    level: AtomicU32,
+    /// Indexed by `input * num_outputs + output`
+    routes: Vec<AtomicBool>,
+    num_inputs: usize,
+    num_outputs: usize,
+}
+
+/// Controls the direct monitoring of the inputs of a [`RunGraph`] on its
+/// outputs, see [`RunGraph::input_monitor`].
+///
+/// Monitoring is disabled by default. When enabled, the mix sets the balance
+/// between the output of the graph and the direct inputs: at 0.0 only the
+/// output of the graph is heard, at 1.0 only the dry inputs and at 0.5 both at
+/// half the amplitude. The level scales the direct inputs on top of that.
+///
+/// By default, a mono input is routed to all outputs and otherwise every
+/// input is routed to the output with the same index, if there is one.
+#[derive(Clone, Debug)]
+pub struct InputMonitor {
+    inner: Arc<InputMonitorState>,
+}
+
+impl InputMonitor {
+    fn new(num_inputs: usize, num_outputs: usize) -> Self {
+        let routes = (0..num_inputs)
+            .flat_map(|input| {
+                (0..num_outputs)
+                    .map(move |output| AtomicBool::new(num_inputs == 1 || input == output))
+            })
+            .collect();
+        Self {
+            inner: Arc::new(InputMonitorState {
+                enabled: AtomicBool::new(false),
+                mix: AtomicU32::new((0.5 as Sample).to_bits()),
+                level: AtomicU32::new((1.0 as Sample).to_bits()),
+                routes,
+                num_inputs,
+                num_outputs,
+            }),
+        }
+    }
+    /// Enable or disable monitoring. When disabled the outputs are only the output of the graph.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.enabled.store(enabled, Ordering::Relaxed);
+    }
+    /// Returns true if monitoring is enabled
+    pub fn enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::Relaxed)
+    }
+    /// Set the balance between the output of the graph (0.0) and the direct inputs (1.0). Default: 0.5
+    pub fn set_mix(&self, mix: Sample) {
+        self.inner
+            .mix
+            .store(mix.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+    /// The balance between the output of the graph (0.0) and the direct inputs (1.0)
+    pub fn mix(&self) -> Sample {
+        Sample::from_bits(self.inner.mix.load(Ordering::Relaxed))
+    }
+    /// Set the amplitude of the direct inputs. Default: 1.0
+    pub fn set_level(&self, level: Sample) {
+        self.inner.level.store(level.to_bits(), Ordering::Relaxed);
+    }
+    /// The amplitude of the direct inputs
+    pub fn level(&self) -> Sample {
+        Sample::from_bits(self.inner.level.load(Ordering::Relaxed))
+    }
+    /// Set whether `input` is monitored on `output`.
+    ///
+    /// # Panics
+    /// If `input` or `output` is not a channel of the graph.
+    pub fn set_route(&self, input: usize, output: usize, routed: bool) {
+        assert!(input < self.inner.num_inputs && output < self.inner.num_outputs);
+        self.inner.routes[input * self.inner.num_outputs + output].store(routed, Ordering::Relaxed);
+    }
+    /// Returns true if `input` is monitored on `output`
+    pub fn route(&self, input: usize, output: usize) -> bool {
+        input < self.inner.num_inputs
+            && output < self.inner.num_outputs
+            && self.inner.routes[input * self.inner.num_outputs + output].load(Ordering::Relaxed)
+    }
+}
+
 #[allow(missing_docs)]
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum RunGraphError {
@@ -0,0 +1,252 @@
+//! Strategies for the order in which the nodes of a [`Graph`](super::Graph)
+//! are processed.
+//!
+//! Every time the connections of a graph change, the graph plans the order
+//! in which its nodes are run. Any order in which a node comes after all of
+//! its inputs produces the same output, but different orders suit different
+//! topologies. The strategy is set through
+//! [`GraphSettings::scheduler_strategy`](super::GraphSettings):
+//!
+//! - [`DepthFirst`] (default) processes chains of nodes one after the other,
+//!   so that the output of a node is usually read right after it has been
+//!   written. Suits patches made of many independent voices or effect chains.
+//! - [`Clustered`] processes a node as soon as possible after the node whose
+//!   output it reads most recently, keeping the working set small in patches
+//!   with a lot of fan-out, e.g. one modulator feeding many oscillators.
+//! - [`Partitioned`] is a level ordering: nodes are sorted by the length of
+//!   the longest chain of nodes leading up to them, processing every node as
+//!   early as its inputs allow. This is an order only, not parallel
+//!   processing. Every node is still processed one at a time on the audio
+//!   thread.
+//!
+//! Custom strategies can be made by implementing [`SchedulerStrategy`].
+
+use std::{cmp::Reverse, collections::BinaryHeap, fmt::Debug};
+
+/// The connections between the nodes of a graph, passed to a
+/// [`SchedulerStrategy`] to plan the processing order.
+///
+/// Nodes are referred to by their index. The nodes are listed in the order
+/// of [`DepthFirst`], which is always a valid processing order. Feedback
+/// nodes are left out since they are always processed first.
+#[derive(Clone, Debug, Default)]
+pub struct GraphTopology {
+    names: Vec<&'static str>,
+    inputs: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+    graph_outputs: Vec<bool>,
+}
+
+impl GraphTopology {
+    /// Create a topology from the name of every node, the nodes which have to
+    /// be processed before each node and whether each node is connected to
+    /// the graph outputs. The nodes have to be listed in a valid processing
+    /// order, i.e. every node after all of its inputs.
+    pub fn new(
+        names: Vec<&'static str>,
+        mut inputs: Vec<Vec<usize>>,
+        graph_outputs: Vec<bool>,
+    ) -> Self {
+        let mut dependents = vec![vec![]; names.len()];
+        for (node, node_inputs) in inputs.iter_mut().enumerate() {
+            node_inputs.sort_unstable();
+            node_inputs.dedup();
+            for &input in node_inputs.iter() {
+                dependents[input].push(node);
+            }
+        }
+        Self {
+            names,
+            inputs,
+            dependents,
+            graph_outputs,
+        }
+    }
+    /// The number of nodes
+    pub fn num_nodes(&self) -> usize {
+        self.names.len()
+    }
+    /// The name of the node
+    pub fn name(&self, node: usize) -> &'static str {
+        self.names[node]
+    }
+    /// The nodes which have to be processed before `node`, either because
+    /// `node` reads from them or because they are pinned to run before it.
+    pub fn inputs(&self, node: usize) -> &[usize] {
+        &self.inputs[node]
+    }
+    /// The nodes which have `node` as one of their [`GraphTopology::inputs`]
+    pub fn dependents(&self, node: usize) -> &[usize] {
+        &self.dependents[node]
+    }
+    /// Returns true if the node is connected to an output of the graph
+    pub fn is_graph_output(&self, node: usize) -> bool {
+        self.graph_outputs[node]
+    }
+    /// Returns true if `order` contains every node exactly once and every
+    /// node comes after all of its inputs.
+    pub fn is_valid_order(&self, order: &[usize]) -> bool {
+        if order.len() != self.num_nodes() {
+            return false;
+        }
+        let mut position = vec![usize::MAX; self.num_nodes()];
+        for (i, &node) in order.iter().enumerate() {
+            if node >= self.num_nodes() || position[node] != usize::MAX {
+                return false;
+            }
+            position[node] = i;
+        }
+        (0..self.num_nodes()).all(|node| {
+            self.inputs[node]
+                .iter()
+                .all(|&input| position[input] < position[node])
+        })
+    }
+}
+
+/// Plans the order in which the nodes of a graph are processed, see the
+/// [module documentation](self).
+pub trait SchedulerStrategy: Debug + Send + Sync {
+    /// Return every node index of the `topology` once, each one after all of
+    /// its inputs. If the order isn't valid, the graph falls back to
+    /// [`DepthFirst`] and returns an
+    /// [`OrderError::InvalidStrategyOrder`](super::OrderError::InvalidStrategyOrder).
+    ///
+    /// Called on the thread that changes the graph, never on the audio thread.
+    fn order(&self, topology: &GraphTopology) -> Vec<usize>;
+    /// Return true if [`SchedulerStrategy::order`] always keeps the depth
+    /// first order, so that the graph can skip building the
+    /// [`GraphTopology`]. Default: false
+    fn is_depth_first(&self) -> bool {
+        false
+    }
+}
+
+/// Processes chains of nodes one after the other, starting from the nodes
+/// connected to the graph outputs and going depth first through their inputs.
+/// The default strategy.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DepthFirst;
+
+impl SchedulerStrategy for DepthFirst {
+    fn order(&self, topology: &GraphTopology) -> Vec<usize> {
+        // The topology is already in depth first order
+        (0..topology.num_nodes()).collect()
+    }
+    fn is_depth_first(&self) -> bool {
+        true
+    }
+}
+
+/// Processes a node as soon as possible after the most recently processed of
+/// its inputs, so that its inputs are likely to still be in the cache.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Clustered;
+
+impl SchedulerStrategy for Clustered {
+    fn order(&self, topology: &GraphTopology) -> Vec<usize> {
+        let num_nodes = topology.num_nodes();
+        let mut remaining_inputs: Vec<usize> = (0..num_nodes)
+            .map(|node| topology.inputs(node).len())
+            .collect();
+        // Ready nodes by the position of their last processed input, then by
+        // their depth first position. Nodes without inputs have the lowest
+        // priority so that they are processed when no chain can be continued.
+        let mut ready = BinaryHeap::new();
+        for (node, &remaining) in remaining_inputs.iter().enumerate() {
+            if remaining == 0 {
+                ready.push((0, Reverse(node)));
+            }
+        }
+        let mut order = Vec::with_capacity(num_nodes);
+        while let Some((_, Reverse(node))) = ready.pop() {
+            order.push(node);
+            for &dependent in topology.dependents(node) {
+                remaining_inputs[dependent] -= 1;
+                if remaining_inputs[dependent] == 0 {
+                    ready.push((order.len(), Reverse(dependent)));
+                }
+            }
+        }
+        order
+    }
+}
+
+/// A level ordering which groups the nodes into partitions (levels) of nodes
+/// which don't depend on each other and processes the partitions one after
+/// the other, see [`Partitioned::partitions`]. Nothing is processed in
+/// parallel: the nodes within a partition are processed one after the other
+/// on the audio thread.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Partitioned;
+
+impl Partitioned {
+    /// Split the nodes into partitions where every node only depends on nodes
+    /// in earlier partitions. Every node is placed in the earliest partition
+    /// possible, so the number of partitions is the length of the longest
+    /// chain of nodes and the nodes within a partition could be processed in
+    /// parallel.
+    pub fn partitions(topology: &GraphTopology) -> Vec<Vec<usize>> {
+        let mut partition_of = vec![0; topology.num_nodes()];
+        let mut partitions: Vec<Vec<usize>> = vec![];
+        // The depth first order guarantees that the inputs are visited first
+        for node in 0..topology.num_nodes() {
+            let partition = topology
+                .inputs(node)
+                .iter()
+                .map(|&input| partition_of[input] + 1)
+                .max()
+                .unwrap_or(0);
+            partition_of[node] = partition;
+            if partitions.len() <= partition {
+                partitions.resize(partition + 1, vec![]);
+            }
+            partitions[partition].push(node);
+        }
+        partitions
+    }
+}
+
+impl SchedulerStrategy for Partitioned {
+    fn order(&self, topology: &GraphTopology) -> Vec<usize> {
+        Self::partitions(topology).into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clustered, DepthFirst, GraphTopology, Partitioned, SchedulerStrategy};
+
+    /// A modulator (0) feeding two oscillators (1, 3), each followed by a
+    /// filter (2, 4), and an unrelated chain (5 -> 6)
+    fn topology() -> GraphTopology {
+        GraphTopology::new(
+            vec!["lfo", "osc", "filter", "osc", "filter", "noise", "filter"],
+            vec![vec![], vec![0], vec![1], vec![0], vec![3], vec![], vec![5]],
+            vec![false, false, true, false, true, false, true],
+        )
+    }
+
+    #[test]
+    fn strategies_produce_valid_orders() {
+        let topology = topology();
+        let strategies: [&dyn SchedulerStrategy; 3] = [&DepthFirst, &Clustered, &Partitioned];
+        for strategy in strategies {
+            let order = strategy.order(&topology);
+            assert!(topology.is_valid_order(&order), "{strategy:?}: {order:?}");
+        }
+        // The oscillator (3) is moved up to right after the modulator (0) it reads
+        let topology_with_mixer = GraphTopology::new(
+            vec!["lfo", "noise", "filter", "osc", "mix"],
+            vec![vec![], vec![], vec![1], vec![0], vec![2, 3]],
+            vec![false, false, false, false, true],
+        );
+        assert_eq!(Clustered.order(&topology_with_mixer), vec![0, 3, 1, 2, 4]);
+        assert_eq!(
+            Partitioned::partitions(&topology),
+            vec![vec![0, 5], vec![1, 3, 6], vec![2, 4]]
+        );
+        assert!(!topology.is_valid_order(&[1, 0, 2, 3, 4, 5, 6]));
+        assert!(!topology.is_valid_order(&[0, 1, 2, 3, 4, 5]));
+    }
+}
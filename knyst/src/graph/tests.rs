@@ -3,14 +3,20 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use knyst::graph::Time;
-use knyst::resources::{IdOrKey, ResourcesSettings};
+use knyst::resources::{BufferId, IdOrKey, ResourcesSettings};
 use knyst::Resources;
 
 use super::{Gen, RunGraph};
 use crate as knyst;
 use crate::controller::KnystCommands;
+use crate::gen::filter::one_pole::OnePoleLpf;
 use crate::gen::{BufferReader, WavetableOscillatorOwned};
-use crate::graph::{FreeError, Oversampling, ScheduleError};
+use crate::graph::connection::ConnectionError;
+use crate::graph::{
+    FreeError, GraphCounts, InputConstant, NodeDoneKind, OrderError, Oversampling, PushError,
+    ScheduleError,
+};
+use crate::inspection::{GraphInspection, GraphInspector};
 use crate::prelude::*;
 use crate::time::{Beats, Seconds};
 use crate::{controller::Controller, graph::connection::constant};
@@ -484,6 +490,56 @@ fn self_freeing_nodes() {
     assert_eq!(graph.num_nodes(), 0);
 }
 #[test]
+fn node_done_notifications() {
+    const BLOCK: usize = 4;
+    const SR: u64 = 44100;
+    let mut graph: Graph = Graph::new(GraphSettings {
+        block_size: BLOCK,
+        sample_rate: SR as Sample,
+        ..Default::default()
+    });
+    let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+    let n0 = graph.push(SelfFreeing {
+        samples_countdown: 6,
+        value: 1.,
+        mend: false,
+    });
+    let n1 = graph.push(SelfFreeing {
+        samples_countdown: 100,
+        value: 1.,
+        mend: false,
+    });
+    graph.connect(Connection::graph_output(n0)).unwrap();
+    graph.connect(Connection::graph_output(n1)).unwrap();
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    graph.notify_when_done(n0, sender.clone()).unwrap();
+    graph.notify_when_done(n1, sender).unwrap();
+    graph.update();
+    for _ in 0..3 {
+        run_graph.process_block();
+        graph.update();
+    }
+    let event = receiver.try_recv().unwrap();
+    assert_eq!(event.node, n0);
+    assert_eq!(event.kind, NodeDoneKind::FreedSelf);
+    // The countdown reaches 0 in the second block
+    assert_eq!(event.time, Seconds::from_samples(BLOCK as u64, SR));
+    assert!(receiver.try_recv().is_err());
+    graph.free_node(n1).unwrap();
+    let event = receiver.try_recv().unwrap();
+    assert_eq!(event.node, n1);
+    assert_eq!(event.kind, NodeDoneKind::Freed);
+    assert_eq!(event.time, Seconds::from_samples(BLOCK as u64 * 3, SR));
+    // Every event is only sent once
+    run_graph.process_block();
+    graph.update();
+    assert!(receiver.try_recv().is_err());
+    assert_eq!(
+        graph.notify_when_done(n1, crossbeam_channel::unbounded().0),
+        Err(ScheduleError::NodeNotFound)
+    );
+}
+#[test]
 fn scheduling() {
     const BLOCK: usize = 4;
     const SR: u64 = 44100;
@@ -585,6 +641,228 @@ fn scheduling() {
     // increasing the number of iterations above.
     assert_eq!(run_graph.graph_output_buffers().read(0, 0), 1002.0);
 }
+#[test]
+fn external_clock_transport_jumps() {
+    const BLOCK: usize = 4;
+    const SR: u64 = 44100;
+    let mut graph: Graph = Graph::new(GraphSettings {
+        block_size: BLOCK,
+        sample_rate: SR as Sample,
+        ..Default::default()
+    });
+    let mut run_graph = test_run_graph(
+        &mut graph,
+        RunGraphSettings {
+            scheduling_latency: Duration::from_millis(0),
+        },
+    );
+    let node = graph.push(OneGen {});
+    graph.connect(Connection::graph_output(node)).unwrap();
+    graph.update();
+    let samples = |n: u64| Duration::from_secs_f64((n as f64 + 0.5) / SR as f64);
+    let output = |run_graph: &RunGraph| run_graph.graph_output_buffers().get_channel(0).to_vec();
+    // The host starts at an arbitrary position
+    run_graph.process_block_at(1000);
+    assert_eq!(output(&run_graph), vec![1.0; 4]);
+    // Relative to the position of the host, not to the wall clock
+    graph
+        .schedule_change(ParameterChange::duration_from_now(
+            node.input(0),
+            5.0,
+            samples(2),
+        ))
+        .unwrap();
+    graph.update();
+    run_graph.process_block_at(1004);
+    assert_eq!(output(&run_graph), vec![1.0, 1.0, 6.0, 6.0]);
+    // Changes which are pending when the transport jumps keep their timing
+    // relative to the playhead, both on the audio thread and in the scheduler
+    graph
+        .schedule_change(ParameterChange::duration_from_now(
+            node.input(0),
+            20.0,
+            samples(6),
+        ))
+        .unwrap();
+    graph
+        .schedule_change(ParameterChange::duration_from_now(
+            node.input(0),
+            100.0,
+            samples(SR),
+        ))
+        .unwrap();
+    graph.update();
+    run_graph.process_block_at(500);
+    graph.update();
+    assert_eq!(output(&run_graph), vec![6.0; 4]);
+    run_graph.process_block_at(504);
+    graph.update();
+    assert_eq!(output(&run_graph), vec![6.0, 6.0, 21.0, 21.0]);
+    let mut frame = 508;
+    while frame < 500 + SR + 4 {
+        run_graph.process_block_at(frame);
+        graph.update();
+        let expected = if frame + 4 <= 500 + SR { 21.0 } else { 101.0 };
+        assert_eq!(output(&run_graph), vec![expected; 4], "frame {frame}");
+        frame += BLOCK as u64;
+    }
+}
+#[test]
+fn input_monitor_mixes_inputs_into_outputs() {
+    const BLOCK: usize = 4;
+    let mut graph: Graph = Graph::new(GraphSettings {
+        block_size: BLOCK,
+        num_inputs: 1,
+        num_outputs: 2,
+        ..Default::default()
+    });
+    let node = graph.push(OneGen {});
+    graph
+        .connect(Connection::graph_output(node).channels(2))
+        .unwrap();
+    graph.update();
+    let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+    unsafe { run_graph.graph_input_buffers().get_channel_mut(0) }.fill(2.0);
+    let output = |run_graph: &RunGraph, channel: usize| {
+        run_graph
+            .graph_output_buffers()
+            .get_channel(channel)
+            .to_vec()
+    };
+    run_graph.process_block();
+    assert_eq!(output(&run_graph, 0), vec![1.0; BLOCK]);
+    let monitor = run_graph.input_monitor();
+    monitor.set_enabled(true);
+    monitor.set_mix(0.5);
+    // The change is ramped over one block
+    run_graph.process_block();
+    assert_eq!(output(&run_graph, 0)[BLOCK - 1], 1.5);
+    assert!(output(&run_graph, 0)[0] < 1.5);
+    run_graph.process_block();
+    assert_eq!(output(&run_graph, 0), vec![1.5; BLOCK]);
+    assert_eq!(output(&run_graph, 1), vec![1.5; BLOCK]);
+    // Only the dry input on the left
+    monitor.set_route(0, 1, false);
+    monitor.set_mix(1.0);
+    run_graph.process_block();
+    run_graph.process_block();
+    assert_eq!(output(&run_graph, 0), vec![2.0; BLOCK]);
+    assert_eq!(output(&run_graph, 1), vec![0.0; BLOCK]);
+    monitor.set_enabled(false);
+    run_graph.process_block();
+    run_graph.process_block();
+    assert_eq!(output(&run_graph, 0), vec![1.0; BLOCK]);
+    assert_eq!(output(&run_graph, 1), vec![1.0; BLOCK]);
+}
+
+#[test]
+fn shutdown_fades_out_and_returns_resources() {
+    const BLOCK: usize = 4;
+    const FADE_FRAMES: usize = 16;
+    let mut graph: Graph = Graph::new(GraphSettings {
+        block_size: BLOCK,
+        num_inputs: 0,
+        num_outputs: 1,
+        sample_rate: 1000.,
+        ..Default::default()
+    });
+    let node = graph.push(OneGen {});
+    graph.connect(Connection::graph_output(node)).unwrap();
+    graph.update();
+    let mut resources = Resources::new(ResourcesSettings {
+        max_wavetables: 1,
+        max_buffers: 1,
+        max_user_data: 0,
+    });
+    let buffer = Buffer::from_vec(vec![1., 2., 3.], 1000.);
+    let buffer_id = BufferId::new(&buffer);
+    resources.insert_buffer_with_id(buffer, buffer_id).unwrap();
+    let wavetable_id = WavetableId::new();
+    resources
+        .insert_wavetable_with_id(crate::wavetable_aa::Wavetable::cosine(), wavetable_id)
+        .unwrap();
+    let (mut run_graph, resources_command_sender, resources_response_receiver) =
+        RunGraph::new(&mut graph, resources, RunGraphSettings::default()).unwrap();
+    let mut controller = Controller::new(
+        graph,
+        |e| println!("{e}"),
+        resources_command_sender,
+        resources_response_receiver,
+    );
+    let shutdown = std::thread::spawn(move || {
+        controller.shutdown(Duration::from_secs_f64(FADE_FRAMES as f64 / 1000.))
+    });
+    let mut output = vec![];
+    while !shutdown.is_finished() {
+        run_graph.run_resources_communication(50);
+        run_graph.process_block();
+        output.extend_from_slice(run_graph.graph_output_buffers().get_channel(0));
+        std::thread::sleep(Duration::from_micros(100));
+    }
+    let returned = shutdown.join().unwrap();
+    assert_eq!(returned.buffers.len(), 1);
+    assert_eq!(returned.buffers[0].0, buffer_id);
+    assert_eq!(returned.buffers[0].1.get_interleaved(2), [3.]);
+    // The default wavetable is not returned
+    assert_eq!(returned.wavetables.len(), 1);
+    assert_eq!(returned.wavetables[0].0, wavetable_id);
+    // A linear fade over FADE_FRAMES frames followed by silence
+    let fade_start = output.iter().position(|&s| s < 1.0).unwrap();
+    for (i, &s) in output[fade_start..].iter().enumerate() {
+        let expected = 1.0 - ((i + 1).min(FADE_FRAMES) as Sample / FADE_FRAMES as Sample);
+        assert!((s - expected).abs() < 1e-6, "{i}: {s} != {expected}");
+    }
+    assert!(output.len() >= fade_start + FADE_FRAMES);
+    run_graph.process_block();
+    assert_eq!(
+        run_graph.graph_output_buffers().get_channel(0),
+        [0.0; BLOCK]
+    );
+}
+
+#[test]
+fn controller_reports_activity_changes() {
+    use crate::gen::activity::{ActivityDetector, ActivityEvent};
+    let mut graph: Graph = Graph::new(GraphSettings {
+        block_size: 4,
+        num_inputs: 0,
+        num_outputs: 1,
+        sample_rate: 1000.,
+        ..Default::default()
+    });
+    let (detector, meter) = ActivityDetector::new(1, Seconds::from_seconds_f64(0.01));
+    let detector = graph.push(detector);
+    graph.connect(Connection::graph_output(detector)).unwrap();
+    graph.update();
+    let resources = Resources::new(test_resources_settings());
+    let (mut run_graph, resources_command_sender, resources_response_receiver) =
+        RunGraph::new(&mut graph, resources, RunGraphSettings::default()).unwrap();
+    let mut controller = Controller::new(
+        graph,
+        |e| println!("{e}"),
+        resources_command_sender,
+        resources_response_receiver,
+    );
+    let mut k = controller.get_knyst_commands();
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    k.watch_activity(meter, sender);
+    controller.run(100);
+    for _ in 0..2 {
+        run_graph.process_block();
+    }
+    controller.run(100);
+    assert!(receiver.try_recv().is_err());
+    run_graph.process_block();
+    controller.run(100);
+    assert_eq!(
+        receiver.try_recv(),
+        Ok(ActivityEvent::Silent { channel: 0 })
+    );
+    assert_eq!(run_graph.graph_output_buffers().get_channel(0), [1.0; 4]);
+    controller.run(100);
+    assert!(receiver.try_recv().is_err());
+}
+
 #[test]
 fn index_routing() {
     let graph_settings = GraphSettings {
@@ -951,6 +1229,284 @@ fn beat_scheduling() {
     assert_eq!(out[SR as usize / 4], 5.0);
 }
 
+#[test]
+fn scheduling_latency_override() {
+    const SR: u64 = 16;
+    const BLOCK_SIZE: usize = SR as usize;
+    let graph_settings = GraphSettings {
+        block_size: BLOCK_SIZE,
+        sample_rate: SR as Sample,
+        ..Default::default()
+    };
+    let mut graph = Graph::new(graph_settings);
+    let node = graph.push(OneGen {});
+    graph.connect(node.to_graph_out()).unwrap();
+    assert_eq!(graph.scheduling_latency(), None);
+    let (mut run_graph, _, _) = RunGraph::new(
+        &mut graph,
+        Resources::new(ResourcesSettings::default()),
+        RunGraphSettings {
+            scheduling_latency: Duration::from_secs(1),
+        },
+    )
+    .unwrap();
+    assert_eq!(graph.scheduling_latency(), Some(Duration::from_secs(1)));
+    assert_eq!(
+        graph.minimum_scheduling_latency(),
+        Duration::from_secs_f64(2. * BLOCK_SIZE as f64 / SR as f64)
+    );
+    // Uses the graph latency, i.e. one second (block) later
+    graph
+        .schedule_changes(
+            vec![node.change().set(0, 1.0)],
+            Time::Beats(Beats::from_fractional_beats::<4>(0, 1)),
+        )
+        .unwrap();
+    // Overrides the graph latency
+    graph
+        .schedule_changes_with_latency(
+            vec![node.change().set(0, 2.0)],
+            Time::Beats(Beats::from_fractional_beats::<4>(0, 2)),
+            Some(Duration::ZERO),
+        )
+        .unwrap();
+    graph.update();
+    run_graph.process_block();
+    let out = run_graph.graph_output_buffers().get_channel(0);
+    assert_eq!(out[0], 1.0);
+    assert_eq!(out[(SR / 4) as usize], 1.0);
+    assert_eq!(out[(SR / 2) as usize], 3.0);
+    graph.update();
+    run_graph.process_block();
+    let out = run_graph.graph_output_buffers().get_channel(0);
+    assert_eq!(out[0], 3.0);
+    assert_eq!(out[(SR / 4) as usize - 1], 3.0);
+    assert_eq!(out[(SR / 4) as usize], 2.0);
+}
+
+#[test]
+fn dezipper_constant_changes() {
+    // Outputs its input, without dezippering
+    struct GateGen;
+    impl Gen for GateGen {
+        fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+            let block_size = ctx.block_size();
+            for i in 0..block_size {
+                ctx.outputs.write(ctx.inputs.read(0, i), 0, i);
+            }
+            GenState::Continue
+        }
+        fn num_inputs(&self) -> usize {
+            1
+        }
+        fn num_outputs(&self) -> usize {
+            1
+        }
+        fn input_dezipper(&self, _input: usize) -> bool {
+            false
+        }
+    }
+    // The same, opting out through `impl_gen`
+    struct MacroGateGen;
+    #[impl_gen]
+    impl MacroGateGen {
+        #[process]
+        fn process(&mut self, #[dezipper(false)] gate: &[Sample], out: &mut [Sample]) -> GenState {
+            out.copy_from_slice(gate);
+            GenState::Continue
+        }
+    }
+    const SR: u64 = 16;
+    const BLOCK_SIZE: usize = 8;
+    let graph_settings = GraphSettings {
+        block_size: BLOCK_SIZE,
+        sample_rate: SR as Sample,
+        num_outputs: 3,
+        ..Default::default()
+    }
+    .dezipper(Duration::from_secs_f64(0.25));
+    let mut graph = Graph::new(graph_settings);
+    let node = graph.push(OneGen {});
+    graph.connect(node.to_graph_out()).unwrap();
+    let gate = graph.push(GateGen);
+    graph.connect(gate.to_graph_out().to_index(1)).unwrap();
+    let macro_gate = graph.push(MacroGateGen);
+    graph
+        .connect(macro_gate.to_graph_out().to_index(2))
+        .unwrap();
+    let mut run_graph = test_run_graph(
+        &mut graph,
+        RunGraphSettings {
+            scheduling_latency: Duration::new(0, 0),
+        },
+    );
+    graph
+        .schedule_changes(
+            vec![
+                node.change().set(0, 4.0),
+                gate.change().set(0, 4.0),
+                macro_gate.change().set(0, 4.0),
+            ],
+            Time::Seconds(Seconds::from_samples(2, SR)),
+        )
+        .unwrap();
+    graph
+        .schedule_change(ParameterChange::seconds(
+            node.input(0),
+            0.0,
+            Seconds::from_samples(7, SR),
+        ))
+        .unwrap();
+    graph.update();
+    run_graph.process_block();
+    let out = run_graph.graph_output_buffers().get_channel(0);
+    assert_eq!(out, &[1.0, 1.0, 2.0, 3.0, 4.0, 5.0, 5.0, 4.0]);
+    let gate_out = run_graph.graph_output_buffers().get_channel(1);
+    assert_eq!(gate_out, &[0.0, 0.0, 4.0, 4.0, 4.0, 4.0, 4.0, 4.0]);
+    let macro_gate_out = run_graph.graph_output_buffers().get_channel(2);
+    assert_eq!(macro_gate_out, gate_out);
+    // The ramp continues into the next block
+    graph.update();
+    run_graph.process_block();
+    let out = run_graph.graph_output_buffers().get_channel(0);
+    assert_eq!(out, &[3.0, 2.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+}
+
+#[test]
+fn input_constants_and_reset() {
+    // Outputs its "freq" input
+    struct DefaultsGen;
+    impl Gen for DefaultsGen {
+        fn process(&mut self, ctx: GenContext, _resources: &mut Resources) -> GenState {
+            let block_size = ctx.block_size();
+            for i in 0..block_size {
+                ctx.outputs.write(ctx.inputs.read(0, i), 0, i);
+            }
+            GenState::Continue
+        }
+        fn num_inputs(&self) -> usize {
+            2
+        }
+        fn num_outputs(&self) -> usize {
+            1
+        }
+        fn input_desc(&self, input: usize) -> &'static str {
+            match input {
+                0 => "freq",
+                1 => "amp",
+                _ => "",
+            }
+        }
+        fn input_default(&self, input: usize) -> Sample {
+            match input {
+                0 => 440.0,
+                _ => 0.5,
+            }
+        }
+    }
+    let graph_settings = GraphSettings {
+        block_size: 8,
+        num_outputs: 1,
+        ..Default::default()
+    };
+    let mut graph = Graph::new(graph_settings.clone());
+    let mut inner_graph = Graph::new(graph_settings);
+    let node = inner_graph.push(DefaultsGen);
+    inner_graph.connect(node.to_graph_out()).unwrap();
+    let inner_graph = graph.push(inner_graph);
+    graph.connect(inner_graph.to_graph_out()).unwrap();
+    let defaults = vec![
+        InputConstant {
+            name: "freq",
+            value: 440.0,
+            default: 440.0,
+        },
+        InputConstant {
+            name: "amp",
+            value: 0.5,
+            default: 0.5,
+        },
+    ];
+    assert_eq!(graph.input_constants(node), Some(defaults.clone()));
+    let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+    graph.update();
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().get_channel(0), &[440.0; 8]);
+
+    graph
+        .connect(constant(220.0).to(node).to_label("freq"))
+        .unwrap();
+    graph
+        .schedule_change(ParameterChange::seconds(
+            node.input("amp"),
+            1.0,
+            Seconds::from_seconds_f64(10.),
+        ))
+        .unwrap();
+    graph.update();
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().get_channel(0), &[220.0; 8]);
+    // Changes scheduled for later are included
+    let constants = graph.input_constants(node).unwrap();
+    assert_eq!((constants[0].value, constants[1].value), (220.0, 1.0));
+    assert!(!constants[0].is_default());
+
+    graph.reset_input(node.input("freq")).unwrap();
+    graph.reset_input(node.input(1)).unwrap();
+    graph.update();
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().get_channel(0), &[440.0; 8]);
+    assert_eq!(graph.input_constants(node), Some(defaults));
+    assert!(matches!(
+        graph.reset_input(node.input("phase")),
+        Err(ScheduleError::InputLabelNotFound("phase"))
+    ));
+    assert!(matches!(
+        graph.reset_input(node.input(2)),
+        Err(ScheduleError::InputOutOfRange { .. })
+    ));
+    graph.free_node(node).unwrap();
+    assert_eq!(graph.input_constants(node), None);
+}
+
+#[test]
+fn built_in_inputs_reset_to_declared_defaults() {
+    let graph_settings = GraphSettings {
+        block_size: 8,
+        num_outputs: 1,
+        ..Default::default()
+    };
+    let mut graph = Graph::new(graph_settings);
+    let osc = graph.push(WavetableOscillatorOwned::new(Wavetable::sine()));
+    let lpf = graph.push(OnePoleLpf::new());
+    graph.connect(osc.to(lpf)).unwrap();
+    graph.connect(lpf.to_graph_out()).unwrap();
+    let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+    graph.update();
+    // The oscillator is heard without setting its frequency
+    for _ in 0..10 {
+        run_graph.process_block();
+    }
+    assert!(run_graph.graph_output_buffers().get_channel(0)[7] != 0.0);
+
+    graph
+        .connect(constant(220.0).to(osc).to_label("freq"))
+        .unwrap();
+    graph.update();
+    assert_eq!(graph.input_constants(osc).unwrap()[0].value, 220.0);
+    graph.reset_input(osc.input("freq")).unwrap();
+    graph.update();
+    let freq = graph.input_constants(osc).unwrap()[0];
+    assert_eq!((freq.value, freq.default), (440.0, 440.0));
+    let cutoff = graph.input_constants(lpf).unwrap()[1];
+    assert_eq!((cutoff.value, cutoff.default), (19000.0, 19000.0));
+
+    // A node connected to an input replaces its default
+    graph.connect(osc.to(lpf).to_label("cutoff_freq")).unwrap();
+    let cutoff = graph.input_constants(lpf).unwrap()[1];
+    assert_eq!((cutoff.value, cutoff.default), (0.0, 19000.0));
+}
+
 #[test]
 fn inner_graph_different_block_size() {
     // An inner graph should get to have any valid block size and be converted
@@ -1197,3 +1753,409 @@ fn stereo_timing_mult_inner_graph() {
         assert!(l == r - 200.);
     }
 }
+#[test]
+fn gen_with_state_keeps_state_between_blocks() {
+    const BLOCK: usize = 4;
+    let mut graph = Graph::new(GraphSettings {
+        block_size: BLOCK,
+        sample_rate: 100.,
+        ..Default::default()
+    });
+    let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+    // Counts up from sample_rate + block_size, adding the input to every value
+    let counter = graph.push(
+        gen_with_state(0.0 as Sample, |counter, inputs, outputs, ctx| {
+            let input = inputs.get_channel(0);
+            let out = outputs.iter_mut().next().unwrap();
+            for (o, i) in out.iter_mut().zip(input.iter()).take(ctx.block_size) {
+                *o = *counter + *i;
+                *counter += 1.0;
+            }
+            GenState::Continue
+        })
+        .init(|counter, sample_rate, block_size| {
+            *counter = sample_rate + block_size as Sample;
+        })
+        .input("add")
+        .output("out"),
+    );
+    graph.connect(counter.to_graph_out()).unwrap();
+    graph.connect(constant(0.5).to(counter)).unwrap();
+    graph.update();
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().read(0, 0), 104.5);
+    assert_eq!(run_graph.graph_output_buffers().read(0, 3), 107.5);
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().read(0, 0), 108.5);
+}
+#[test]
+fn scheduler_strategies_give_the_same_output() {
+    use crate::graph::scheduler_strategy::{Clustered, DepthFirst, Partitioned};
+    use crate::graph::SchedulerStrategy;
+    let strategies: [Arc<dyn SchedulerStrategy>; 3] = [
+        Arc::new(DepthFirst),
+        Arc::new(Clustered),
+        Arc::new(Partitioned),
+    ];
+    let mut outputs = vec![];
+    for (i, strategy) in strategies.into_iter().enumerate() {
+        let mut graph = Graph::new(
+            GraphSettings::default()
+                .block_size(4)
+                .num_outputs(2)
+                .shared_scheduler_strategy(strategy),
+        );
+        // A counter feeding a chain to the left output and a node to the right
+        let counter = graph.push(DummyGen { counter: 0.0 });
+        let left0 = graph.push(OneGen {});
+        let left1 = graph.push(OneGen {});
+        let right = graph.push(OneGen {});
+        graph.connect(left1.to_graph_out()).unwrap();
+        graph.connect(right.to_graph_out().to_index(1)).unwrap();
+        graph.connect(left0.to(left1)).unwrap();
+        graph.connect(counter.to(left0)).unwrap();
+        graph.connect(counter.to(right)).unwrap();
+        let order = graph.node_order();
+        if i == 2 {
+            // Partitioned, the nodes reading from the counter are in the same partition
+            assert_eq!(order[0], counter);
+            assert!(order[1..3].contains(&left0) && order[1..3].contains(&right));
+            assert_eq!(order[3], left1);
+        }
+        graph.update();
+        let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+        run_graph.process_block();
+        run_graph.process_block();
+        outputs.push((
+            run_graph.graph_output_buffers().get_channel(0).to_vec(),
+            run_graph.graph_output_buffers().get_channel(1).to_vec(),
+        ));
+    }
+    assert_eq!(
+        outputs[0],
+        (vec![7.0, 8.0, 9.0, 10.0], vec![6.0, 7.0, 8.0, 9.0])
+    );
+    assert!(outputs.iter().all(|output| *output == outputs[0]));
+}
+#[test]
+fn invalid_scheduler_order_returns_an_error() {
+    use crate::graph::scheduler_strategy::GraphTopology;
+    use crate::graph::{OrderError, SchedulerStrategy};
+    /// Returns the depth first order backwards
+    #[derive(Debug)]
+    struct Backwards;
+    impl SchedulerStrategy for Backwards {
+        fn order(&self, topology: &GraphTopology) -> Vec<usize> {
+            (0..topology.num_nodes()).rev().collect()
+        }
+    }
+    let mut graph = Graph::new(
+        GraphSettings::default()
+            .block_size(4)
+            .num_outputs(1)
+            .scheduler_strategy(Backwards),
+    );
+    let first = graph.push(OneGen {});
+    let second = graph.push(OneGen {});
+    graph.connect(first.to(second)).unwrap();
+    graph.connect(second.to_graph_out()).unwrap();
+    assert!(matches!(
+        graph.calculate_node_order(),
+        Err(OrderError::InvalidStrategyOrder { .. })
+    ));
+    // The graph falls back to depth first order and keeps the error for the Controller
+    assert_eq!(graph.node_order(), vec![first, second]);
+    assert!(graph.take_scheduler_error().is_some());
+    assert!(graph.take_scheduler_error().is_none());
+}
+#[test]
+fn edit_batch_is_applied_at_once() {
+    let mut graph = Graph::new(GraphSettings {
+        block_size: 4,
+        num_outputs: 1,
+        ..Default::default()
+    });
+    let first = graph.push(OneGen {});
+    graph.connect(first.to_graph_out()).unwrap();
+    let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().read(0, 0), 1.0);
+    // Insert a node between `first` and the output in a batch
+    graph.start_edit_batch();
+    let second = graph.push(OneGen {});
+    graph.disconnect(first.to_graph_out()).unwrap();
+    // Scheduled changes are still sent while the batch is open
+    graph
+        .schedule_change(ParameterChange::seconds(
+            first.input(0),
+            1.0,
+            Seconds::from_samples(0, 44100),
+        ))
+        .unwrap();
+    graph.update();
+    run_graph.process_block();
+    // Without the batch `first` would have been disconnected here
+    assert_eq!(run_graph.graph_output_buffers().read(0, 0), 2.0);
+    graph.connect(first.to(second)).unwrap();
+    graph.connect(second.to_graph_out()).unwrap();
+    assert!(graph.is_in_edit_batch());
+    graph.commit_edit_batch();
+    assert!(!graph.is_in_edit_batch());
+    graph.update();
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().read(0, 0), 3.0);
+}
+#[test]
+fn task_data_in_flight_is_limited() {
+    let mut graph = Graph::new(GraphSettings {
+        block_size: 4,
+        num_outputs: 1,
+        ring_buffer_size: 2,
+        ..Default::default()
+    });
+    let mut last = graph.push(OneGen {});
+    graph.connect(last.to_graph_out()).unwrap();
+    let mut run_graph = test_run_graph(&mut graph, RunGraphSettings::default());
+    // Many updates while the audio thread isn't running, more than the ring
+    // buffers can hold
+    for _ in 0..6 {
+        let node = graph.push(OneGen {});
+        graph.disconnect(last.to_graph_out()).unwrap();
+        graph.connect(last.to(node)).unwrap();
+        graph.connect(node.to_graph_out()).unwrap();
+        last = node;
+        graph.update();
+    }
+    run_graph.process_block();
+    // The task lists that made it through are returned and the latest one is sent
+    graph.update();
+    run_graph.process_block();
+    assert_eq!(run_graph.graph_output_buffers().read(0, 0), 7.0);
+}
+#[test]
+fn pinned_node_order() {
+    let mut graph = Graph::new(GraphSettings {
+        block_size: 4,
+        ..Default::default()
+    });
+    let consumer = graph.push(OneGen {});
+    let analysis = graph.push(OneGen {});
+    let output = graph.push(OneGen {});
+    graph.connect(output.to_graph_out()).unwrap();
+    graph.connect(consumer.to(output)).unwrap();
+    let position = |order: &[NodeId], node: NodeId| order.iter().position(|&n| n == node);
+    let order = graph.node_order();
+    assert_eq!(order, vec![consumer, output, analysis]);
+    // The analysis node isn't connected, but has to run before the consumer
+    graph.order_nodes(analysis, consumer).unwrap();
+    let order = graph.node_order();
+    assert_eq!(order.len(), 3);
+    assert!(position(&order, analysis) < position(&order, consumer));
+    assert!(position(&order, consumer) < position(&order, output));
+    // Constraints between nodes that aren't connected to the output
+    let a = graph.push(OneGen {});
+    let b = graph.push(OneGen {});
+    graph.order_nodes(b, a).unwrap();
+    let order = graph.node_order();
+    assert!(position(&order, b) < position(&order, a));
+    graph.remove_node_order(b, a).unwrap();
+    let order = graph.node_order();
+    assert!(position(&order, a) < position(&order, b));
+    // Contradicting the connections is an error
+    assert_eq!(graph.order_nodes(output, consumer), Err(OrderError::Cycle));
+    assert_eq!(graph.order_nodes(a, a), Err(OrderError::SameNode));
+    // Freeing a node removes its constraints
+    graph.free_node(analysis).unwrap();
+    let order = graph.node_order();
+    assert_eq!(position(&order, analysis), None);
+    graph.order_nodes(consumer, a).unwrap();
+    graph.commit_changes();
+    let inspection = graph.generate_inspection();
+    assert_eq!(inspection.node_order.len(), 4);
+}
+#[test]
+fn chunked_inspection_reuses_buffers() {
+    let mut graph = Graph::new(GraphSettings {
+        block_size: 4,
+        ..Default::default()
+    });
+    let mut nodes = vec![];
+    for _ in 0..10 {
+        let node = graph.push(OneGen {});
+        if let Some(&last) = nodes.last() {
+            graph.connect(node.to(last)).unwrap();
+        }
+        nodes.push(node);
+    }
+    graph.connect(nodes[0].to_graph_out()).unwrap();
+    graph.commit_changes();
+    let full = graph.generate_inspection();
+    let mut inspector = GraphInspector::new(GraphInspection::empty());
+    assert!(!graph.inspect_chunk(&mut inspector, 4));
+    assert!(inspector.in_progress());
+    assert!(!graph.inspect_chunk(&mut inspector, 4));
+    assert!(graph.inspect_chunk(&mut inspector, 4));
+    let inspection = inspector.inspection();
+    assert_eq!(inspection.nodes.len(), 10);
+    for (a, b) in inspection.nodes.iter().zip(full.nodes.iter()) {
+        assert_eq!(a.address, b.address);
+        assert_eq!(a.input_edges.len(), b.input_edges.len());
+    }
+    assert_eq!(inspection.graph_output_input_edges.len(), 1);
+    assert_eq!(inspection.node_order, full.node_order);
+    // Nodes freed in the middle of an inspection are kept in it, so that it
+    // finishes even if nodes keep being freed
+    assert!(!graph.inspect_chunk(&mut inspector, 5));
+    graph.free_node(nodes[9]).unwrap();
+    graph.update();
+    graph.update();
+    assert!(graph.inspect_chunk(&mut inspector, 5));
+    let inspection = inspector.inspection();
+    assert_eq!(inspection.nodes.len(), 10);
+    assert_eq!(inspection.nodes[9].address, nodes[9]);
+    assert_eq!(inspection.nodes_pending_removal, vec![9]);
+    assert_eq!(graph.num_nodes(), 9);
+    // Reusing the buffers of an existing inspection
+    let mut inspection = inspector.into_inspection();
+    graph.generate_inspection_into(&mut inspection);
+    assert_eq!(inspection.nodes.len(), graph.num_nodes());
+}
+#[test]
+fn node_and_connection_quotas() {
+    let mut graph = Graph::new(
+        GraphSettings {
+            block_size: 4,
+            num_outputs: 1,
+            ..Default::default()
+        }
+        .max_nodes(3)
+        .max_connections(2),
+    );
+    let graph_id = graph.id();
+    let n0 = graph.push(OneGen {});
+    let n1 = graph.push(OneGen {});
+    let n2 = graph.push(OneGen {});
+    let result = graph.push_to_graph(OneGen {}, graph_id);
+    assert!(matches!(
+        result,
+        Err(PushError::NodeQuotaExceeded { max_nodes: 3, .. })
+    ));
+    graph.connect(n0.to_graph_out()).unwrap();
+    graph.connect(n1.to(n0)).unwrap();
+    // Constants are not counted as connections
+    graph.connect(constant(1.0).to(n2)).unwrap();
+    assert!(matches!(
+        graph.connect(n2.to(n1)),
+        Err(ConnectionError::ConnectionQuotaExceeded {
+            max_connections: 2,
+            num_connections: 2,
+            ..
+        })
+    ));
+    assert_eq!(
+        graph.counts(),
+        GraphCounts {
+            graph_id,
+            nodes: 3,
+            connections: 2,
+            max_nodes: Some(3),
+            max_connections: Some(2),
+        }
+    );
+    // Freed nodes and their connections don't count
+    graph.free_node(n1).unwrap();
+    assert_eq!(graph.num_active_nodes(), 2);
+    assert_eq!(graph.num_connections(), 1);
+    let n3 = graph.push_to_graph(OneGen {}, graph_id).unwrap();
+    graph.connect(n2.to(n3)).unwrap();
+    graph.set_max_nodes(None);
+    graph.push(OneGen {});
+    assert_eq!(graph.counts_of_graph(graph_id).unwrap().nodes, 4);
+    assert_eq!(graph.counts_of_graph(graph_id + 1000), None);
+}
+#[test]
+fn feedback_connections_count_towards_quotas() {
+    let mut graph = Graph::new(
+        GraphSettings {
+            block_size: 4,
+            num_outputs: 1,
+            ..Default::default()
+        }
+        .max_nodes(2),
+    );
+    let n0 = graph.push(OneGen {});
+    let n1 = graph.push(OneGen {});
+    graph.connect(n0.to_graph_out()).unwrap();
+    // The feedback node doesn't fit
+    assert!(matches!(
+        graph.connect(n0.feedback_to(n1)),
+        Err(ConnectionError::NodeQuotaExceeded { max_nodes: 2, .. })
+    ));
+    assert_eq!(graph.num_active_nodes(), 2);
+    assert_eq!(graph.num_connections(), 1);
+    graph.set_max_nodes(Some(3));
+    graph.connect(n0.feedback_to(n1)).unwrap();
+    assert_eq!(graph.num_active_nodes(), 3);
+    assert_eq!(graph.num_connections(), 2);
+    graph.set_max_connections(Some(2));
+    assert!(matches!(
+        graph.connect(n1.feedback_to(n0)),
+        Err(ConnectionError::ConnectionQuotaExceeded {
+            num_connections: 2,
+            ..
+        })
+    ));
+    graph.disconnect(n0.feedback_to(n1)).unwrap();
+    assert_eq!(graph.num_connections(), 1);
+}
+#[test]
+fn connection_count_follows_every_edge_change() {
+    let mut graph = Graph::new(GraphSettings {
+        block_size: 4,
+        num_inputs: 2,
+        num_outputs: 2,
+        ..Default::default()
+    });
+    let n0 = graph.push(OneGen {});
+    let n1 = graph.push(OneGen {});
+    let n2 = graph.push(OneGen {});
+    let changes = [
+        Connection::graph_input(n0),
+        n0.to(n1),
+        n1.to(n2),
+        n2.feedback_to(n0),
+        n1.feedback_to(n0),
+        n2.to_graph_out().channels(2),
+        Connection::GraphInputToOutput {
+            graph_id: graph.id(),
+            from_input_channel: 0,
+            to_output_channel: 0,
+            channels: 2,
+        },
+    ];
+    for connection in changes {
+        graph.connect(connection).unwrap();
+        assert_eq!(graph.num_connections(), graph.count_connections());
+    }
+    assert_eq!(graph.num_connections(), 9);
+    graph.disconnect(n1.feedback_to(n0)).unwrap();
+    assert_eq!(graph.num_connections(), graph.count_connections());
+    graph
+        .connect(Connection::clear_from_graph_inputs(n0))
+        .unwrap();
+    assert_eq!(graph.num_connections(), graph.count_connections());
+    graph
+        .connect(Connection::clear_to_graph_outputs(n2))
+        .unwrap();
+    assert_eq!(graph.num_connections(), graph.count_connections());
+    graph.connect(n2.to_graph_out()).unwrap();
+    graph.free_node(n2).unwrap();
+    assert_eq!(graph.num_connections(), graph.count_connections());
+    let n3 = graph.push(OneGen {});
+    graph.connect(n3.feedback_to(n0)).unwrap();
+    graph.connect(Connection::clear_from_nodes(n0)).unwrap();
+    assert_eq!(graph.num_connections(), graph.count_connections());
+    graph.connect(Connection::clear_to_nodes(n0)).unwrap();
+    assert_eq!(graph.num_connections(), graph.count_connections());
+    assert_eq!(graph.num_connections(), 2);
+}
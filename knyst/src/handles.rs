@@ -42,7 +42,7 @@ use std::{
 };
 
 use crate::{
-    graph::{Change, Connection, GraphId, ParameterChange, SimultaneousChanges},
+    graph::{Change, Connection, GraphId, ParameterChange, SimultaneousChanges, Time},
     prelude::{PowfGen, PowfHandle, SubGen},
     Sample,
 };
@@ -185,15 +185,40 @@ impl<A: Copy + HandleData> Handle<A> {
     }
     /// The non-typed way to send a trigger to an input channel
     pub fn trig(self, channel: impl Into<NodeChannel>) -> Handle<A> {
+        self.trig_at(channel, Time::Immediately)
+    }
+    /// Send a one sample trigger to an input channel at `time`, e.g. to
+    /// restart an envelope or a sampler.
+    ///
+    /// Inside a [`schedule_bundle`](crate::controller::schedule_bundle),
+    /// [`Handle::trig`] is sufficient for a trigger at the time of the bundle.
+    /// A trigger with any other `time` is applied at `time`, not at the time
+    /// of the bundle.
+    pub fn trig_at(self, channel: impl Into<NodeChannel>, time: Time) -> Handle<A> {
         let channel = channel.into();
-        // TODO: better way to send a trigger
-        let mut changes = SimultaneousChanges::now();
+        let mut changes = SimultaneousChanges::at(time);
         for id in self.node_ids() {
             changes.push(id.change().trigger(channel.clone()));
         }
         knyst_commands().schedule_changes(changes);
         self
     }
+    /// Set an input channel's constant back to the default value of its Gen,
+    /// see [`Gen::input_default`]. The channel is resolved in the same way as
+    /// in [`Handle::set`].
+    pub fn reset(self, channel: impl Into<NodeChannel>) -> Handle<A> {
+        let input = match channel.into() {
+            NodeChannel::Label(channel_label) => self
+                .in_channels()
+                .next()
+                .map(|(sink, _chan)| (sink, NodeChannel::Label(channel_label))),
+            NodeChannel::Index(channel_index) => self.in_channels().nth(channel_index),
+        };
+        if let Some((Sink::Gen(id), chan)) = input {
+            knyst_commands().reset_input(id.input(chan));
+        }
+        self
+    }
     /// Sets the mortality for every referenced by the Handle
     pub fn set_mortality(self, is_mortal: bool) -> Handle<A> {
         for id in self.node_ids() {
@@ -1315,9 +1340,12 @@ impl AnyNodeHandle {
     }
     /// The non-typed way to send a trigger to an input channel
     pub fn trig(&self, channel: impl Into<NodeChannel>) -> &Self {
+        self.trig_at(channel, Time::Immediately)
+    }
+    /// Send a one sample trigger to an input channel at `time`. See [`Handle::trig_at`].
+    pub fn trig_at(&self, channel: impl Into<NodeChannel>, time: Time) -> &Self {
         let channel = channel.into();
-        // TODO: better way to send a trigger
-        let mut changes = SimultaneousChanges::now();
+        let mut changes = SimultaneousChanges::at(time);
         for id in self.node_ids() {
             changes.push(id.change().trigger(channel.clone()));
         }
@@ -2,7 +2,15 @@
 //!
 //! Metadata from the structs in this module can be used to visualise and/or
 //! manipulate a graph based on the whole graph structure.
-use crate::graph::NodeId;
+//!
+//! For very large graphs, a [`GraphInspector`] can fill a [`GraphInspection`]
+//! a limited number of nodes at a time using [`Graph::inspect_chunk`], reusing
+//! the buffers of previous inspections.
+use slotmap::SecondaryMap;
+
+#[allow(unused)]
+use crate::graph::Graph;
+use crate::graph::{GraphId, NodeId, NodeKey};
 
 /// The metadata of a Graph
 // TODO: Feedback edges
@@ -13,7 +21,13 @@ pub struct GraphInspection {
     pub nodes: Vec<NodeInspection>,
     /// Nodes that are not connected to any graph output in any chain
     pub unconnected_nodes: Vec<usize>,
-    /// Node indices that are in the Graph, but will be removed as soon as it is safe.
+    /// The indices of the nodes in the order they are processed, as of the
+    /// last time the node order was calculated
+    pub node_order: Vec<usize>,
+    /// Node indices that are in the Graph, but will be removed as soon as it
+    /// is safe, or that were removed while a chunked inspection was in
+    /// progress. The [`NodeInspection`] of a node that was removed before it
+    /// was inspected only contains its address.
     pub nodes_pending_removal: Vec<usize>,
     /// The indices of nodes connected to the graph output(s)
     pub graph_output_input_edges: Vec<EdgeInspection>,
@@ -31,6 +45,7 @@ impl GraphInspection {
         Self {
             nodes: vec![],
             unconnected_nodes: vec![],
+            node_order: vec![],
             nodes_pending_removal: vec![],
             graph_output_input_edges: vec![],
             num_inputs: 0,
@@ -73,3 +88,81 @@ pub enum EdgeSource {
     Node(usize),
     Graph,
 }
+
+/// Incrementally generates a [`GraphInspection`] using [`Graph::inspect_chunk`]
+/// so that inspecting a graph with many nodes can be spread out over time, e.g.
+/// between processing commands on the controller thread.
+///
+/// The buffers of the inspection and the internal bookkeeping are reused
+/// between inspections. Once an inspector and its inspection have grown to the
+/// size of the graph, inspecting the graph again does not allocate, except
+/// for any inner graphs which are inspected in full in one go.
+///
+/// The inspection is a snapshot of the nodes that existed when it was
+/// started. Nodes that are freed before the inspection is finished stay in the
+/// inspection and are listed in [`GraphInspection::nodes_pending_removal`].
+#[derive(Debug)]
+pub struct GraphInspector {
+    pub(crate) inspection: GraphInspection,
+    /// The keys and ids of the nodes in the inspection, in the order of the inspection
+    pub(crate) keys: Vec<(NodeKey, NodeId)>,
+    /// The indices of nodes that were freed before they were inspected
+    pub(crate) removed: Vec<usize>,
+    /// Maps node keys to their index in the inspection
+    pub(crate) indices: SecondaryMap<NodeKey, usize>,
+    /// The index of the next node to inspect
+    pub(crate) next: usize,
+    pub(crate) in_progress: bool,
+    pub(crate) graph_id: GraphId,
+}
+
+impl GraphInspector {
+    /// Create a new inspector, reusing the buffers of `inspection`. Use
+    /// [`GraphInspection::empty`] if you don't have a previous inspection.
+    pub fn new(inspection: GraphInspection) -> Self {
+        Self {
+            inspection,
+            keys: Vec::new(),
+            removed: Vec::new(),
+            indices: SecondaryMap::new(),
+            next: 0,
+            in_progress: false,
+            graph_id: 0,
+        }
+    }
+    /// True if an inspection has been started, but not finished.
+    pub fn in_progress(&self) -> bool {
+        self.in_progress
+    }
+    /// The number of nodes that have been inspected so far in the current
+    /// inspection, or in the last one if it is finished.
+    pub fn nodes_inspected(&self) -> usize {
+        self.next
+    }
+    /// The number of nodes in the current inspection
+    pub fn num_nodes(&self) -> usize {
+        self.keys.len()
+    }
+    /// The inspection. It is only complete when [`Graph::inspect_chunk`] has
+    /// returned true and no new inspection has been started.
+    pub fn inspection(&self) -> &GraphInspection {
+        &self.inspection
+    }
+    /// Take the inspection out of the inspector, replacing it by `buffer`
+    /// which will be reused for the next inspection.
+    pub fn replace_inspection(&mut self, buffer: GraphInspection) -> GraphInspection {
+        self.in_progress = false;
+        self.next = 0;
+        std::mem::replace(&mut self.inspection, buffer)
+    }
+    /// Consume the inspector and return the inspection
+    pub fn into_inspection(self) -> GraphInspection {
+        self.inspection
+    }
+    /// Abort the current inspection so that the next call to
+    /// [`Graph::inspect_chunk`] starts a new one.
+    pub fn restart(&mut self) {
+        self.in_progress = false;
+        self.next = 0;
+    }
+}
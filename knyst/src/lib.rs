@@ -4,7 +4,10 @@
 //! performance. It's main target use case is desktop multi-threaded real time
 //! environments, but it can also do single threaded and/or non real time
 //! synthesis. Embedded platforms are currently not supported, but on the
-//! roadmap.
+//! roadmap. The first step is the `knyst_core` crate which contains the types
+//! of Knyst that build without std, see [`wavetable`] and [`xorrng`]. The
+//! [`gen::Gen`] trait, [`graph::Graph`] and [`resources::Resources`] still
+//! require std.
 //!
 //! The main selling point of Knyst is that the graph can be modified while it's
 //! running: nodes and connections between nodes can be added/removed. It also
@@ -61,10 +64,10 @@ use audio_backend::AudioBackendError;
 use core::fmt::Debug;
 use modal_interface::SphereError;
 use resources::ResourcesError;
-use std::ops::{Deref, DerefMut};
 // Import these for docs
 #[allow(unused_imports)]
 use graph::{Connection, Graph, RunGraph};
+pub use knyst_core::{amplitude_to_db, db_to_amplitude, BlockSize, Sample, SampleRate, Trig};
 pub use modal_interface::knyst_commands;
 pub use resources::Resources;
 
@@ -91,14 +94,15 @@ pub mod modal_interface;
 pub mod node_buffer;
 pub mod offline;
 pub mod prelude;
+pub mod quick;
 pub mod resources;
 pub mod scheduling;
 pub mod sphere;
 pub mod time;
 pub mod trig;
-pub mod wavetable;
+pub use knyst_core::wavetable;
 pub mod wavetable_aa;
-pub mod xorrng;
+pub use knyst_core::xorrng;
 
 /// Combined error type for Knyst, containing any other error in the library.
 #[derive(thiserror::Error, Debug)]
@@ -115,6 +119,9 @@ pub enum KnystError {
     /// Error scheduling a change
     #[error("Error scheduling a change: {0}")]
     ScheduleError(#[from] graph::ScheduleError),
+    /// Error pinning the order of nodes in a [`Graph`]
+    #[error("Error ordering nodes: {0}")]
+    OrderError(#[from] graph::OrderError),
     /// Error from creating a RunGraph
     #[error("Error with the RunGraph: {0}")]
     RunGraphError(#[from] graph::run_graph::RunGraphError),
@@ -128,102 +135,3 @@ pub enum KnystError {
     #[error("Audio backend error : {0}")]
     AudioBackendError(#[from] AudioBackendError),
 }
-
-/// Convert db to amplitude
-#[inline]
-#[must_use]
-pub fn db_to_amplitude(db: Sample) -> Sample {
-    (10.0 as Sample).powf(db / 20.0)
-}
-/// Convert amplitude to db
-#[inline]
-#[must_use]
-pub fn amplitude_to_db(amplitude: Sample) -> Sample {
-    20.0 * amplitude.log10()
-}
-
-/// The current sample type used throughout Knyst
-pub type Sample = f32;
-
-/// Marker for inputs that are trigs. This makes it possible to set that value correctly through a Handle.
-pub type Trig = Sample;
-
-/// Newtype for a sample rate to identify it in function signatures. Derefs to a `Sample` for easy use on the audio thread.
-#[derive(Copy, Clone, Debug)]
-pub struct SampleRate(pub Sample);
-
-impl SampleRate {
-    #[inline(always)]
-    #[allow(missing_docs)]
-    pub fn to_f64(self) -> f64 {
-        self.0 as f64
-    }
-    #[allow(missing_docs)]
-    #[inline(always)]
-    pub fn to_usize(self) -> usize {
-        self.0 as usize
-    }
-}
-
-impl Deref for SampleRate {
-    type Target = Sample;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl DerefMut for SampleRate {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-impl From<SampleRate> for f64 {
-    fn from(value: SampleRate) -> Self {
-        value.0 as f64
-    }
-}
-
-impl From<f32> for SampleRate {
-    fn from(value: f32) -> Self {
-        Self(value as Sample)
-    }
-}
-impl From<f64> for SampleRate {
-    fn from(value: f64) -> Self {
-        Self(value as Sample)
-    }
-}
-
-#[derive(Copy, Clone, Debug)]
-/// BlockSize.
-///
-/// Can be an unorthodox block size value in the event of a partial block at the beginning of a node's existence in the graph.
-pub struct BlockSize(pub usize);
-
-impl From<BlockSize> for usize {
-    #[inline(always)]
-    fn from(value: BlockSize) -> Self {
-        value.0
-    }
-}
-impl From<usize> for BlockSize {
-    fn from(value: usize) -> Self {
-        Self(value)
-    }
-}
-
-impl Deref for BlockSize {
-    type Target = usize;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl DerefMut for BlockSize {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
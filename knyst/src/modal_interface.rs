@@ -15,6 +15,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{atomic::AtomicU16, Mutex};
+use std::time::Duration;
 
 use crate::audio_backend::AudioBackendError;
 use crate::resources::{BufferId, WavetableId};
@@ -289,6 +290,55 @@ impl KnystCommands for UnifiedKnystCommands {
         }
     }
 
+    fn request_counts(
+        &mut self,
+        graph_id: crate::graph::GraphId,
+    ) -> std::sync::mpsc::Receiver<Option<crate::graph::GraphCounts>> {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().request_counts(graph_id),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+                std::sync::mpsc::sync_channel(0).1
+            }
+        }
+    }
+
+    fn request_input_constants(
+        &mut self,
+        node: NodeId,
+    ) -> std::sync::mpsc::Receiver<Option<Vec<crate::graph::InputConstant>>> {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().request_input_constants(node),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+                std::sync::mpsc::sync_channel(0).1
+            }
+        }
+    }
+
+    fn reset_input(&mut self, input: crate::graph::connection::NodeInput) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().reset_input(input),
+            UnifiedKnystCommands::Dummy(kc) => kc.report_dummy(),
+        }
+    }
+
+    fn request_chunked_inspection(
+        &mut self,
+        buffer: crate::inspection::GraphInspection,
+        nodes_per_update: usize,
+    ) -> std::sync::mpsc::Receiver<crate::inspection::GraphInspection> {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc
+                .borrow_mut()
+                .request_chunked_inspection(buffer, nodes_per_update),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+                std::sync::mpsc::sync_channel(0).1
+            }
+        }
+    }
+
     fn to_graph(&mut self, graph_id: crate::graph::GraphId) {
         match self {
             UnifiedKnystCommands::Real(kc) => kc.borrow_mut().to_graph(graph_id),
@@ -310,6 +360,15 @@ impl KnystCommands for UnifiedKnystCommands {
         }
     }
 
+    fn start_scheduling_bundle_with_latency(&mut self, time: Time, latency: Duration) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc
+                .borrow_mut()
+                .start_scheduling_bundle_with_latency(time, latency),
+            UnifiedKnystCommands::Dummy(kc) => kc.report_dummy(),
+        }
+    }
+
     fn upload_scheduling_bundle(&mut self) {
         match self {
             UnifiedKnystCommands::Real(kc) => kc.borrow_mut().upload_scheduling_bundle(),
@@ -317,6 +376,30 @@ impl KnystCommands for UnifiedKnystCommands {
         }
     }
 
+    fn start_edit_batch(&mut self) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().start_edit_batch(),
+            UnifiedKnystCommands::Dummy(kc) => kc.report_dummy(),
+        }
+    }
+
+    fn commit_edit_batch(&mut self) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().commit_edit_batch(),
+            UnifiedKnystCommands::Dummy(kc) => kc.report_dummy(),
+        }
+    }
+
+    fn minimum_scheduling_latency(&self) -> Duration {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().minimum_scheduling_latency(),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+                Duration::ZERO
+            }
+        }
+    }
+
     fn current_graph(&self) -> crate::graph::GraphId {
         match self {
             UnifiedKnystCommands::Real(kc) => kc.borrow_mut().current_graph(),
@@ -335,6 +418,50 @@ impl KnystCommands for UnifiedKnystCommands {
             }
         }
     }
+
+    fn notify_when_done(
+        &mut self,
+        node: NodeId,
+        sender: crossbeam_channel::Sender<crate::graph::NodeDoneEvent>,
+    ) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().notify_when_done(node, sender),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+            }
+        }
+    }
+
+    fn watch_activity(
+        &mut self,
+        meter: crate::gen::activity::ActivityMeter,
+        sender: crossbeam_channel::Sender<crate::gen::activity::ActivityEvent>,
+    ) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().watch_activity(meter, sender),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+            }
+        }
+    }
+
+    fn order_nodes(&mut self, before: NodeId, after: NodeId) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().order_nodes(before, after),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+            }
+        }
+    }
+
+    fn remove_node_order(&mut self, before: NodeId, after: NodeId) {
+        match self {
+            UnifiedKnystCommands::Real(kc) => kc.borrow_mut().remove_node_order(before, after),
+            UnifiedKnystCommands::Dummy(kc) => {
+                kc.report_dummy();
+            }
+        }
+    }
     // fn push(&mut self) {
     //     match self {
     //         UnifiedKnystCommands::Real(kc) => kc.borrow_mut().push(),
@@ -379,13 +506,16 @@ pub(crate) fn register_sphere(sphere: KnystSphere) -> Result<SphereId, SphereErr
         Err(SphereError::NoMoreSphereIds)
     }
 }
-pub(crate) fn remove_sphere(sphere_id: SphereId) -> Result<(), SphereError> {
+/// Remove the sphere from the global list of spheres and return it. The
+/// sphere is returned rather than dropped here so that it is dropped after the
+/// lock on the list has been released.
+pub(crate) fn remove_sphere(sphere_id: SphereId) -> Result<KnystSphere, SphereError> {
     let mut spheres = match ALL_KNYST_SPHERES.lock() {
         Ok(s) => s,
         Err(poison) => poison.into_inner(),
     };
     if let Some(index) = spheres.iter().position(|(_, id)| *id == sphere_id) {
-        let (_old_sphere, _) = spheres.remove(index);
+        let (old_sphere, _) = spheres.remove(index);
         if ACTIVE_KNYST_SPHERE.with(|aks| *aks.borrow_mut()) == sphere_id {
             if let Some((_, new_active)) = spheres.first() {
                 let new_active = *new_active;
@@ -397,7 +527,7 @@ pub(crate) fn remove_sphere(sphere_id: SphereId) -> Result<(), SphereError> {
                 });
             }
         }
-        Ok(())
+        Ok(old_sphere)
     } else {
         Err(SphereError::SphereNotFound)
     }
@@ -416,6 +546,19 @@ fn get_sphere_commands(sphere_id: SphereId) -> Result<MultiThreadedKnystCommands
     }
 }
 
+/// Returns the id of the default sphere if it has been started on any thread.
+pub(crate) fn default_sphere_id() -> Option<SphereId> {
+    let spheres = match ALL_KNYST_SPHERES.lock() {
+        Ok(spheres) => spheres,
+        Err(poison_lock) => poison_lock.into_inner(),
+    };
+    let default_sphere = SphereId(DEFAULT_KNYST_SPHERE.load(std::sync::atomic::Ordering::SeqCst));
+    spheres
+        .iter()
+        .any(|(_s, id)| *id == default_sphere)
+        .then_some(default_sphere)
+}
+
 /// Set the selected sphere to be active on this thread.
 pub fn set_active_sphere(id: SphereId) -> Result<(), SphereError> {
     ACTIVE_KNYST_SPHERE.with(|aks| *aks.borrow_mut() = id);
@@ -430,6 +573,16 @@ pub fn set_active_sphere(id: SphereId) -> Result<(), SphereError> {
 // Return impl KnystCommands to avoid committing to a return type and being able to change the return type through conditional compilation for different platforms
 /// Returns an implementor of [`KnystCommands`] which allows interacting with Knyst
 pub fn knyst_commands() -> impl KnystCommands {
+    unified_knyst_commands()
+}
+
+/// Returns true if there is a [`KnystSphere`] for [`knyst_commands`] to send
+/// commands to from this thread.
+pub fn is_sphere_active() -> bool {
+    matches!(unified_knyst_commands(), UnifiedKnystCommands::Real(_))
+}
+
+fn unified_knyst_commands() -> UnifiedKnystCommands {
     if let Some(kc) = ACTIVE_KNYST_SPHERE_COMMANDS.with(|aksc| aksc.borrow().clone()) {
         UnifiedKnystCommands::Real(kc)
     } else {
@@ -457,6 +610,9 @@ pub enum SphereError {
     /// There was an error in the audio backend
     #[error("Audio backend error: {0}")]
     AudioBackendError(#[from] AudioBackendError),
+    /// There was an error creating the [`RunGraph`](crate::graph::RunGraph)
+    #[error("RunGraph error: {0}")]
+    RunGraphError(#[from] crate::graph::run_graph::RunGraphError),
 }
 
 // pub fn test_using() {
@@ -1,12 +1,20 @@
 //! For running and inspecting the output of Knyst offline i.e. generating buffers of samples without automatically outputing them anywhere e.g. to a sound card.
 //!
 //! Useful for tests and non-realtime processing.
+//!
+//! A whole piece can be rendered in one call from a [`Score`], a list of
+//! timestamped commands similar to SuperCollider's NRT scores.
+//!
+//! Two versions of the same signal chain can be compared sample by sample
+//! using [`null_test`].
 use crate::{
     audio_backend::AudioBackend,
-    controller::{print_error_handler, Controller},
-    graph::RunGraph,
+    controller::{print_error_handler, schedule_bundle, Controller},
+    gen::null_test::{NullTestAccumulator, NullTestReport},
+    graph::{RunGraph, Time},
     modal_interface::{remove_sphere, set_active_sphere, SphereId},
     prelude::{KnystSphere, SphereSettings},
+    time::Seconds,
     Sample,
 };
 
@@ -97,6 +105,40 @@ impl KnystOffline {
             None
         }
     }
+    /// Render a [`Score`] from the start, returning `duration` worth of audio
+    /// per output channel. The events of the [`Score`] are applied block by
+    /// block, just in time for the block in which they take place, and are
+    /// scheduled at their exact sample.
+    ///
+    /// Since the [`KnystOffline`] keeps running from its current time,
+    /// rendering a [`Score`] should be the first thing you do with it.
+    pub fn render_score(&mut self, score: Score, duration: Seconds) -> Vec<Vec<Sample>> {
+        let sample_rate = self.test_backend.sample_rate as u64;
+        let block_size = self.test_backend.block_size;
+        let num_frames = duration.to_samples(sample_rate) as usize;
+        let mut output = vec![Vec::with_capacity(num_frames); self.test_backend.num_outputs];
+        let mut events = score.events.into_iter().peekable();
+        let mut block_start = 0;
+        while block_start < num_frames {
+            let block_end = (block_start + block_size) as u64;
+            while let Some((time, _)) = events.peek() {
+                if time.to_samples(sample_rate) >= block_end {
+                    break;
+                }
+                let (time, commands) = events.next().unwrap();
+                schedule_bundle(Time::Seconds(time), commands);
+            }
+            self.process_block();
+            let frames_in_block = block_size.min(num_frames - block_start);
+            for (channel, out) in output.iter_mut().enumerate() {
+                if let Some(samples) = self.output_channel(channel) {
+                    out.extend_from_slice(&samples[..frames_in_block]);
+                }
+            }
+            block_start += block_size;
+        }
+        output
+    }
 }
 impl Drop for KnystOffline {
     fn drop(&mut self) {
@@ -104,6 +146,127 @@ impl Drop for KnystOffline {
     }
 }
 
+/// A list of timestamped commands for rendering a piece non-realtime using
+/// [`Score::render`] or [`KnystOffline::render_score`].
+///
+/// Every event is a closure using the modal API, e.g. creating nodes through
+/// handles or changing their inputs. All the changes made in the closure are
+/// scheduled as a bundle at the time of the event so that they take effect at
+/// the exact sample, regardless of the block size.
+///
+/// ```
+/// use knyst::prelude::*;
+/// use knyst::offline::Score;
+///
+/// let score = Score::new()
+///     .at(Seconds::ZERO, || {
+///         graph_output(0, oscillator(WavetableId::cos()).freq(220.) * 0.25);
+///     })
+///     .at(Seconds::from_seconds_f64(0.5), || {
+///         graph_output(0, oscillator(WavetableId::cos()).freq(330.) * 0.25);
+///     });
+/// let output = score.render(44100, 64, 1, Seconds::from_seconds_f64(1.0));
+/// assert_eq!(output[0].len(), 44100);
+/// ```
+#[derive(Default)]
+pub struct Score {
+    events: Vec<(Seconds, Box<dyn FnOnce()>)>,
+}
+
+impl Score {
+    /// Create an empty [`Score`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add an event at `time` from the start of the [`Score`]. Events at the
+    /// same time are applied in the order they were added.
+    #[must_use]
+    pub fn at(mut self, time: Seconds, commands: impl FnOnce() + 'static) -> Self {
+        self.push(time, commands);
+        self
+    }
+    /// Add an event at `time` from the start of the [`Score`]. Events at the
+    /// same time are applied in the order they were added.
+    pub fn push(&mut self, time: Seconds, commands: impl FnOnce() + 'static) {
+        // Insert after any events at the same time to keep the order stable
+        let index = self.events.partition_point(|(t, _)| *t <= time);
+        self.events.insert(index, (time, Box::new(commands)));
+    }
+    /// The number of events in the [`Score`]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+    /// Returns true if the [`Score`] contains no events
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+    /// Render the [`Score`] using a new [`KnystOffline`] sphere, returning
+    /// `duration` worth of audio per output channel.
+    pub fn render(
+        self,
+        sample_rate: usize,
+        block_size: usize,
+        num_outputs: usize,
+        duration: Seconds,
+    ) -> Vec<Vec<Sample>> {
+        let mut kt = KnystOffline::new(sample_rate, block_size, 0, num_outputs);
+        kt.render_score(self, duration)
+    }
+}
+
+/// Render the graphs built by `a` and `b` in separate [`KnystOffline`]
+/// spheres and compare their outputs sample by sample, e.g. to verify that an
+/// optimised version of a signal chain is transparent. Returns one
+/// [`NullTestReport`] per output channel, so a difference can be traced to the
+/// channel it is on and `max_difference_at` is the frame within that channel.
+///
+/// Gens using randomness are seeded in creation order, so `a` and `b` will
+/// only produce the same random values if the random Gens are created in the
+/// same order and the same number of random Gens is created before either.
+///
+/// ```
+/// use knyst::prelude::*;
+/// use knyst::offline::null_test;
+///
+/// let reports = null_test(
+///     44100,
+///     64,
+///     1,
+///     Seconds::from_seconds_f64(0.5),
+///     || graph_output(0, oscillator(WavetableId::cos()).freq(220.) * 0.5),
+///     || graph_output(0, oscillator(WavetableId::cos()).freq(220.) * 0.25 * 2.0),
+/// );
+/// assert!(reports.iter().all(|report| report.is_within(1e-6)));
+/// ```
+pub fn null_test(
+    sample_rate: usize,
+    block_size: usize,
+    num_outputs: usize,
+    duration: Seconds,
+    a: impl FnOnce() + 'static,
+    b: impl FnOnce() + 'static,
+) -> Vec<NullTestReport> {
+    let render = |build: Box<dyn FnOnce()>| {
+        Score::new()
+            .at(Seconds::ZERO, build)
+            .render(sample_rate, block_size, num_outputs, duration)
+    };
+    let output_a = render(Box::new(a));
+    let output_b = render(Box::new(b));
+    output_a
+        .iter()
+        .zip(output_b.iter())
+        .map(|(a, b)| {
+            let mut accumulator = NullTestAccumulator::new();
+            accumulator.compare(a, b, None);
+            accumulator.report()
+        })
+        .collect()
+}
+
 struct OfflineBackend {
     sample_rate: usize,
     block_size: usize,
@@ -151,3 +314,137 @@ impl AudioBackend for OfflineBackend {
         Some(self.num_inputs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Score;
+    use crate::controller::schedule_bundle;
+    use crate::graph::Time;
+    use crate::prelude::*;
+    use crate::trig::once_trig;
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn score_is_sample_accurate() {
+        let sr = 44100;
+        let bus_node: Rc<Cell<Option<Handle<GenericHandle>>>> = Rc::new(Cell::new(None));
+        let b = bus_node.clone();
+        let score = Score::new()
+            .at(Seconds::from_samples(70, sr), move || {
+                bus_node.get().unwrap().set(0, 2.0);
+            })
+            .at(Seconds::ZERO, move || {
+                let node = bus(1).set(0, 1.0);
+                graph_output(0, node);
+                b.set(Some(node));
+            })
+            .at(Seconds::from_samples(130, sr), || {
+                graph_output(0, once_trig());
+            });
+        assert_eq!(score.len(), 3);
+        let output = score.render(sr as usize, 64, 1, Seconds::from_samples(150, sr));
+        let o = &output[0];
+        assert_eq!(o.len(), 150);
+        assert_eq!(o[0], 1.0);
+        assert_eq!(o[69], 1.0);
+        assert_eq!(o[70], 2.0);
+        assert_eq!(o[129], 2.0);
+        assert_eq!(o[130], 3.0);
+        assert_eq!(o[131], 2.0);
+    }
+
+    #[test]
+    fn null_test_reports_differences() {
+        let sr = 44100;
+        let same = super::null_test(
+            sr,
+            64,
+            1,
+            Seconds::from_samples(1000, sr as u64),
+            || graph_output(0, bus(1).set(0, 0.5)),
+            || graph_output(0, bus(1).set(0, 0.25) * 2.0),
+        );
+        assert_eq!(same.len(), 1);
+        assert_eq!(same[0].num_samples, 1000);
+        assert!(same[0].is_within(0.0), "{same:?}");
+        let different = super::null_test(
+            sr,
+            64,
+            2,
+            Seconds::from_samples(1000, sr as u64),
+            || {
+                graph_output(0, bus(1).set(0, 0.5));
+                graph_output(1, bus(1).set(0, 0.5));
+            },
+            || {
+                graph_output(0, bus(1).set(0, 0.5));
+                graph_output(1, bus(1).set(0, 0.4));
+            },
+        );
+        assert!(different[0].is_within(0.0), "{different:?}");
+        assert_eq!(different[1].num_samples, 1000);
+        assert_eq!(different[1].max_difference_at, 0);
+        assert!((different[1].max_difference - 0.1).abs() < 1e-6);
+        assert!((different[1].rms_difference - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn null_test_gen_measures_a_running_graph() {
+        let mut kt = super::KnystOffline::new(44100, 64, 0, 1);
+        let (node, meter) = crate::gen::null_test::null_test();
+        node.set(0, 1.0).set(1, 0.75);
+        graph_output(0, node);
+        kt.process_block();
+        kt.process_block();
+        assert_eq!(kt.output_channel(0).unwrap()[0], 0.25);
+        let report = meter.report();
+        assert_eq!(report.num_samples, 128);
+        assert_eq!(report.max_difference, 0.25);
+    }
+
+    #[test]
+    fn edit_batch_is_committed_when_the_closure_panics() {
+        let mut kt = super::KnystOffline::new(44100, 64, 0, 1);
+        let result = std::panic::catch_unwind(|| {
+            crate::controller::edit_batch(|| {
+                graph_output(0, bus(1).set(0, 1.0));
+                panic!("panic inside an edit batch");
+            })
+        });
+        assert!(result.is_err());
+        kt.process_block();
+        kt.process_block();
+        assert_eq!(kt.output_channel(0).unwrap()[0], 1.0);
+    }
+
+    #[test]
+    fn handle_trig_at_is_sample_accurate() {
+        let sr = 44100;
+        let mut kt = super::KnystOffline::new(sr, 64, 0, 1);
+        let node = bus(1);
+        graph_output(0, node);
+        node.trig_at(0, Time::Seconds(Seconds::from_samples(70, sr as u64)));
+        kt.process_block();
+        kt.process_block();
+        let o = kt.output_channel(0).unwrap();
+        assert_eq!(o[70 - 64], 1.0);
+        assert_eq!(o[69 - 64], 0.0);
+        assert_eq!(o[71 - 64], 0.0);
+    }
+
+    #[test]
+    fn handle_trig_at_in_bundle_keeps_its_time() {
+        let sr = 44100;
+        let mut kt = super::KnystOffline::new(sr, 64, 0, 1);
+        let node = bus(1);
+        graph_output(0, node);
+        schedule_bundle(Time::Seconds(Seconds::from_samples(10, sr as u64)), || {
+            node.trig(0);
+            node.trig_at(0, Time::Seconds(Seconds::from_samples(40, sr as u64)));
+        });
+        kt.process_block();
+        let o = kt.output_channel(0).unwrap();
+        let trigs: Vec<usize> = (0..64).filter(|i| o[*i] == 1.0).collect();
+        assert_eq!(trigs, vec![10, 40]);
+    }
+}
@@ -9,8 +9,8 @@ pub use crate::gen::{Gen, GenContext, GenState, StopAction};
 pub use crate::graph::{
     connection::constant,
     connection::{ConnectionBundle, InputBundle},
-    gen, Connection, Graph, GraphInput, GraphSettings, Mult, NodeId, ParameterChange,
-    RunGraphSettings,
+    gen, gen_with_state, Connection, Graph, GraphInput, GraphSettings, Mult, NodeId,
+    ParameterChange, RunGraphSettings,
 };
 pub use crate::handles::{
     bus, graph_input, graph_output, handle, GenericHandle, GraphHandle, Handle, HandleData,
@@ -19,7 +19,7 @@ pub use crate::inputs;
 pub use crate::modal_interface::knyst_commands;
 pub use crate::resources::{IdOrKey, WavetableId, WavetableKey};
 pub use crate::resources::{Resources, ResourcesSettings};
-pub use crate::sphere::{KnystSphere, SphereSettings};
+pub use crate::sphere::{KnystSphere, SingleThreadedSphere, SphereSettings};
 pub use crate::time::{Beats, Seconds};
 pub use crate::wavetable::{TABLE_POWER, TABLE_SIZE};
 pub use crate::wavetable_aa::Wavetable;
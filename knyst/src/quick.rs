@@ -0,0 +1,254 @@
+//! One-liners for quick sketches and acoustic tests, without setting up a
+//! backend and a [`KnystSphere`](crate::sphere::KnystSphere) first.
+//!
+//! - [`play`] starts the default audio backend and a
+//!   [`KnystSphere`](crate::sphere::KnystSphere) unless one is already
+//!   running, and then runs a closure which builds the graph.
+//! - [`play_buffer`] does the same and plays a sound file.
+//! - [`render_to_wav`] renders the graph built by a closure offline and saves
+//!   it to a wav file.
+//!
+//! ```no_run
+//! use knyst::prelude::*;
+//! use knyst::quick::{play, sine};
+//!
+//! play(|| graph_output(0, sine().freq(440.) * 0.2)).unwrap();
+//! // The sound stops when the program exits
+//! std::thread::sleep(std::time::Duration::from_secs(2));
+//! ```
+//!
+//! For anything more than that, e.g. choosing the backend or the number of
+//! channels, start a [`KnystSphere`](crate::sphere::KnystSphere) yourself.
+use std::path::{Path, PathBuf};
+
+#[cfg(any(feature = "cpal", feature = "jack"))]
+use crate::{
+    audio_backend::AudioBackendError,
+    buffer::{Buffer, BufferError},
+    controller::print_error_handler,
+    gen::{BufferReaderMulti, BufferReaderMultiHandle, StopAction},
+    handles::graph_output,
+    modal_interface::{
+        default_sphere_id, is_sphere_active, knyst_commands, set_active_sphere, SphereError,
+        SphereId,
+    },
+    prelude::KnystCommands,
+    sphere::{self, SphereSettings},
+};
+use crate::{
+    gen::{WavetableOscillatorOwned, WavetableOscillatorOwnedHandle},
+    handles::Handle,
+    offline::Score,
+    time::Seconds,
+    wavetable_aa::Wavetable,
+};
+
+/// The sample rate used by [`render_to_wav`]
+pub const RENDER_SAMPLE_RATE: usize = 44100;
+/// The number of channels rendered by [`render_to_wav`]
+pub const RENDER_CHANNELS: usize = 2;
+const RENDER_BLOCK_SIZE: usize = 64;
+
+/// Error from the functions in [`quick`](self)
+#[derive(thiserror::Error, Debug)]
+pub enum QuickError {
+    /// The default audio backend could not be started
+    #[cfg(any(feature = "cpal", feature = "jack"))]
+    #[error("Unable to start the audio backend: {0}")]
+    AudioBackend(#[from] AudioBackendError),
+    /// The [`KnystSphere`](crate::sphere::KnystSphere) could not be started
+    #[cfg(any(feature = "cpal", feature = "jack"))]
+    #[error("Unable to start a KnystSphere: {0}")]
+    Sphere(#[from] SphereError),
+    /// The sound file could not be loaded
+    #[cfg(any(feature = "cpal", feature = "jack"))]
+    #[error("Unable to load the sound file: {0}")]
+    Buffer(#[from] BufferError),
+    /// The wav file could not be written
+    #[error("Unable to write the wav file {path:?}: {error}")]
+    Wav {
+        /// The file that couldn't be written
+        path: PathBuf,
+        /// The error from writing the file
+        error: hound::Error,
+    },
+}
+
+/// Start the default audio backend (CPAL if enabled, otherwise JACK) and a
+/// [`KnystSphere`](crate::sphere::KnystSphere) outputting to it, unless a
+/// sphere is already active on this thread or the default sphere has been
+/// started on any thread. The backend stays open until the program exits.
+#[cfg(any(feature = "cpal", feature = "jack"))]
+pub fn start_default_sphere() -> Result<(), QuickError> {
+    // Keeps two threads calling this at the same time from both starting a
+    // backend
+    static STARTING: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _starting = STARTING.lock().unwrap_or_else(|poison| poison.into_inner());
+    if is_sphere_active() {
+        return Ok(());
+    }
+    if let Some(sphere_id) = default_sphere_id() {
+        set_active_sphere(sphere_id)?;
+        return Ok(());
+    }
+    // Backends are not necessarily `Send` so the backend is created on, and
+    // kept alive by, a thread of its own. Keeping it on the calling thread
+    // would stop the audio when that thread exits, while the sphere stays.
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut backend = match start_default_backend() {
+            Ok(backend) => backend,
+            Err(e) => {
+                sender.send(Err(e)).ok();
+                return;
+            }
+        };
+        match sphere::KnystSphere::start(
+            &mut backend,
+            SphereSettings::default(),
+            print_error_handler,
+        ) {
+            Ok(sphere_id) => {
+                sender.send(Ok(sphere_id)).ok();
+                loop {
+                    std::thread::park();
+                }
+            }
+            Err(e) => {
+                sender.send(Err(e.into())).ok();
+            }
+        }
+    });
+    let sphere_id: SphereId = receiver
+        .recv()
+        .expect("the thread starting the default backend panicked")?;
+    set_active_sphere(sphere_id)?;
+    Ok(())
+}
+
+#[cfg(feature = "cpal")]
+fn start_default_backend() -> Result<crate::audio_backend::CpalBackend, QuickError> {
+    crate::audio_backend::CpalBackend::new(Default::default()).map_err(QuickError::from)
+}
+#[cfg(all(feature = "jack", not(feature = "cpal")))]
+fn start_default_backend() -> Result<crate::audio_backend::JackBackend, QuickError> {
+    crate::audio_backend::JackBackend::new("knyst")
+        .map_err(|e| QuickError::from(AudioBackendError::from(e)))
+}
+
+/// Make sure there is a [`KnystSphere`](crate::sphere::KnystSphere) running,
+/// see [`start_default_sphere`], and run `build` to add nodes to it.
+///
+/// The closure is run straight away and its return value is returned, e.g. a
+/// [`Handle`] to change later. Keep the program running for as long as you
+/// want to hear the result.
+#[cfg(any(feature = "cpal", feature = "jack"))]
+pub fn play<R>(build: impl FnOnce() -> R) -> Result<R, QuickError> {
+    start_default_sphere()?;
+    Ok(build())
+}
+
+/// Make sure there is a [`KnystSphere`](crate::sphere::KnystSphere) running,
+/// see [`start_default_sphere`], and play the sound file at `path` once from
+/// the start. Mono files are played on the first two outputs.
+///
+/// The file is loaded on the current thread before playback starts. The node
+/// playing the file frees itself when the file has been played.
+#[cfg(any(feature = "cpal", feature = "jack"))]
+pub fn play_buffer(path: impl AsRef<Path>) -> Result<Handle<BufferReaderMultiHandle>, QuickError> {
+    start_default_sphere()?;
+    let buffer = Buffer::from_sound_file(path.as_ref())?;
+    let num_channels = buffer.num_channels();
+    let mut commands = knyst_commands();
+    let num_outputs = commands.default_graph_settings().num_outputs;
+    let buffer = commands.insert_buffer(buffer);
+    let reader = BufferReaderMulti::new(buffer, 1.0, StopAction::FreeSelf)
+        .channels(num_channels)
+        .upload();
+    graph_output(0, reader.channels(num_channels.max(2).min(num_outputs)));
+    Ok(reader)
+}
+
+/// Render `duration` of the graph built by `build` offline and save it to a
+/// 32 bit float wav file at `path`, with [`RENDER_CHANNELS`] channels at
+/// [`RENDER_SAMPLE_RATE`]. Doesn't need an audio backend.
+///
+/// The rendering uses a separate
+/// [`KnystOffline`](crate::offline::KnystOffline) sphere which is removed
+/// afterwards. For more control, render a [`Score`] instead.
+///
+/// ```
+/// use knyst::prelude::*;
+/// use knyst::quick::{render_to_wav, sine};
+///
+/// let path = std::env::temp_dir().join("knyst_quick_doc.wav");
+/// render_to_wav(Seconds::from_seconds_f64(0.5), &path, || {
+///     graph_output(0, sine().freq(440.) * 0.2);
+/// })
+/// .unwrap();
+/// # std::fs::remove_file(path).ok();
+/// ```
+pub fn render_to_wav(
+    duration: Seconds,
+    path: impl AsRef<Path>,
+    build: impl FnOnce() + 'static,
+) -> Result<(), QuickError> {
+    let output = Score::new().at(Seconds::ZERO, build).render(
+        RENDER_SAMPLE_RATE,
+        RENDER_BLOCK_SIZE,
+        RENDER_CHANNELS,
+        duration,
+    );
+    let path = path.as_ref();
+    let wav_error = |error| QuickError::Wav {
+        path: path.to_owned(),
+        error,
+    };
+    let spec = hound::WavSpec {
+        channels: RENDER_CHANNELS as u16,
+        sample_rate: RENDER_SAMPLE_RATE as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(wav_error)?;
+    let num_frames = output.first().map_or(0, |channel| channel.len());
+    for frame in 0..num_frames {
+        for channel in &output {
+            writer.write_sample(channel[frame]).map_err(wav_error)?;
+        }
+    }
+    writer.finalize().map_err(wav_error)
+}
+
+/// Upload a sine oscillator to the current graph. Set its frequency using
+/// `.freq()`.
+pub fn sine() -> Handle<WavetableOscillatorOwnedHandle> {
+    WavetableOscillatorOwned::new(Wavetable::sine()).upload()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_to_wav, sine, RENDER_CHANNELS, RENDER_SAMPLE_RATE};
+    use crate::prelude::*;
+
+    #[test]
+    fn render_to_wav_writes_every_channel() {
+        let path = std::env::temp_dir().join("knyst_render_to_wav_test.wav");
+        render_to_wav(Seconds::from_seconds_f64(0.1), &path, || {
+            graph_output(0, sine().freq(441.) * 0.5);
+            graph_output(1, bus(1).set(0, 0.25));
+        })
+        .unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.sample_rate as usize, RENDER_SAMPLE_RATE);
+        assert_eq!(spec.channels as usize, RENDER_CHANNELS);
+        let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(samples.len(), 4410 * RENDER_CHANNELS);
+        let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+        let peak = left.iter().fold(0.0_f32, |acc, s| acc.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.01, "{peak}");
+        assert!(samples.iter().skip(1).step_by(2).all(|s| *s == 0.25));
+    }
+}
@@ -8,7 +8,7 @@ use crate::gen::Gen;
 use core::fmt::Debug;
 use downcast_rs::{impl_downcast, Downcast};
 use slotmap::{new_key_type, SecondaryMap, SlotMap};
-use std::{collections::HashMap, hash::Hash, sync::atomic::AtomicU64};
+use std::{collections::HashMap, hash::Hash, sync::atomic::AtomicU64, time::Duration};
 
 use crate::{
     buffer::{Buffer, BufferKey},
@@ -206,6 +206,13 @@ pub enum ResourcesCommand {
         id: WavetableId,
         wavetable: Wavetable,
     },
+    /// Fade out the output of the [`RunGraph`](crate::graph::RunGraph) over
+    /// `fade_out` and then send all buffers and wavetables back, followed by
+    /// [`ResourcesResponse::ShutdownComplete`]. The output stays silent after
+    /// the fade.
+    Shutdown {
+        fade_out: Duration,
+    },
 }
 
 /// Response to a [`ResourcesCommand`]. Usually used to send anything that
@@ -218,6 +225,23 @@ pub enum ResourcesResponse {
     InsertWavetable(Result<WavetableKey, ResourcesError>),
     RemoveWavetable(Result<Option<Wavetable>, ResourcesError>),
     ReplaceWavetable(Result<Wavetable, ResourcesError>),
+    ReturnBuffer(BufferId, Buffer),
+    ReturnWavetable(WavetableId, Wavetable),
+    ShutdownComplete,
+}
+
+/// The buffers and wavetables returned by a [`Resources`] when it is shut
+/// down, e.g. through [`KnystSphere::stop`](crate::sphere::KnystSphere::stop).
+/// They can be inserted into a new sphere without having to be loaded or
+/// generated again.
+#[derive(Default)]
+#[non_exhaustive]
+pub struct ReturnedResources {
+    /// All buffers with the ids they were inserted with
+    pub buffers: Vec<(BufferId, Buffer)>,
+    /// All wavetables with the ids they were inserted with, except for the
+    /// default wavetables which every [`Resources`] contains.
+    pub wavetables: Vec<(WavetableId, Wavetable)>,
 }
 
 impl Resources {
@@ -288,8 +312,37 @@ impl Resources {
                     )),
                 }
             }
+            // The fade out is done by the RunGraph which then returns the
+            // resources using `take_any_buffer` and `take_any_wavetable`
+            ResourcesCommand::Shutdown { .. } => ResourcesResponse::ShutdownComplete,
         }
     }
+    /// Remove any buffer and return it together with its id. Does not
+    /// allocate or deallocate.
+    pub(crate) fn take_any_buffer(&mut self) -> Option<(BufferId, Buffer)> {
+        let key = self.buffers.keys().next()?;
+        let buffer = self.buffers.remove(key)?;
+        let id = match self.buffer_ids.remove(key) {
+            Some(id) => id,
+            None => BufferId::new(&buffer),
+        };
+        Some((id, buffer))
+    }
+    /// Remove any wavetable, except for the default wavetables, and return it
+    /// together with its id. Does not allocate or deallocate.
+    pub(crate) fn take_any_wavetable(&mut self) -> Option<(WavetableId, Wavetable)> {
+        let (key, id) =
+            self.wavetables
+                .keys()
+                .find_map(|key| match self.wavetable_ids.get(key) {
+                    Some(&id) if id == WavetableId::cos() => None,
+                    Some(&id) => Some((key, id)),
+                    None => Some((key, WavetableId::new())),
+                })?;
+        self.wavetable_ids.remove(key);
+        let wavetable = self.wavetables.remove(key)?;
+        Some((id, wavetable))
+    }
     /// Return a Buffer if the key is valid.
     pub fn buffer(&self, key: BufferKey) -> Option<&Buffer> {
         self.buffers.get(key)
@@ -1,17 +1,30 @@
 //! A [`KnystSphere`] contains one instance of Knyst running on a backend. You can
 //! create multiple [`KnystSphere`]s in one program and switch between them using
 //! [`set_active_sphere`], but most use cases require only one [`KnystSphere`].
+//!
+//! If you are integrating Knyst into an engine with its own audio callback and
+//! scheduler, use a [`SingleThreadedSphere`] instead. It does not spawn any
+//! threads; the engine renders audio through the returned [`RunGraph`] and
+//! pumps the commands using [`SingleThreadedSphere::update`].
+//!
+//! A running [`KnystSphere`] can be stopped using [`KnystSphere::stop`], which
+//! fades out the output, stops the helper thread and the backend and returns
+//! all buffers and wavetables so that a new sphere can be started with them.
 
-use crate::controller::Controller;
+use crate::audio_backend::OutputChannelMap;
 #[allow(unused)]
 use crate::controller::KnystCommands;
+use crate::controller::{Controller, ControllerThread, DEFAULT_MAX_COMMANDS_BEFORE_UPDATE};
 use crate::KnystError;
-use crate::{resources::ResourcesSettings, Resources, Sample};
+use crate::{
+    resources::{ResourcesSettings, ReturnedResources},
+    Resources, Sample,
+};
 use std::time::Duration;
 
 use crate::{
-    graph::{Graph, GraphSettings, RunGraphSettings},
-    modal_interface::{register_sphere, set_active_sphere, SphereError, SphereId},
+    graph::{Graph, GraphSettings, RunGraph, RunGraphSettings},
+    modal_interface::{register_sphere, remove_sphere, set_active_sphere, SphereError, SphereId},
     prelude::{AudioBackend, MultiThreadedKnystCommands},
 };
 
@@ -20,8 +33,14 @@ pub struct KnystSphere {
     #[allow(unused)]
     name: String,
     knyst_commands: MultiThreadedKnystCommands,
+    /// The thread running the Controller, if the sphere started it
+    controller_thread: Option<ControllerThread>,
 }
 
+/// The duration of the fade out when a running [`KnystSphere`] is dropped
+/// without being stopped through [`KnystSphere::stop`].
+pub const DROP_FADE_OUT: Duration = Duration::from_millis(20);
+
 impl KnystSphere {
     /// Create a graph matching the settings and the backend and start processing with a helper thread.
     pub fn start<B: AudioBackend>(
@@ -30,20 +49,29 @@ impl KnystSphere {
         error_handler: impl FnMut(KnystError) + Send + 'static,
     ) -> Result<SphereId, SphereError> {
         let resources = Resources::new(settings.resources_settings);
+        if let Some(map) = &settings.output_channel_map {
+            backend.set_output_channel_map(map.clone())?;
+        }
         let graph_settings = GraphSettings {
             name: settings.name.clone(),
             num_inputs: backend
                 .native_input_channels()
                 .unwrap_or(settings.num_inputs),
-            num_outputs: backend
-                .native_output_channels()
-                .unwrap_or(settings.num_outputs),
+            num_outputs: match &settings.output_channel_map {
+                Some(map) => map.num_graph_outputs(),
+                None => backend
+                    .native_output_channels()
+                    .unwrap_or(settings.num_outputs),
+            },
             block_size: backend.block_size().unwrap_or(64),
             sample_rate: backend.sample_rate() as Sample,
+            dezipper: settings.dezipper,
+            max_nodes: settings.max_nodes,
+            max_connections: settings.max_connections,
             ..Default::default()
         };
         let graph: Graph = Graph::new(graph_settings);
-        let k = backend.start_processing(
+        let controller = backend.start_processing_return_controller(
             graph,
             resources,
             RunGraphSettings {
@@ -51,9 +79,11 @@ impl KnystSphere {
             },
             Box::new(error_handler),
         )?;
+        let (k, controller_thread) = controller.start_on_new_thread_with_handle();
         let s = Self {
             name: settings.name,
             knyst_commands: k,
+            controller_thread: Some(controller_thread),
         };
         // Add the sphere to the global list of spheres
         let sphere_id = register_sphere(s)?;
@@ -69,17 +99,26 @@ impl KnystSphere {
         error_handler: impl FnMut(KnystError) + Send + 'static,
     ) -> Result<(SphereId, Controller), SphereError> {
         let resources = Resources::new(settings.resources_settings);
+        if let Some(map) = &settings.output_channel_map {
+            backend.set_output_channel_map(map.clone())?;
+        }
         let graph_settings = GraphSettings {
             name: settings.name.clone(),
             num_inputs: backend
                 .native_input_channels()
                 .unwrap_or(settings.num_inputs),
-            num_outputs: backend
-                .native_output_channels()
-                .unwrap_or(settings.num_outputs),
+            num_outputs: match &settings.output_channel_map {
+                Some(map) => map.num_graph_outputs(),
+                None => backend
+                    .native_output_channels()
+                    .unwrap_or(settings.num_outputs),
+            },
             block_size: backend.block_size().unwrap_or(64),
             sample_rate: backend.sample_rate() as Sample,
             ring_buffer_size: settings.scheduling_ring_buffer_capacity,
+            dezipper: settings.dezipper,
+            max_nodes: settings.max_nodes,
+            max_connections: settings.max_connections,
             ..Default::default()
         };
         let graph: Graph = Graph::new(graph_settings);
@@ -94,6 +133,7 @@ impl KnystSphere {
         let s = Self {
             name: settings.name,
             knyst_commands: controller.get_knyst_commands(),
+            controller_thread: None,
         };
         // Add the sphere to the global list of spheres
         let sphere_id = register_sphere(s)?;
@@ -106,6 +146,161 @@ impl KnystSphere {
     pub fn commands(&self) -> MultiThreadedKnystCommands {
         self.knyst_commands.clone()
     }
+    /// Stop a sphere started using [`KnystSphere::start`] on `backend`. The
+    /// output is faded out over `fade_out`, after which the Controller thread
+    /// and the backend are stopped. If the sphere was active, the next
+    /// remaining sphere is made active.
+    ///
+    /// Returns all buffers and wavetables that were in the sphere so that they
+    /// can be inserted into a new sphere. If the Controller was returned to
+    /// the user through [`KnystSphere::start_return_controller`] no resources
+    /// are returned; use [`Controller::shutdown`] instead before stopping the
+    /// sphere.
+    pub fn stop<B: AudioBackend>(
+        sphere_id: SphereId,
+        backend: &mut B,
+        fade_out: Duration,
+    ) -> Result<ReturnedResources, SphereError> {
+        let mut sphere = remove_sphere(sphere_id)?;
+        let returned_resources = match sphere.controller_thread.take() {
+            Some(controller_thread) => controller_thread.stop().shutdown(fade_out),
+            None => ReturnedResources::default(),
+        };
+        backend.stop()?;
+        Ok(returned_resources)
+    }
+}
+
+impl Drop for KnystSphere {
+    fn drop(&mut self) {
+        if let Some(controller_thread) = self.controller_thread.take() {
+            // Panicking in drop while the Controller thread has already
+            // panicked would abort, so a panicked thread is left as it is.
+            if let Ok(mut controller) = controller_thread.try_stop() {
+                controller.shutdown(DROP_FADE_OUT);
+            }
+        }
+    }
+}
+
+/// A [`KnystSphere`] without any helper thread, for integrating Knyst into
+/// game engines and other environments with their own scheduling.
+///
+/// Commands sent through [`knyst_commands`](crate::knyst_commands) are
+/// applied to the graph when [`SingleThreadedSphere::update`] is called, e.g.
+/// once per frame from the main thread of the engine. Audio is rendered by
+/// calling [`RunGraph::run_resources_communication`] and
+/// [`RunGraph::process_block`] from the audio callback provided by the engine.
+/// Neither of the two ever blocks the other.
+///
+/// The sphere is removed when the [`SingleThreadedSphere`] is dropped.
+///
+/// ```
+/// # use knyst::prelude::*;
+/// # use knyst::controller::print_error_handler;
+/// let (mut sphere, mut run_graph) =
+///     SingleThreadedSphere::start(44100, 64, SphereSettings::default(), print_error_handler)
+///         .unwrap();
+/// let sig = oscillator(WavetableId::cos()).freq(440.);
+/// graph_output(0, sig);
+/// // In the game loop
+/// sphere.update();
+/// // In the audio callback
+/// run_graph.run_resources_communication(50);
+/// run_graph.process_block();
+/// let output = run_graph.graph_output_buffers().get_channel(0);
+/// # assert_eq!(output.len(), 64);
+/// ```
+pub struct SingleThreadedSphere {
+    controller: Controller,
+    sphere_id: SphereId,
+}
+
+impl SingleThreadedSphere {
+    /// Create a graph matching the settings and register it as the active
+    /// sphere. Returns the [`SingleThreadedSphere`] for applying commands and
+    /// the [`RunGraph`] for rendering audio, which can be moved to the audio
+    /// callback.
+    pub fn start(
+        sample_rate: usize,
+        block_size: usize,
+        settings: SphereSettings,
+        error_handler: impl FnMut(KnystError) + Send + 'static,
+    ) -> Result<(Self, RunGraph), SphereError> {
+        let resources = Resources::new(settings.resources_settings);
+        let graph_settings = GraphSettings {
+            name: settings.name.clone(),
+            num_inputs: settings.num_inputs,
+            num_outputs: settings.num_outputs,
+            block_size,
+            sample_rate: sample_rate as Sample,
+            ring_buffer_size: settings.scheduling_ring_buffer_capacity,
+            dezipper: settings.dezipper,
+            max_nodes: settings.max_nodes,
+            max_connections: settings.max_connections,
+            ..Default::default()
+        };
+        let mut graph: Graph = Graph::new(graph_settings);
+        let (run_graph, resources_command_sender, resources_command_receiver) = RunGraph::new(
+            &mut graph,
+            resources,
+            RunGraphSettings {
+                scheduling_latency: settings.scheduling_latency,
+            },
+        )?;
+        let controller = Controller::new(
+            graph,
+            error_handler,
+            resources_command_sender,
+            resources_command_receiver,
+        );
+        let s = KnystSphere {
+            name: settings.name,
+            knyst_commands: controller.get_knyst_commands(),
+            controller_thread: None,
+        };
+        let sphere_id = register_sphere(s)?;
+        set_active_sphere(sphere_id)?;
+        Ok((
+            Self {
+                controller,
+                sphere_id,
+            },
+            run_graph,
+        ))
+    }
+    /// Apply all pending commands to the graph and run maintenance, sending
+    /// the changes to the [`RunGraph`]. Call this regularly, e.g. once per
+    /// frame, from the thread that owns the [`SingleThreadedSphere`].
+    ///
+    /// Returns true if all pending commands were applied.
+    pub fn update(&mut self) -> bool {
+        self.update_with_limit(DEFAULT_MAX_COMMANDS_BEFORE_UPDATE)
+    }
+    /// Like [`SingleThreadedSphere::update`], but apply at most
+    /// `max_commands` commands before running maintenance. Useful for
+    /// limiting the time spent per frame.
+    ///
+    /// Returns true if all pending commands were applied.
+    pub fn update_with_limit(&mut self, max_commands: usize) -> bool {
+        self.controller.run(max_commands)
+    }
+    /// Returns the [`SphereId`] of this sphere
+    #[must_use]
+    pub fn sphere_id(&self) -> SphereId {
+        self.sphere_id
+    }
+    /// Return an object implementing [`KnystCommands`]
+    #[must_use]
+    pub fn commands(&self) -> MultiThreadedKnystCommands {
+        self.controller.get_knyst_commands()
+    }
+}
+
+impl Drop for SingleThreadedSphere {
+    fn drop(&mut self) {
+        remove_sphere(self.sphere_id).ok();
+    }
 }
 
 /// Settings pertaining to a sphere
@@ -122,10 +317,75 @@ pub struct SphereSettings {
     /// If the [`AudioBackend`]
     /// provides a native number of outputs that number is chosen instead.
     pub num_outputs: usize,
+    /// If set, routes the outputs of the top level graph to the channels of
+    /// the [`AudioBackend`], and the graph gets
+    /// [`OutputChannelMap::num_graph_outputs`] outputs. Starting the sphere
+    /// fails if the backend doesn't support the map. Not used by
+    /// [`SingleThreadedSphere`] which has no backend.
+    pub output_channel_map: Option<OutputChannelMap>,
     /// The latency added for time scheduled changes to the audio thread to allow enough time for events to take place.
     pub scheduling_latency: Duration,
     /// The capacity of the ring buffer transferring changes to constant inputs to the audio thread.
     pub scheduling_ring_buffer_capacity: usize,
+    /// If set, changes to input constants in the top level graph are interpolated over this duration. See [`GraphSettings::dezipper`].
+    pub dezipper: Option<Duration>,
+    /// If set, limits the number of nodes in the top level graph. See [`GraphSettings::max_nodes`].
+    pub max_nodes: Option<usize>,
+    /// If set, limits the number of connections in the top level graph. See [`GraphSettings::max_connections`].
+    pub max_connections: Option<usize>,
+}
+
+impl SphereSettings {
+    /// Set the name to a new value
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+    /// Set the resources_settings to a new value
+    pub fn resources_settings(mut self, resources_settings: ResourcesSettings) -> Self {
+        self.resources_settings = resources_settings;
+        self
+    }
+    /// Set the num_inputs to a new value
+    pub fn num_inputs(mut self, num_inputs: usize) -> Self {
+        self.num_inputs = num_inputs;
+        self
+    }
+    /// Set the num_outputs to a new value
+    pub fn num_outputs(mut self, num_outputs: usize) -> Self {
+        self.num_outputs = num_outputs;
+        self
+    }
+    /// Route the outputs of the top level graph, see [`SphereSettings::output_channel_map`]
+    pub fn output_channel_map(mut self, output_channel_map: OutputChannelMap) -> Self {
+        self.output_channel_map = Some(output_channel_map);
+        self
+    }
+    /// Set the scheduling_latency to a new value
+    pub fn scheduling_latency(mut self, scheduling_latency: Duration) -> Self {
+        self.scheduling_latency = scheduling_latency;
+        self
+    }
+    /// Set the scheduling_ring_buffer_capacity to a new value
+    pub fn scheduling_ring_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.scheduling_ring_buffer_capacity = capacity;
+        self
+    }
+    /// Interpolate changes to input constants over `duration`, see [`SphereSettings::dezipper`]
+    pub fn dezipper(mut self, duration: Duration) -> Self {
+        self.dezipper = Some(duration);
+        self
+    }
+    /// Limit the number of nodes in the top level graph, see [`SphereSettings::max_nodes`]
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+    /// Limit the number of connections in the top level graph, see [`SphereSettings::max_connections`]
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
 }
 
 impl Default for SphereSettings {
@@ -136,7 +396,42 @@ impl Default for SphereSettings {
             scheduling_latency: Duration::from_millis(100),
             num_inputs: 2,
             num_outputs: 2,
+            output_channel_map: None,
             scheduling_ring_buffer_capacity: 1000,
+            dezipper: None,
+            max_nodes: None,
+            max_connections: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SingleThreadedSphere, SphereSettings};
+    use crate::controller::print_error_handler;
+    use crate::prelude::*;
+
+    #[test]
+    fn single_threaded_sphere_applies_commands_on_update() {
+        let (mut sphere, mut run_graph) = SingleThreadedSphere::start(
+            44100,
+            64,
+            SphereSettings {
+                num_inputs: 0,
+                num_outputs: 1,
+                ..Default::default()
+            },
+            print_error_handler,
+        )
+        .unwrap();
+        graph_output(0, bus(1).set(0, 2.0));
+        run_graph.run_resources_communication(50);
+        run_graph.process_block();
+        // Nothing is applied until the sphere is updated
+        assert_eq!(run_graph.graph_output_buffers().get_channel(0), [0.0; 64]);
+        assert!(sphere.update());
+        run_graph.run_resources_communication(50);
+        run_graph.process_block();
+        assert_eq!(run_graph.graph_output_buffers().get_channel(0), [2.0; 64]);
+    }
+}
@@ -0,0 +1,58 @@
+//! The states a `Gen` can return from its process function to tell the
+//! `Graph` it is in whether to keep running it or free it.
+
+/// The Gen should return Continue unless it needs to free itself or the Graph it is in.
+///
+/// No promise is made as to when the node or the Graph will be freed so the Node needs to do the right thing
+/// if run again the next block. E.g. a node returning `FreeSelfMendConnections` is expected to act as a
+/// connection bridge from its non constant inputs to its outputs as if it weren't there. Only inputs with a
+/// corresponding output should be passed through, e.g. in\[0\] -> out\[0\], in\[1\] -> out\[1\], in\[2..5\] go nowhere.
+///
+/// The FreeGraph and FreeGraphMendConnections values also return the relative
+/// sample in the current block after which the graph should return 0 or connect
+/// its non constant inputs to its outputs.
+#[derive(Debug, Clone, Copy)]
+pub enum GenState {
+    /// Continue running
+    Continue,
+    /// Free the node containing the Gen
+    FreeSelf,
+    /// Free the node containing the Gen, bridging its input node(s) to its output node(s).
+    FreeSelfMendConnections,
+    /// Free the graph containing the node containing the Gen.
+    FreeGraph(usize),
+    /// Free the graph containing the node containing the Gen, bridging its input node(s) to its output node(s).
+    FreeGraphMendConnections(usize),
+}
+
+/// Specify what happens when a `Gen` is done with its processing. This translates to a [`GenState`] being returned from the `Gen`, but without additional parameters.
+#[derive(Debug, Clone, Copy)]
+pub enum StopAction {
+    /// Continue running
+    Continue,
+    /// Free the node containing the Gen
+    FreeSelf,
+    /// Free the node containing the Gen, bridging its input node(s) to its output node(s).
+    FreeSelfMendConnections,
+    /// Free the graph containing the node containing the Gen.
+    FreeGraph,
+    /// Free the graph containing the node containing the Gen, bridging its input node(s) to its output node(s).
+    FreeGraphMendConnections,
+}
+impl StopAction {
+    /// Convert the [`StopAction`] into a [`GenState`].
+    ///
+    /// `stop_sample` is only used for the `FreeGraph` and
+    /// `FreeGraphMendConnections` variants to communicate from what sample time
+    /// the graph outputs should be 0.
+    #[must_use]
+    pub fn to_gen_state(&self, stop_sample: usize) -> GenState {
+        match self {
+            StopAction::Continue => GenState::Continue,
+            StopAction::FreeSelf => GenState::FreeSelf,
+            StopAction::FreeSelfMendConnections => GenState::FreeSelfMendConnections,
+            StopAction::FreeGraph => GenState::FreeGraph(stop_sample),
+            StopAction::FreeGraphMendConnections => GenState::FreeGraphMendConnections(stop_sample),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! # Knyst core
+//!
+//! The types shared by [Knyst](https://github.com/ErikNatanael/knyst) and its
+//! Gens: the sample types, [`wavetable::Wavetable`], [`xorrng::XOrShift32Rng`]
+//! and the states a Gen can return. Only `alloc` is required.
+//!
+//! This crate does not make Knyst itself usable without `std`. The `Gen`
+//! trait, `Graph` and `Resources` are in `knyst`, which requires `std`.
+//!
+//! Everything in this crate is re-exported from `knyst` under the same paths,
+//! so you only need to depend on `knyst_core` directly when building for a
+//! target without `std`.
+//!
+//! ## Features
+//!
+//! - *std*: (default) Use the float math from `std`. When disabled, `libm` is used instead.
+//!
+#![cfg_attr(not(feature = "std"), no_std)]
+#![deny(rustdoc::broken_intra_doc_links)] // error if there are broken intra-doc links
+#![warn(missing_docs)]
+
+extern crate alloc;
+
+use core::ops::{Deref, DerefMut};
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+pub mod gen;
+pub mod wavetable;
+pub mod xorrng;
+
+/// Convert db to amplitude
+#[inline]
+#[must_use]
+pub fn db_to_amplitude(db: Sample) -> Sample {
+    (10.0 as Sample).powf(db / 20.0)
+}
+/// Convert amplitude to db
+#[inline]
+#[must_use]
+pub fn amplitude_to_db(amplitude: Sample) -> Sample {
+    20.0 * amplitude.log10()
+}
+
+/// The current sample type used throughout Knyst
+pub type Sample = f32;
+
+/// Marker for inputs that are trigs. This makes it possible to set that value correctly through a Handle.
+pub type Trig = Sample;
+
+/// Newtype for a sample rate to identify it in function signatures. Derefs to a `Sample` for easy use on the audio thread.
+#[derive(Copy, Clone, Debug)]
+pub struct SampleRate(pub Sample);
+
+impl SampleRate {
+    #[inline(always)]
+    #[allow(missing_docs)]
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64
+    }
+    #[allow(missing_docs)]
+    #[inline(always)]
+    pub fn to_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Deref for SampleRate {
+    type Target = Sample;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SampleRate {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<SampleRate> for f64 {
+    fn from(value: SampleRate) -> Self {
+        value.0 as f64
+    }
+}
+
+impl From<f32> for SampleRate {
+    fn from(value: f32) -> Self {
+        Self(value as Sample)
+    }
+}
+impl From<f64> for SampleRate {
+    fn from(value: f64) -> Self {
+        Self(value as Sample)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+/// BlockSize.
+///
+/// Can be an unorthodox block size value in the event of a partial block at the beginning of a node's existence in the graph.
+pub struct BlockSize(pub usize);
+
+impl From<BlockSize> for usize {
+    #[inline(always)]
+    fn from(value: BlockSize) -> Self {
+        value.0
+    }
+}
+impl From<usize> for BlockSize {
+    fn from(value: usize) -> Self {
+        Self(value)
+    }
+}
+
+impl Deref for BlockSize {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BlockSize {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
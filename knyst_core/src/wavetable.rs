@@ -2,9 +2,11 @@
 
 use crate::Sample;
 
-// use std::f64::consts::PI;
 use crate::xorrng::XOrShift32Rng;
-use std::f64::consts::PI;
+use alloc::{format, string::String, vec, vec::Vec};
+use core::f64::consts::PI;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
 
 /// Decides the number of samples per [`Wavetable`] buffer, and therefore also
 /// the number of high bits used for the phase indexing into the wavetable. With
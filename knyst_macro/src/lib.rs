@@ -1,7 +1,7 @@
 use proc_macro2::{Ident, Span};
 use quote::{format_ident, quote};
 use syn::{
-    parse::Parse, parse_macro_input, punctuated::Punctuated, spanned::Spanned, ExprAssign,
+    parse::Parse, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Expr, ExprAssign,
     ExprPath, FnArg, ImplItem, ImplItemFn, ItemImpl, Meta, Pat, PatIdent, PatType, Path, Result,
     ReturnType, Token, Type, TypePath,
 };
@@ -26,6 +26,10 @@ struct ProcessData {
     inputs: Vec<Ident>,
     outputs: Vec<Ident>,
     parameters: Vec<Parameter>,
+    /// Default values of inputs set through `#[default(value)]`, by input index
+    input_defaults: Vec<(usize, Expr)>,
+    /// Inputs opting out of dezippering through `#[dezipper(false)]`, by input index
+    input_dezippers: Vec<(usize, Expr)>,
 }
 
 struct ArgData {
@@ -201,6 +205,8 @@ impl GenImplData {
             inputs,
             outputs,
             parameters,
+            input_defaults,
+            input_dezippers,
         } = process_data;
         let ArgData { range } = arg_data;
         let init_function = if let Some(init_data) = init_data {
@@ -224,6 +230,33 @@ impl GenImplData {
             let name_string = name.to_string();
             quote! { #i => #name_string, }
         });
+        // Only override the default implementations if any input attributes were set
+        let input_default_function = (!input_defaults.is_empty()).then(|| {
+            let match_input_defaults = input_defaults.iter().map(|(i, value)| {
+                quote! { #i => #value, }
+            });
+            quote! {
+                fn input_default(&self, input: usize) -> knyst::Sample {
+                    match input {
+                        #(#match_input_defaults)*
+                        _ => 0.0,
+                    }
+                }
+            }
+        });
+        let input_dezipper_function = (!input_dezippers.is_empty()).then(|| {
+            let match_input_dezippers = input_dezippers.iter().map(|(i, value)| {
+                quote! { #i => #value, }
+            });
+            quote! {
+                fn input_dezipper(&self, input: usize) -> bool {
+                    match input {
+                        #(#match_input_dezippers)*
+                        _ => true,
+                    }
+                }
+            }
+        });
         let extract_inputs = inputs.iter().enumerate().map(|(i, ident)| {
             let ident = Ident::new(&format!("__impl_gen_{ident}"), Span::call_site());
             quote! { let #ident = inputs.get_channel(#i); }
@@ -355,6 +388,8 @@ impl GenImplData {
                             _ => ""
                         }
                     }
+                    #input_default_function
+                    #input_dezipper_function
                     #init_function
                     fn name(&self) -> &'static str {
                         #type_name_string
@@ -448,6 +483,7 @@ impl Parse for GenImplData {
 
         for item in &mut item_impl.items {
             if let ImplItem::Fn(ref mut impl_item_fn) = item {
+                let input_attributes = take_input_attributes(impl_item_fn)?;
                 let mut remove_attributes = vec![];
                 // Does this function have an attribute we recognise?
                 let mut handled_through_attribute = false;
@@ -463,7 +499,8 @@ impl Parse for GenImplData {
                                         ));
                                     }
                                     remove_attributes.push(attr_i);
-                                    process_data = Some(parse_process_fn(impl_item_fn)?);
+                                    process_data =
+                                        Some(parse_process_fn(impl_item_fn, &input_attributes)?);
                                     handled_through_attribute = true;
                                 }
                                 "init" => {
@@ -507,7 +544,7 @@ impl Parse for GenImplData {
                                     "Multiple process functions in impl.",
                                 ));
                             }
-                            process_data = Some(parse_process_fn(impl_item_fn)?);
+                            process_data = Some(parse_process_fn(impl_item_fn, &input_attributes)?);
                         }
                         "init" => {
                             if init_data.is_some() {
@@ -530,6 +567,20 @@ impl Parse for GenImplData {
                         _ => (),
                     }
                 }
+                let is_process_fn = process_data
+                    .as_ref()
+                    .is_some_and(|data| data.fn_name == impl_item_fn.sig.ident);
+                if let Some(attribute) = input_attributes.first() {
+                    if !is_process_fn {
+                        return Err(syn::Error::new(
+                            attribute.kind.span(),
+                            format!(
+                                "#[{}] is only supported in the process method",
+                                attribute.kind
+                            ),
+                        ));
+                    }
+                }
             }
         }
 
@@ -580,10 +631,61 @@ struct Parameter {
     ident: Ident,
 }
 
-fn parse_process_fn(impl_item_fn: &ImplItemFn) -> Result<ProcessData> {
+/// The names of the attributes which can be set on inputs
+const INPUT_ATTRIBUTES: [&str; 2] = ["default", "dezipper"];
+
+/// An attribute on a parameter, e.g. `#[default(440.)]`
+struct InputAttribute {
+    /// The name of the parameter
+    input: Ident,
+    /// The name of the attribute, one of [`INPUT_ATTRIBUTES`]
+    kind: Ident,
+    value: Expr,
+}
+
+/// Remove the input attributes from the parameters of a method and return them
+fn take_input_attributes(impl_item_fn: &mut ImplItemFn) -> Result<Vec<InputAttribute>> {
+    let mut attributes = vec![];
+    for arg in &mut impl_item_fn.sig.inputs {
+        if let FnArg::Typed(param) = arg {
+            let mut error = None;
+            param.attrs.retain(|attr| {
+                let Some(kind) = attr.path().get_ident() else {
+                    return true;
+                };
+                if !INPUT_ATTRIBUTES.iter().any(|name| kind == name) {
+                    return true;
+                }
+                match (&*param.pat, attr.parse_args::<Expr>()) {
+                    (Pat::Ident(PatIdent { ident, .. }), Ok(value)) => {
+                        attributes.push(InputAttribute {
+                            input: ident.clone(),
+                            kind: kind.clone(),
+                            value,
+                        })
+                    }
+                    (_, Err(e)) => error = Some(e),
+                    _ => error = Some(syn::Error::new(attr.span(), "Unsupported param")),
+                }
+                false
+            });
+            if let Some(e) = error {
+                return Err(e);
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+fn parse_process_fn(
+    impl_item_fn: &ImplItemFn,
+    input_attributes: &[InputAttribute],
+) -> Result<ProcessData> {
     let mut inputs = vec![];
     let mut outputs = vec![];
     let mut parameters = vec![];
+    let mut input_defaults = vec![];
+    let mut input_dezippers = vec![];
 
     let ReturnType::Type(_, return_type) = &impl_item_fn.sig.output else {
         return Err(syn::Error::new(
@@ -614,8 +716,25 @@ fn parse_process_fn(impl_item_fn: &ImplItemFn) -> Result<ProcessData> {
                 return Err(syn::Error::new(param.span(), "Unsupported param"));
             };
             let parameter = parse_parameter(param, name)?;
+            let mut attributes = input_attributes.iter().filter(|a| a.input == *name);
             match parameter._ty {
-                ParameterTy::Input | ParameterTy::InputTrig => inputs.push(parameter.ident.clone()),
+                ParameterTy::Input | ParameterTy::InputTrig => {
+                    for attribute in attributes {
+                        let value = (inputs.len(), attribute.value.clone());
+                        if attribute.kind == "default" {
+                            input_defaults.push(value);
+                        } else {
+                            input_dezippers.push(value);
+                        }
+                    }
+                    inputs.push(parameter.ident.clone())
+                }
+                _ if attributes.next().is_some() => {
+                    return Err(syn::Error::new(
+                        param.span(),
+                        "Input attributes are only supported on inputs",
+                    ));
+                }
                 ParameterTy::Output | ParameterTy::OutputTrig => {
                     outputs.push(parameter.ident.clone())
                 }
@@ -629,6 +748,8 @@ fn parse_process_fn(impl_item_fn: &ImplItemFn) -> Result<ProcessData> {
         inputs,
         outputs,
         parameters,
+        input_defaults,
+        input_dezippers,
     })
 }
 